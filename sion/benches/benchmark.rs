@@ -1,38 +1,80 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use sion::dem_tile::DemTile;
+use rayon::ThreadPoolBuilder;
 use sion::grayscale8_bitmap::Grayscale8Bitmap;
-use sion::hillshading::igor_hillshading_opt1::hillshade as hillshade_opt1;
-use sion::hillshading::igor_hillshading_orig::hillshade as hillshade_orig;
+use sion::hillshading::hillshader::HillshaderRegistry;
+use sion::hillshading::igor_hillshading_simd;
 use sion::hillshading::parameters::HillshadingParameters;
+use sion::testing::SyntheticTerrain;
+use wide::f32x8;
 
-fn benchmark_igor_hillshade_orig(c: &mut Criterion) {
-    let dem = DemTile::from_hgt_file("tests/data/N46E006.hgt");
+/// Benchmarks each of the registered hillshaders (`cargo bench --
+/// igor_opt1` runs just the one).
+fn benchmark_hillshaders(c: &mut Criterion) {
+    let dem = SyntheticTerrain::mountains(6, 46, 3601).build();
     let mut bitmap = Grayscale8Bitmap::new(dem.size as u16, dem.size as u16);
     let parameters = HillshadingParameters::default();
 
-    c.bench_function("igor_hillshade_orig", |b| {
-        b.iter(|| {
-            hillshade_orig(
-                black_box(&dem),
-                black_box(&parameters),
-                black_box(&mut bitmap),
-            )
-        })
-    });
+    for hillshader in HillshaderRegistry::new().iter() {
+        c.bench_function(hillshader.name(), |b| {
+            b.iter(|| {
+                hillshader.hillshade(
+                    black_box(&dem),
+                    black_box(&parameters),
+                    black_box(&mut bitmap),
+                )
+            })
+        });
+    }
 }
 
-fn benchmark_igor_hillshade_opt1(c: &mut Criterion) {
-    let dem = DemTile::from_hgt_file("tests/data/N46E006.hgt");
+/// Benchmarks each of the registered hillshaders shading in parallel, on
+/// all the CPU cores.
+fn benchmark_parallel_hillshaders(c: &mut Criterion) {
+    let dem = SyntheticTerrain::mountains(6, 46, 3601).build();
     let mut bitmap = Grayscale8Bitmap::new(dem.size as u16, dem.size as u16);
     let parameters = HillshadingParameters::default();
+    let pool = ThreadPoolBuilder::new().build().unwrap();
+
+    for hillshader in HillshaderRegistry::new().iter() {
+        let name = format!("{}_parallel", hillshader.name());
+        c.bench_function(&name, |b| {
+            b.iter(|| {
+                hillshader.hillshade_parallel(
+                    black_box(&dem),
+                    black_box(&parameters),
+                    black_box(&mut bitmap),
+                    &pool,
+                )
+            })
+        });
+    }
+}
 
-    c.bench_function("igor_hillshade_opt1", |b| {
+/// Benchmarks the SIMD arctangent approximations against the scalar
+/// arctangents, for a row's worth of values.
+fn benchmark_arctangents(c: &mut Criterion) {
+    let values: Vec<f32> = (0..3600).map(|i| (i as f32 - 1800.) / 7.).collect();
+
+    c.bench_function("atan2_scalar", |b| {
+        b.iter(|| {
+            values
+                .iter()
+                .zip(values.iter().rev())
+                .map(|(y, x)| y.atan2(*x))
+                .sum::<f32>()
+        })
+    });
+    c.bench_function("atan2_simd", |b| {
         b.iter(|| {
-            hillshade_opt1(
-                black_box(&dem),
-                black_box(&parameters),
-                black_box(&mut bitmap),
-            )
+            values
+                .chunks_exact(8)
+                .zip(values.rchunks_exact(8))
+                .map(|(y, x)| {
+                    let y = f32x8::from(<[f32; 8]>::try_from(y).unwrap());
+                    let x = f32x8::from(<[f32; 8]>::try_from(x).unwrap());
+                    igor_hillshading_simd::atan2(y, x)
+                })
+                .fold(f32x8::ZERO, |sum, angles| sum + angles)
         })
     });
 }
@@ -44,7 +86,8 @@ fn criterion_config() -> Criterion {
 criterion_group! {
     name = benches;
     config = criterion_config();
-    targets = benchmark_igor_hillshade_orig, benchmark_igor_hillshade_opt1
+    targets = benchmark_hillshaders, benchmark_parallel_hillshaders,
+        benchmark_arctangents
 }
 
 criterion_main!(benches);
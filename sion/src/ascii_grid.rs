@@ -0,0 +1,571 @@
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+use crate::errors::SionError;
+use crate::grid_spacing::GridGeometry;
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// The NODATA_value written into the exported grids.
+const EXPORT_NO_DATA_VALUE: i32 = -9999;
+
+/// A georeferenced elevation raster in the ESRI ASCII grid (`.asc`) format,
+/// with the heights rounded to whole meters and the NODATA cells set to
+/// `NO_DATA_HEIGHT`. The coordinates are in the grid's own reference system
+/// (e.g. meters for the national projected DEMs).
+#[derive(Debug, Clone)]
+pub struct AsciiGrid {
+    pub columns: usize,
+    pub rows: usize,
+    /// The x coordinate of the lower-left corner of the lower-left cell.
+    pub x_lower_left: f64,
+    /// The y coordinate of the lower-left corner of the lower-left cell.
+    pub y_lower_left: f64,
+    pub cell_size: f64,
+    heights: Vec<i16>,
+}
+
+/// The header values, before they are validated.
+#[derive(Default)]
+struct Header {
+    columns: Option<usize>,
+    rows: Option<usize>,
+    x_corner: Option<f64>,
+    y_corner: Option<f64>,
+    x_center: Option<f64>,
+    y_center: Option<f64>,
+    cell_size: Option<f64>,
+    no_data_value: Option<f64>,
+}
+
+impl AsciiGrid {
+    /// Creates a grid from the heights listed row by row, starting with the
+    /// northernmost row.
+    pub fn new(
+        columns: usize,
+        rows: usize,
+        x_lower_left: f64,
+        y_lower_left: f64,
+        cell_size: f64,
+        heights: Vec<i16>,
+    ) -> AsciiGrid {
+        if heights.len() != columns * rows {
+            panic!("The number of heights does not match the grid size");
+        }
+
+        AsciiGrid {
+            columns,
+            rows,
+            x_lower_left,
+            y_lower_left,
+            cell_size,
+            heights,
+        }
+    }
+
+    /// Creates a grid from the DEM tile, georeferenced by the tile's grid
+    /// geometry (in degrees for a geographic grid). The tile's cells are
+    /// centered on the tile's grid points, so the grid extends half a cell
+    /// beyond the tile's edges. A Web Mercator tile has no single cell size
+    /// and is refused.
+    pub fn from_dem_tile(tile: &DemTile) -> Result<AsciiGrid, SionError> {
+        let (west, north, cell_size) = match GridGeometry::of_tile(tile) {
+            GridGeometry::Geographic {
+                north_lat,
+                west_lon,
+                cell_size,
+            } => (west_lon, north_lat, cell_size),
+            GridGeometry::Projected {
+                west_x,
+                north_y,
+                cell_size,
+            } => (west_x, north_y, cell_size),
+            GridGeometry::WebMercator { .. } => {
+                return Err(SionError::UnsupportedTileGrid {
+                    lon: tile.lon,
+                    lat: tile.lat,
+                    reason: "a Web Mercator tile cannot be an ASCII grid"
+                        .to_string(),
+                })
+            }
+        };
+
+        let half_cell = cell_size / 2.;
+        Ok(AsciiGrid::new(
+            tile.size,
+            tile.size,
+            west - half_cell,
+            north - (tile.size - 1) as f64 * cell_size - half_cell,
+            cell_size,
+            tile.heights(),
+        ))
+    }
+
+    /// Reads the grid from an `.asc` file. Both the `xllcorner`/`yllcorner`
+    /// and the `xllcenter`/`yllcenter` headers are supported, the
+    /// `NODATA_value` header is optional.
+    pub fn from_asc_file(file: &str) -> Result<AsciiGrid, SionError> {
+        let text = fs::read_to_string(file).map_err(|error| {
+            SionError::CannotOpenFile {
+                file: file.to_string(),
+                source: error,
+            }
+        })?;
+
+        AsciiGrid::parse(&text).map_err(|reason| SionError::InvalidAsciiGrid {
+            file: file.to_string(),
+            reason,
+        })
+    }
+
+    fn parse(text: &str) -> Result<AsciiGrid, String> {
+        let mut tokens = text.split_ascii_whitespace().peekable();
+        let mut header = Header::default();
+
+        while let Some(key) = tokens.next_if(|token| {
+            token.starts_with(|c: char| c.is_ascii_alphabetic())
+        }) {
+            let value = tokens
+                .next()
+                .ok_or_else(|| format!("missing value of '{}'", key))?;
+            header.set(key, value)?;
+        }
+
+        let columns = header.columns.ok_or("missing 'ncols'")?;
+        let rows = header.rows.ok_or("missing 'nrows'")?;
+        let cell_size = header.cell_size.ok_or("missing 'cellsize'")?;
+        if columns == 0 || rows == 0 {
+            return Err("the grid is empty".to_string());
+        }
+        if cell_size.is_nan() || cell_size <= 0. {
+            return Err(format!("invalid cell size: {}", cell_size));
+        }
+
+        let half_cell = cell_size / 2.;
+        let x_lower_left = match (header.x_corner, header.x_center) {
+            (Some(x), None) => x,
+            (None, Some(x)) => x - half_cell,
+            _ => return Err("expected one of 'xllcorner', 'xllcenter'".into()),
+        };
+        let y_lower_left = match (header.y_corner, header.y_center) {
+            (Some(y), None) => y,
+            (None, Some(y)) => y - half_cell,
+            _ => return Err("expected one of 'yllcorner', 'yllcenter'".into()),
+        };
+
+        let min_height = (NO_DATA_HEIGHT + 1) as f64;
+        let heights = tokens
+            .map(|token| {
+                let value = token
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid height: '{}'", token))?;
+                if Some(value) == header.no_data_value || value.is_nan() {
+                    Ok(NO_DATA_HEIGHT)
+                } else {
+                    Ok(value.round().clamp(min_height, i16::MAX as f64) as i16)
+                }
+            })
+            .collect::<Result<Vec<i16>, String>>()?;
+
+        if heights.len() != columns * rows {
+            return Err(format!(
+                "expected {} heights ({}x{}), found {}",
+                columns * rows,
+                columns,
+                rows,
+                heights.len()
+            ));
+        }
+
+        Ok(AsciiGrid::new(
+            columns,
+            rows,
+            x_lower_left,
+            y_lower_left,
+            cell_size,
+            heights,
+        ))
+    }
+
+    /// Writes the grid into an `.asc` file (with `xllcorner`/`yllcorner`
+    /// headers and -9999 as the NODATA_value).
+    pub fn write_asc_file(&self, file: &str) -> Result<(), SionError> {
+        let cannot_write = |error| SionError::CannotWriteFile {
+            file: file.to_string(),
+            source: error,
+        };
+
+        let mut writer =
+            BufWriter::new(File::create(file).map_err(cannot_write)?);
+
+        write!(
+            writer,
+            "ncols {}\nnrows {}\nxllcorner {}\nyllcorner {}\ncellsize {}\n\
+            NODATA_value {}\n",
+            self.columns,
+            self.rows,
+            self.x_lower_left,
+            self.y_lower_left,
+            self.cell_size,
+            EXPORT_NO_DATA_VALUE
+        )
+        .map_err(cannot_write)?;
+
+        for row in self.heights.chunks(self.columns) {
+            let line: Vec<String> = row
+                .iter()
+                .map(|&height| {
+                    if height == NO_DATA_HEIGHT {
+                        EXPORT_NO_DATA_VALUE.to_string()
+                    } else {
+                        height.to_string()
+                    }
+                })
+                .collect();
+            writeln!(writer, "{}", line.join(" ")).map_err(cannot_write)?;
+        }
+
+        writer.flush().map_err(cannot_write)
+    }
+
+    pub fn height_at(&self, x: usize, y: usize) -> i16 {
+        self.heights[y * self.columns + x]
+    }
+
+    /// Returns the heights row by row, starting with the northernmost row.
+    pub fn heights(&self) -> &[i16] {
+        &self.heights
+    }
+
+    /// Converts a square grid into a DEM tile (e.g. to hillshade it), use
+    /// `to_dem_tiles` for a rectangular grid. The grid's coordinates are
+    /// taken as projected, in the heights' units: the tile gets the
+    /// `GridGeometry::Projected` geometry of the grid's cells, and its
+    /// (geographic) coordinates need to be provided.
+    pub fn to_dem_tile(
+        &self,
+        lon: i16,
+        lat: i16,
+    ) -> Result<DemTile, SionError> {
+        if self.columns != self.rows {
+            return Err(SionError::UnsupportedTileGrid {
+                lon,
+                lat,
+                reason: format!(
+                    "the {}x{} ASCII grid is not square",
+                    self.columns, self.rows
+                ),
+            });
+        }
+
+        Ok(self.crop_to_dem_tile(lon, lat, 0, 0, self.columns))
+    }
+
+    /// Splits the grid into square DEM tiles of `tile_size` cells per side
+    /// (or of the grid's shorter side, if smaller), e.g. to hillshade a
+    /// rectangular grid. The tiles overlap by two cells, so the shaded
+    /// interiors of the tiles (all but their edge pixels) join without
+    /// gaps, and the last tiles of each row and column are moved back to
+    /// end at the grid's edges. Returns the tiles row by row together with
+    /// the cell coordinates of their north-west cells in the grid; like
+    /// `to_dem_tile`, each tile gets the projected geometry of its cells.
+    pub fn to_dem_tiles(
+        &self,
+        lon: i16,
+        lat: i16,
+        tile_size: usize,
+    ) -> Vec<(usize, usize, DemTile)> {
+        let size = tile_size.min(self.columns).min(self.rows);
+        let mut tiles = Vec::new();
+        if size == 0 {
+            return tiles;
+        }
+
+        for y in tile_offsets(self.rows, size) {
+            for x in tile_offsets(self.columns, size) {
+                tiles.push((x, y, self.crop_to_dem_tile(lon, lat, x, y, size)));
+            }
+        }
+
+        tiles
+    }
+
+    /// The DEM tile of the `size`x`size` cells starting at the `x`, `y`
+    /// cell.
+    fn crop_to_dem_tile(
+        &self,
+        lon: i16,
+        lat: i16,
+        x: usize,
+        y: usize,
+        size: usize,
+    ) -> DemTile {
+        let heights: Vec<i16> = self
+            .heights
+            .chunks(self.columns)
+            .skip(y)
+            .take(size)
+            .flat_map(|row| &row[x..x + size])
+            .copied()
+            .collect();
+
+        let half_cell = self.cell_size / 2.;
+        let north_edge = self.y_lower_left + self.rows as f64 * self.cell_size;
+        DemTile::from_heights(lon, lat, size, &heights).with_grid_geometry(
+            GridGeometry::Projected {
+                west_x: self.x_lower_left
+                    + x as f64 * self.cell_size
+                    + half_cell,
+                north_y: north_edge - y as f64 * self.cell_size - half_cell,
+                cell_size: self.cell_size,
+            },
+        )
+    }
+}
+
+/// The offsets of the tiles of `size` cells covering the `length` cells,
+/// overlapping by two cells (or one, for tiles too small for that).
+fn tile_offsets(length: usize, size: usize) -> Vec<usize> {
+    let step = size.saturating_sub(2).max(1);
+    let mut offsets: Vec<usize> = (0..length - size).step_by(step).collect();
+    offsets.push(length - size);
+    offsets
+}
+
+impl Header {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid value of '{}': '{}'", key, value);
+        let count = || value.parse::<usize>().map_err(|_| invalid());
+        let number = || value.parse::<f64>().map_err(|_| invalid());
+
+        match key.to_ascii_lowercase().as_str() {
+            "ncols" => self.columns = Some(count()?),
+            "nrows" => self.rows = Some(count()?),
+            "xllcorner" => self.x_corner = Some(number()?),
+            "yllcorner" => self.y_corner = Some(number()?),
+            "xllcenter" => self.x_center = Some(number()?),
+            "yllcenter" => self.y_center = Some(number()?),
+            "cellsize" => self.cell_size = Some(number()?),
+            "nodata_value" => self.no_data_value = Some(number()?),
+            _ => return Err(format!("unknown header '{}'", key)),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grayscale8_bitmap::Grayscale8Bitmap;
+    use crate::hillshading::lambertian_hillshading::hillshade;
+    use crate::hillshading::parameters::HillshadingParameters;
+    use rstest::rstest;
+
+    fn write_test_file(file_name: &str, text: &str) -> String {
+        let dir = "target/debug/ascii_grid_tests";
+        fs::create_dir_all(dir).unwrap();
+        let path = format!("{}/{}", dir, file_name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_grid_with_corner_header() {
+        let file = write_test_file(
+            "corner.asc",
+            "ncols 3\nNROWS 2\nxllcorner 2600000\nyllcorner 1200000.5\n\
+            cellsize 0.5\nNODATA_value -9999\n\
+            456.2 456.7 -9999\n-3.5 0 1000\n",
+        );
+
+        let grid = AsciiGrid::from_asc_file(&file).unwrap();
+        assert_eq!((grid.columns, grid.rows), (3, 2));
+        assert_eq!(grid.x_lower_left, 2600000.);
+        assert_eq!(grid.y_lower_left, 1200000.5);
+        assert_eq!(grid.cell_size, 0.5);
+        assert_eq!(grid.heights(), [456, 457, NO_DATA_HEIGHT, -4, 0, 1000]);
+        assert_eq!(grid.height_at(2, 1), 1000);
+        match grid.to_dem_tile(7, 46) {
+            Err(error @ SionError::UnsupportedTileGrid { .. }) => assert!(
+                error.to_string().contains("3x2 ASCII grid is not square"),
+                "{}",
+                error
+            ),
+            _ => panic!("Expected an UnsupportedTileGrid error"),
+        }
+    }
+
+    #[test]
+    fn read_grid_with_center_header_and_no_nodata() {
+        let file = write_test_file(
+            "center.asc",
+            "ncols 2\nnrows 2\nxllcenter 10\nyllcenter 20\ncellsize 2\n\
+            1 2\n3 4",
+        );
+
+        let grid = AsciiGrid::from_asc_file(&file).unwrap();
+        assert_eq!((grid.x_lower_left, grid.y_lower_left), (9., 19.));
+
+        let tile = grid.to_dem_tile(7, 46).unwrap();
+        assert_eq!((tile.lon, tile.lat, tile.size), (7, 46, 2));
+        assert_eq!(tile.heights(), [1, 2, 3, 4]);
+        assert_eq!(
+            tile.grid_geometry(),
+            Some(GridGeometry::Projected {
+                west_x: 10.,
+                north_y: 22.,
+                cell_size: 2.,
+            })
+        );
+    }
+
+    #[test]
+    fn rectangular_grid_is_split_into_overlapping_tiles() {
+        // 7 columns, 4 rows, each height being the cell's index
+        let heights: Vec<i16> = (0..7 * 4).collect();
+        let grid = AsciiGrid::new(7, 4, 100., 200., 10., heights);
+
+        let tiles = grid.to_dem_tiles(7, 46, 4);
+        let offsets: Vec<(usize, usize)> =
+            tiles.iter().map(|&(x, y, _)| (x, y)).collect();
+        assert_eq!(offsets, [(0, 0), (2, 0), (3, 0)]);
+
+        let (_, _, last) = &tiles[2];
+        assert_eq!(last.size, 4);
+        assert_eq!(last.heights()[..4], [3, 4, 5, 6]);
+        assert_eq!(last.heights()[12..], [24, 25, 26, 27]);
+        assert_eq!(
+            last.grid_geometry(),
+            Some(GridGeometry::Projected {
+                west_x: 135.,
+                north_y: 235.,
+                cell_size: 10.,
+            })
+        );
+
+        // the tiles are no larger than the grid's shorter side
+        let tiles = grid.to_dem_tiles(7, 46, 5);
+        assert_eq!(tiles.len(), 3);
+        assert!(tiles.iter().all(|(_, _, tile)| tile.size == 4));
+    }
+
+    #[rstest]
+    #[case(
+        "nrows 1\nxllcorner 0\nyllcorner 0\ncellsize 1\n1",
+        "missing 'ncols'"
+    )]
+    #[case(
+        "ncols 1\nnrows 1\nxllcorner 0\nyllcorner 0\n1",
+        "missing 'cellsize'"
+    )]
+    #[case(
+        "ncols 1\nnrows 1\nyllcorner 0\ncellsize 1\n1",
+        "expected one of 'xllcorner', 'xllcenter'"
+    )]
+    #[case(
+        "ncols 1\nnrows 1\nxllcorner 0\nxllcenter 0\nyllcorner 0\n\
+        cellsize 1\n1",
+        "expected one of 'xllcorner', 'xllcenter'"
+    )]
+    #[case(
+        "ncols -1\nnrows 1\nxllcorner 0\nyllcorner 0\ncellsize 1\n1",
+        "invalid value of 'ncols': '-1'"
+    )]
+    #[case(
+        "ncols 1\nnrows 1\nxllcorner 0\nyllcorner 0\ncellsize 0\n1",
+        "invalid cell size: 0"
+    )]
+    #[case(
+        "ncols 1\nnrows 1\nxllcorner 0\nyllcorner 0\ncellsize 1\ndx 1\n1",
+        "unknown header 'dx'"
+    )]
+    #[case(
+        "ncols 2\nnrows 1\nxllcorner 0\nyllcorner 0\ncellsize 1\n1",
+        "expected 2 heights (2x1), found 1"
+    )]
+    #[case(
+        "ncols 2\nnrows 1\nxllcorner 0\nyllcorner 0\ncellsize 1\n1 2m",
+        "invalid height: '2m'"
+    )]
+    fn invalid_grids_are_rejected(#[case] text: &str, #[case] reason: &str) {
+        match AsciiGrid::parse(text) {
+            Err(actual_reason) => assert_eq!(actual_reason, reason),
+            _ => panic!("Expected the grid to be rejected"),
+        }
+    }
+
+    #[test]
+    fn projected_grid_is_shaded_with_its_cell_size() {
+        // a 45° slope facing west: the heights rise by the 2 m cell size
+        // with each column
+        let heights: Vec<i16> = (0..11 * 11)
+            .map(|index| 100 + 2 * (index % 11) as i16)
+            .collect();
+        let grid = AsciiGrid::new(11, 11, 2600000., 1200000., 2., heights);
+        let tile = grid.to_dem_tile(7, 46).unwrap();
+
+        let mut bitmap = Grayscale8Bitmap::new(11, 11);
+        hillshade(&tile, &HillshadingParameters::default(), &mut bitmap);
+
+        // 1 + 254 * (sin 45° cos 45° + cos 45° sin 45° cos 45°)
+        let shade = bitmap.get_pixel(5, 5);
+        assert!((217..=219).contains(&shade), "{}", shade);
+    }
+
+    #[test]
+    fn dem_tile_survives_export_and_import() {
+        let tile = DemTile::from_heights(
+            -8,
+            45,
+            3,
+            &[1, 2, 3, NO_DATA_HEIGHT, -5, 6, 7, 8, 9999],
+        );
+        let grid = AsciiGrid::from_dem_tile(&tile).unwrap();
+        assert_eq!(grid.cell_size, 0.5);
+        assert_eq!((grid.x_lower_left, grid.y_lower_left), (-8.25, 44.75));
+
+        let dir = "target/debug/ascii_grid_tests";
+        fs::create_dir_all(dir).unwrap();
+        let file = format!("{}/S45W008.asc", dir);
+        grid.write_asc_file(&file).unwrap();
+
+        let read_grid = AsciiGrid::from_asc_file(&file).unwrap();
+        assert_eq!(read_grid.x_lower_left, -8.25);
+        assert_eq!(read_grid.y_lower_left, 44.75);
+        assert_eq!(read_grid.cell_size, 0.5);
+        assert_eq!(read_grid.heights(), tile.heights());
+    }
+
+    #[test]
+    fn grid_is_georeferenced_by_the_tile_geometry() {
+        let geographic = DemTile::from_heights(7, 46, 2, &[1, 2, 3, 4])
+            .with_grid_geometry(GridGeometry::Geographic {
+                north_lat: 46.75,
+                west_lon: 7.25,
+                cell_size: 0.5,
+            });
+        let grid = AsciiGrid::from_dem_tile(&geographic).unwrap();
+        assert_eq!(grid.cell_size, 0.5);
+        assert_eq!((grid.x_lower_left, grid.y_lower_left), (7., 46.));
+
+        let heights: Vec<i16> = (0..5 * 3).collect();
+        let projected = AsciiGrid::new(5, 3, 2600000., 1200000., 2., heights);
+        for (x, y, tile) in projected.to_dem_tiles(7, 46, 3) {
+            let grid = AsciiGrid::from_dem_tile(&tile).unwrap();
+            assert_eq!(grid.cell_size, 2.);
+            assert_eq!(grid.x_lower_left, 2600000. + 2. * x as f64);
+            assert_eq!(grid.y_lower_left, 1200000. - 2. * y as f64);
+        }
+
+        let web = DemTile::from_heights(7, 46, 2, &[1, 2, 3, 4])
+            .with_grid_geometry(GridGeometry::WebMercator {
+                north_row: 0.5,
+                west_column: 0.5,
+                world_size: 2.,
+            });
+        assert!(matches!(
+            AsciiGrid::from_dem_tile(&web),
+            Err(SionError::UnsupportedTileGrid { .. })
+        ));
+    }
+}
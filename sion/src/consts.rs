@@ -4,4 +4,5 @@ pub const INCHES_PER_METER: f32 = 39.3701;
 
 pub const EARTH_RADIUS_METERS: f32 = 6371000.;
 
-pub const EARTH_CIRCUMFERENCE_METERS: f32 = 2. * std::f32::consts::PI * EARTH_RADIUS_METERS;
+pub const EARTH_CIRCUMFERENCE_METERS: f32 =
+    2. * std::f32::consts::PI * EARTH_RADIUS_METERS;
@@ -1,174 +1,405 @@
+use crate::dem_tile_id::DemTileId;
 use crate::errors::SionError;
-use byteorder::{LittleEndian, ReadBytesExt};
+use crate::grid_spacing::GridGeometry;
+use memmap2::Mmap;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::os::windows::fs::MetadataExt;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
 
+/// The height value marking the cells without data (the SRTM voids).
+pub const NO_DATA_HEIGHT: i16 = i16::MIN;
+
+/// The byte order in which a tile's heights are stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeightsByteOrder {
+    /// Used by the HGT files.
+    BigEndian,
+    /// Used by the XTH files.
+    LittleEndian,
+}
+
+/// The storage holding the tile's raw height bytes.
+enum HeightsData {
+    InMemory(Box<[u8]>),
+    /// The heights are served directly from the memory-mapped file, so
+    /// opening a tile costs almost no RAM or startup time.
+    MemoryMapped(Mmap),
+}
+
+impl HeightsData {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            HeightsData::InMemory(data) => data,
+            HeightsData::MemoryMapped(mmap) => mmap,
+        }
+    }
+}
+
 pub struct DemTile {
     pub lon: i16,
     pub lat: i16,
     pub size: usize,
-    data: Box<[u8]>,
+    byte_order: HeightsByteOrder,
+    data: HeightsData,
+    grid: Option<GridGeometry>,
+}
+
+impl Clone for DemTile {
+    /// Clones the tile into memory (a memory-mapped tile's clone holds a copy
+    /// of the heights, it does not share the mapping).
+    fn clone(&self) -> Self {
+        DemTile {
+            lon: self.lon,
+            lat: self.lat,
+            size: self.size,
+            byte_order: self.byte_order,
+            data: HeightsData::InMemory(self.data.bytes().into()),
+            grid: self.grid,
+        }
+    }
 }
 
 impl DemTile {
+    /// Creates a new tile from big-endian height bytes.
     pub fn new(lon: i16, lat: i16, size: usize, data: Vec<u8>) -> DemTile {
         DemTile {
             lon,
             lat,
             size,
-            data: data.into_boxed_slice(),
+            byte_order: HeightsByteOrder::BigEndian,
+            data: HeightsData::InMemory(data.into_boxed_slice()),
+            grid: None,
         }
     }
 
-    pub fn from_hgt_file(file: &str) -> DemTile {
-        let tile_name = Path::new(file)
-            .file_stem()
-            .and_then(|stem| stem.to_str())
-            .unwrap();
-        let (lon, lat) = DemTile::parse_tile_name(tile_name).unwrap();
-
-        let file = File::open(file);
-        match file {
-            Ok(file) => {
-                // get the file size
-                let metadata = file.metadata().unwrap();
-                let total_heights_count = metadata.file_size() / 2;
-
-                let tile_size = (total_heights_count as f64).sqrt() as u64;
-                if tile_size * tile_size == total_heights_count {
-                    // read the whole file into a byte array
-                    let mut reader = BufReader::new(file);
-
-                    let mut byte_array: Vec<u8> =
-                        vec![0; metadata.file_size() as usize];
-                    reader
-                        .read_exact(&mut byte_array)
-                        .expect("Failed to read the HGT file.");
-
-                    DemTile::new(lon, lat, tile_size as usize, byte_array)
-                } else {
-                    panic!("The HGT file does not contain a square number of heights");
-                }
+    /// Creates a new tile from heights listed row by row, starting with the
+    /// northernmost row.
+    pub fn from_heights(
+        lon: i16,
+        lat: i16,
+        size: usize,
+        heights: &[i16],
+    ) -> DemTile {
+        if heights.len() != size * size {
+            panic!("The number of heights does not match the tile size");
+        }
+
+        let data = heights.iter().flat_map(|h| h.to_be_bytes()).collect();
+        DemTile::new(lon, lat, size, data)
+    }
+
+    /// Loads a DEM tile from an HGT file (big-endian heights). The tile's
+    /// coordinates are taken from the file name (e.g. `N46E006.hgt`).
+    pub fn from_hgt_file(file: &str) -> Result<DemTile, SionError> {
+        let (lon, lat) = DemTile::tile_coords_from_file_name(file)?;
+        let (tile_size, byte_array) = DemTile::read_heights_file(file)?;
+
+        Ok(DemTile::new(lon, lat, tile_size, byte_array))
+    }
+
+    /// Loads a DEM tile from an XTH file (little-endian heights). The tile's
+    /// coordinates are taken from the file name (e.g. `N46E006.xth`).
+    pub fn from_xth_file(file: &str) -> Result<DemTile, SionError> {
+        let (lon, lat) = DemTile::tile_coords_from_file_name(file)?;
+        let (tile_size, byte_array) = DemTile::read_heights_file(file)?;
+
+        Ok(DemTile {
+            lon,
+            lat,
+            size: tile_size,
+            byte_order: HeightsByteOrder::LittleEndian,
+            data: HeightsData::InMemory(byte_array.into_boxed_slice()),
+            grid: None,
+        })
+    }
+
+    /// Opens an HGT file as a memory-mapped tile. The heights are not read
+    /// into memory, they are served from the map on each access.
+    pub fn map_hgt_file(file: &str) -> Result<DemTile, SionError> {
+        DemTile::map_heights_file(file, HeightsByteOrder::BigEndian)
+    }
+
+    /// Opens an XTH file as a memory-mapped tile. The heights are not read
+    /// into memory, they are served from the map on each access.
+    pub fn map_xth_file(file: &str) -> Result<DemTile, SionError> {
+        DemTile::map_heights_file(file, HeightsByteOrder::LittleEndian)
+    }
+
+    fn map_heights_file(
+        file_name: &str,
+        byte_order: HeightsByteOrder,
+    ) -> Result<DemTile, SionError> {
+        let (lon, lat) = DemTile::tile_coords_from_file_name(file_name)?;
+
+        let cannot_open = |error| SionError::CannotOpenFile {
+            file: file_name.to_string(),
+            source: error,
+        };
+
+        let file = File::open(file_name).map_err(cannot_open)?;
+        let file_size = file.metadata().map_err(cannot_open)?.len();
+        let tile_size =
+            DemTile::tile_size_from_file_size(file_name, file_size)?;
+
+        // SAFETY: the DEM files are treated as read-only data. Modifying or
+        // truncating a file while it is mapped is undefined behavior, which is
+        // the usual (and documented) contract of memory-mapped files.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(cannot_open)?;
+
+        // the map covers the file as it is now, which is shorter than the
+        // heights if the file was truncated after its size was read
+        let heights_bytes = (tile_size * tile_size * 2) as u64;
+        if (mmap.len() as u64) < heights_bytes {
+            return Err(SionError::TruncatedRead {
+                file: file_name.to_string(),
+                expected_bytes: heights_bytes,
+                actual_bytes: mmap.len() as u64,
+            });
+        }
+
+        Ok(DemTile {
+            lon,
+            lat,
+            size: tile_size,
+            byte_order,
+            data: HeightsData::MemoryMapped(mmap),
+            grid: None,
+        })
+    }
+
+    /// Sets where the tile's cells lie, for the tiles not following the HGT
+    /// convention of `size - 1` intervals spanning a degree (e.g. the
+    /// Copernicus tiles, whose cells do not reach the tile's edges).
+    pub fn with_grid_geometry(mut self, grid: GridGeometry) -> DemTile {
+        self.grid = Some(grid);
+        self
+    }
+
+    /// The geometry set by `with_grid_geometry`, `None` for a regular tile
+    /// (see `GridGeometry::of_tile`).
+    pub fn grid_geometry(&self) -> Option<GridGeometry> {
+        self.grid
+    }
+
+    /// Returns `true` if the tile's edge rows and columns lie on the whole
+    /// degrees, i.e. they are shared with the neighbouring tiles.
+    pub fn shares_edges(&self) -> bool {
+        match GridGeometry::of_tile(self) {
+            GridGeometry::Geographic {
+                north_lat,
+                west_lon,
+                cell_size,
+            } => {
+                self.size >= 2
+                    && north_lat == self.lat as f64 + 1.
+                    && west_lon == self.lon as f64
+                    && cell_size == 1. / (self.size - 1) as f64
             }
-            Err(error) => {
-                // raise an error if the file cannot be opened
-                panic!("Problem opening the HGT file: {:?}", error);
+            GridGeometry::WebMercator { .. }
+            | GridGeometry::Projected { .. } => false,
+        }
+    }
+
+    /// Returns `true` if the tile's heights are served from a memory-mapped
+    /// file.
+    pub fn is_memory_mapped(&self) -> bool {
+        matches!(self.data, HeightsData::MemoryMapped(_))
+    }
+
+    /// Writes the tile into an HGT file (big-endian heights).
+    pub fn write_hgt(&self, file: &str) -> Result<(), SionError> {
+        self.write_heights_file(file, HeightsByteOrder::BigEndian)
+    }
+
+    /// Writes the tile into an XTH file (little-endian heights).
+    pub fn write_xth(&self, file: &str) -> Result<(), SionError> {
+        self.write_heights_file(file, HeightsByteOrder::LittleEndian)
+    }
+
+    fn write_heights_file(
+        &self,
+        file_name: &str,
+        byte_order: HeightsByteOrder,
+    ) -> Result<(), SionError> {
+        let cannot_write = |error| SionError::CannotWriteFile {
+            file: file_name.to_string(),
+            source: error,
+        };
+
+        let file = File::create(file_name).map_err(cannot_write)?;
+        let mut writer = BufWriter::new(file);
+
+        if byte_order == self.byte_order {
+            // the heights are already encoded the way the file needs them
+            writer.write_all(self.data.bytes()).map_err(cannot_write)?;
+        } else {
+            for index in 0..self.size * self.size {
+                let height = self.height_at_index(index);
+                let height_bytes = match byte_order {
+                    HeightsByteOrder::BigEndian => height.to_be_bytes(),
+                    HeightsByteOrder::LittleEndian => height.to_le_bytes(),
+                };
+                writer.write_all(&height_bytes).map_err(cannot_write)?;
             }
         }
+
+        writer.flush().map_err(cannot_write)
     }
 
-    pub fn from_xth_file(file: &str) -> DemTile {
+    fn tile_coords_from_file_name(file: &str) -> Result<(i16, i16), SionError> {
         let tile_name = Path::new(file)
             .file_stem()
             .and_then(|stem| stem.to_str())
-            .unwrap();
-        let (lon, lat) = DemTile::parse_tile_name(tile_name).unwrap();
-
-        let file = File::open(file);
-        match file {
-            Ok(file) => {
-                // get the file size
-                let metadata = file.metadata().unwrap();
-                let total_heights_count = metadata.file_size() / 2;
-
-                let tile_size = (total_heights_count as f64).sqrt() as u64;
-                if tile_size * tile_size == total_heights_count {
-                    // read the whole file into a byte array
-                    let mut reader = BufReader::new(file);
-
-                    let mut byte_array: Vec<u8> =
-                        vec![0; metadata.file_size() as usize];
-
-                    // since XTH is little-endian, we need to read each height
-                    // as little-endian value and then write it as big-endian
-                    // into the tile's byte array
-                    let mut i = 0;
-                    while let Ok(height) = reader.read_i16::<LittleEndian>() {
-                        // now encode the height into a byte array
-                        byte_array[i] = (height >> 8) as u8;
-                        byte_array[i + 1] = height as u8;
-                        i += 2;
-                    }
-
-                    DemTile::new(lon, lat, tile_size as usize, byte_array)
-                } else {
-                    panic!("The HGT file does not contain a square number of heights");
-                }
+            .ok_or_else(|| SionError::InvalidTileName(file.to_string()))?;
+        DemTile::parse_tile_name(tile_name)
+    }
+
+    /// Reads the whole file containing 16-bit heights into a byte array and
+    /// returns it together with the tile size (the number of heights in each
+    /// row).
+    fn read_heights_file(
+        file_name: &str,
+    ) -> Result<(usize, Vec<u8>), SionError> {
+        let file = File::open(file_name).map_err(|error| {
+            SionError::CannotOpenFile {
+                file: file_name.to_string(),
+                source: error,
             }
-            Err(error) => {
-                // raise an error if the file cannot be opened
-                panic!("Problem opening the HGT file: {:?}", error);
+        })?;
+
+        let file_size = file
+            .metadata()
+            .map_err(|error| SionError::CannotOpenFile {
+                file: file_name.to_string(),
+                source: error,
+            })?
+            .len();
+
+        let tile_size =
+            DemTile::tile_size_from_file_size(file_name, file_size)?;
+
+        let mut byte_array: Vec<u8> = Vec::with_capacity(file_size as usize);
+        let read_result = BufReader::new(file)
+            .take(file_size)
+            .read_to_end(&mut byte_array);
+
+        match read_result {
+            Ok(_) if byte_array.len() as u64 == file_size => {
+                Ok((tile_size, byte_array))
             }
+            _ => Err(SionError::TruncatedRead {
+                file: file_name.to_string(),
+                expected_bytes: file_size,
+                actual_bytes: byte_array.len() as u64,
+            }),
         }
     }
 
+    /// Calculates the tile size (the number of heights in each row) from
+    /// the size of a file holding 16-bit heights.
+    pub(crate) fn tile_size_from_file_size(
+        file_name: &str,
+        file_size: u64,
+    ) -> Result<usize, SionError> {
+        let total_heights_count = file_size / 2;
+        let tile_size = (total_heights_count as f64).sqrt() as u64;
+        if file_size % 2 != 0 || tile_size * tile_size != total_heights_count {
+            return Err(SionError::NonSquareHeightsCount {
+                file: file_name.to_string(),
+                heights_count: total_heights_count,
+            });
+        }
+
+        Ok(tile_size as usize)
+    }
+
     pub fn height_at(&self, x: u16, y: u16) -> i16 {
-        let byte_offset = ((y as usize) * (self.size) + (x as usize)) << 1;
-        (self.data[byte_offset] as i16) << 8
-            | (self.data[byte_offset + 1] as i16)
+        self.height_at_index((y as usize) * (self.size) + (x as usize))
     }
 
-    pub fn height_at_index(&self, index: usize) -> i16 {
-        let byte_offset = index << 1;
-        (self.data[byte_offset] as i16) << 8
-            | (self.data[byte_offset + 1] as i16)
+    /// Returns all the tile's heights, row by row, starting with the
+    /// northernmost row.
+    pub fn heights(&self) -> Vec<i16> {
+        (0..self.size * self.size)
+            .map(|index| self.height_at_index(index))
+            .collect()
     }
 
-    pub fn parse_tile_name(tile_name: &str) -> Result<(i16, i16), SionError> {
-        fn parse_lat_sign(tile_name: &str) -> Result<i16, SionError> {
-            match tile_name.chars().nth(0) {
-                Some('N') => Ok(1),
-                Some('S') => Ok(-1),
-                _ => Err(SionError::new("Invalid tile name")),
-            }
+    /// Reads the row's heights (converted to the native byte order) into the
+    /// slice, which must have the tile's size.
+    pub fn read_row(&self, y: usize, heights: &mut [i16]) {
+        if heights.len() != self.size {
+            panic!("The number of heights does not match the tile size");
         }
 
-        fn parse_lat(tile_name: &str, lat_sign: i16) -> Result<i16, SionError> {
-            match tile_name[1..3].parse::<i16>() {
-                Ok(lat) => Ok(lat_sign * lat),
-                Err(_) => Err(SionError::new("Invalid tile name")),
+        let row_bytes = self.size << 1;
+        let data = &self.data.bytes()[y * row_bytes..(y + 1) * row_bytes];
+        let pairs = heights.iter_mut().zip(data.chunks_exact(2));
+        match self.byte_order {
+            HeightsByteOrder::BigEndian => pairs.for_each(|(height, bytes)| {
+                *height = i16::from_be_bytes([bytes[0], bytes[1]])
+            }),
+            HeightsByteOrder::LittleEndian => {
+                pairs.for_each(|(height, bytes)| {
+                    *height = i16::from_le_bytes([bytes[0], bytes[1]])
+                })
             }
         }
+    }
 
-        fn parse_lon_sign(
-            tile_name: &str,
-            lat: i16,
-        ) -> Result<(i16, i16), SionError> {
-            match tile_name.chars().nth(3) {
-                Some('E') => Ok((1, lat)),
-                Some('W') => Ok((-1, lat)),
-                _ => Err(SionError::new("Invalid tile name")),
-            }
+    /// Replaces all the tile's heights, keeping the tile's byte order. A
+    /// memory-mapped tile is moved into memory (the file is left untouched).
+    pub(crate) fn set_heights(&mut self, heights: &[i16]) {
+        if heights.len() != self.size * self.size {
+            panic!("The number of heights does not match the tile size");
         }
 
-        fn parse_lon(
-            tile_name: &str,
-            lon_sign: i16,
-            lat: i16,
-        ) -> Result<(i16, i16), SionError> {
-            match tile_name[4..7].parse::<i16>() {
-                Ok(lon) => Ok((lon_sign * lon, lat)),
-                Err(_) => Err(SionError::new("Invalid tile name")),
+        let data: Vec<u8> = match self.byte_order {
+            HeightsByteOrder::BigEndian => {
+                heights.iter().flat_map(|h| h.to_be_bytes()).collect()
             }
-        }
+            HeightsByteOrder::LittleEndian => {
+                heights.iter().flat_map(|h| h.to_le_bytes()).collect()
+            }
+        };
+        self.data = HeightsData::InMemory(data.into_boxed_slice());
+    }
 
-        match tile_name.len() {
-            7 => Ok(()),
-            _ => Err(SionError::new("Invalid tile name")),
+    pub fn height_at_index(&self, index: usize) -> i16 {
+        let byte_offset = index << 1;
+        let data = self.data.bytes();
+        let height_bytes = [data[byte_offset], data[byte_offset + 1]];
+
+        match self.byte_order {
+            HeightsByteOrder::BigEndian => i16::from_be_bytes(height_bytes),
+            HeightsByteOrder::LittleEndian => i16::from_le_bytes(height_bytes),
         }
-        .and_then(|_| parse_lat_sign(&tile_name))
-        .and_then(|lat_sign| parse_lat(&tile_name, lat_sign))
-        .and_then(|lat| parse_lon_sign(&tile_name, lat))
-        .and_then(|(lon_sign, lat)| parse_lon(&tile_name, lon_sign, lat))
+    }
+
+    /// Parses the tile name (e.g. `N46E006`) into the coordinates of the
+    /// tile's south-west corner, see `DemTileId`.
+    pub fn parse_tile_name(tile_name: &str) -> Result<(i16, i16), SionError> {
+        let tile_id: DemTileId = tile_name.parse()?;
+        Ok((tile_id.lon, tile_id.lat))
+    }
+
+    /// The tile's ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tile's coordinates are out of range.
+    pub fn tile_id(&self) -> DemTileId {
+        DemTileId::new(self.lon, self.lat)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::DemTile;
+    use super::{DemTile, NO_DATA_HEIGHT};
+    use crate::errors::SionError;
+    use crate::testing::SyntheticTerrain;
     use rstest::rstest;
+    use std::fs;
 
     #[rstest]
     #[case("N46E006", 6, 46)]
@@ -184,36 +415,223 @@ mod tests {
                 assert_eq!(lat, expected_lat);
             }
             Err(_) => {
-                assert!(false, "Failed to parse tile name");
+                panic!("Failed to parse tile name");
             }
         }
     }
 
     #[rstest]
-    #[case("X", "Invalid tile name")]
-    #[case("46E006", "Invalid tile name")]
-    #[case("SX6W123", "Invalid tile name")]
-    #[case("S16W1234", "Invalid tile name")]
+    #[case("X", "Invalid tile name: X")]
+    #[case("46E006", "Invalid tile name: 46E006")]
+    #[case("SX6W123", "Invalid tile name: SX6W123")]
+    #[case("S16W1234", "Invalid tile name: S16W1234")]
+    #[case("N91E006", "Invalid tile name: N91E006")]
     fn invalid_tile_names(
         #[case] file_name: &str,
         #[case] expected_error: &str,
     ) {
         match DemTile::parse_tile_name(file_name) {
             Ok(_) => {
-                assert!(false, "Should not have parsed tile name");
+                panic!("Should not have parsed tile name");
             }
             Err(error) => {
-                assert_eq!(error.message, expected_error);
+                assert!(matches!(error, SionError::InvalidTileName(_)));
+                assert_eq!(error.to_string(), expected_error);
             }
         }
     }
 
     #[test]
     fn read_from_file() {
-        let tile = DemTile::from_hgt_file("tests/data/N46E006.hgt");
+        let dir = "target/debug/dem_tile_tests";
+        fs::create_dir_all(dir).unwrap();
+        let terrain = SyntheticTerrain::mountains(6, 46, 1201);
+        let file = terrain.write_hgt(dir).unwrap();
+
+        let tile = DemTile::from_hgt_file(&file).unwrap();
         assert_eq!(tile.lon, 6);
         assert_eq!(tile.lat, 46);
-        assert_eq!(tile.size, 3601);
-        assert_eq!(tile.height_at(100, 100), 732);
+        assert_eq!(tile.size, 1201);
+        assert_eq!(tile.heights(), terrain.build().heights());
+    }
+
+    /// Writes the given bytes into a file in the `target/debug` directory
+    /// and returns the file's path.
+    fn write_test_file(file_name: &str, bytes: &[u8]) -> String {
+        let dir = "target/debug/dem_tile_tests";
+        fs::create_dir_all(dir).unwrap();
+        let path = format!("{}/{}", dir, file_name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_small_hgt_and_xth_files() {
+        let heights: [i16; 4] = [100, -200, 3000, -32768];
+
+        let hgt_bytes: Vec<u8> =
+            heights.iter().flat_map(|h| h.to_be_bytes()).collect();
+        let xth_bytes: Vec<u8> =
+            heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+
+        let hgt_tile =
+            DemTile::from_hgt_file(&write_test_file("S01W002.hgt", &hgt_bytes))
+                .unwrap();
+        let xth_tile =
+            DemTile::from_xth_file(&write_test_file("S01W002.xth", &xth_bytes))
+                .unwrap();
+
+        for tile in [hgt_tile, xth_tile] {
+            assert!(!tile.is_memory_mapped());
+            assert_eq!(tile.lon, -2);
+            assert_eq!(tile.lat, -1);
+            assert_eq!(tile.size, 2);
+            assert_eq!(tile.height_at(0, 0), 100);
+            assert_eq!(tile.height_at(1, 0), -200);
+            assert_eq!(tile.height_at(0, 1), 3000);
+            assert_eq!(tile.height_at_index(3), -32768);
+        }
+    }
+
+    #[test]
+    fn missing_file_cannot_be_opened() {
+        match DemTile::from_hgt_file("target/debug/missing/N10E010.hgt") {
+            Err(SionError::CannotOpenFile { file, .. }) => {
+                assert_eq!(file, "target/debug/missing/N10E010.hgt")
+            }
+            _ => panic!("Expected a CannotOpenFile error"),
+        }
+    }
+
+    #[test]
+    fn file_with_non_square_heights_count_is_rejected() {
+        let file = write_test_file("N10E011.hgt", &[0; 6]);
+        match DemTile::from_xth_file(&file) {
+            Err(SionError::NonSquareHeightsCount { heights_count, .. }) => {
+                assert_eq!(heights_count, 3)
+            }
+            _ => panic!("Expected a NonSquareHeightsCount error"),
+        }
+    }
+
+    #[test]
+    fn file_with_invalid_tile_name_is_rejected() {
+        let file = write_test_file("tile.hgt", &[0; 8]);
+        assert!(matches!(
+            DemTile::from_hgt_file(&file),
+            Err(SionError::InvalidTileName(_))
+        ));
+    }
+
+    #[test]
+    fn memory_mapped_tiles_serve_the_same_heights() {
+        let heights: Vec<i16> = (0..9).map(|i| i * 1000 - 4321).collect();
+
+        let hgt_bytes: Vec<u8> =
+            heights.iter().flat_map(|h| h.to_be_bytes()).collect();
+        let xth_bytes: Vec<u8> =
+            heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+
+        let hgt_file = write_test_file("N47E007.hgt", &hgt_bytes);
+        let xth_file = write_test_file("N47E007.xth", &xth_bytes);
+
+        let mapped_hgt = DemTile::map_hgt_file(&hgt_file).unwrap();
+        let mapped_xth = DemTile::map_xth_file(&xth_file).unwrap();
+        let loaded_hgt = DemTile::from_hgt_file(&hgt_file).unwrap();
+
+        assert!(mapped_hgt.is_memory_mapped());
+        assert!(mapped_xth.is_memory_mapped());
+        assert_eq!(mapped_hgt.lon, 7);
+        assert_eq!(mapped_hgt.lat, 47);
+        assert_eq!(mapped_hgt.size, 3);
+
+        for (index, height) in heights.iter().enumerate() {
+            assert_eq!(mapped_hgt.height_at_index(index), *height);
+            assert_eq!(mapped_xth.height_at_index(index), *height);
+            assert_eq!(loaded_hgt.height_at_index(index), *height);
+        }
+        assert_eq!(mapped_xth.height_at(2, 1), heights[5]);
+    }
+
+    #[test]
+    fn rows_are_read_in_the_native_byte_order() {
+        let heights: Vec<i16> = (0..9).map(|i| i * 777 - 3000).collect();
+        let xth_bytes: Vec<u8> =
+            heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+        let xth_file = write_test_file("N47E008.xth", &xth_bytes);
+
+        let tiles = [
+            DemTile::from_heights(8, 47, 3, &heights),
+            DemTile::from_xth_file(&xth_file).unwrap(),
+        ];
+        for tile in tiles {
+            let mut row = [0; 3];
+            tile.read_row(1, &mut row);
+            assert_eq!(row, heights[3..6]);
+            tile.read_row(2, &mut row);
+            assert_eq!(row, heights[6..9]);
+        }
+    }
+
+    #[test]
+    fn memory_mapping_validates_the_file() {
+        let file = write_test_file("N10E012.hgt", &[0; 10]);
+        assert!(matches!(
+            DemTile::map_hgt_file(&file),
+            Err(SionError::NonSquareHeightsCount { .. })
+        ));
+        assert!(matches!(
+            DemTile::map_xth_file("target/debug/missing/N10E012.xth"),
+            Err(SionError::CannotOpenFile { .. })
+        ));
+    }
+
+    #[test]
+    fn write_and_read_back_hgt_and_xth_files() {
+        let heights: Vec<i16> =
+            (0..25).map(|i| (i * 1777 % 9000 - 500) as i16).collect();
+        let mut heights = heights;
+        heights[12] = NO_DATA_HEIGHT;
+
+        let tile = DemTile::from_heights(-8, 45, 5, &heights);
+
+        let dir = "target/debug/dem_tile_tests/round_trip";
+        fs::create_dir_all(dir).unwrap();
+        let hgt_file = format!("{}/N45W008.hgt", dir);
+        let xth_file = format!("{}/N45W008.xth", dir);
+
+        // HGT -> XTH -> HGT, reading the tile back after each step
+        tile.write_hgt(&hgt_file).unwrap();
+        let from_hgt = DemTile::from_hgt_file(&hgt_file).unwrap();
+        from_hgt.write_xth(&xth_file).unwrap();
+        let from_xth = DemTile::from_xth_file(&xth_file).unwrap();
+        let mapped_xth = DemTile::map_xth_file(&xth_file).unwrap();
+        from_xth.write_hgt(&hgt_file).unwrap();
+        let from_hgt_again = DemTile::from_hgt_file(&hgt_file).unwrap();
+
+        for read_tile in [&from_hgt, &from_xth, &mapped_xth, &from_hgt_again] {
+            assert_eq!(read_tile.lon, -8);
+            assert_eq!(read_tile.lat, 45);
+            assert_eq!(read_tile.size, 5);
+            for (index, height) in heights.iter().enumerate() {
+                assert_eq!(read_tile.height_at_index(index), *height);
+            }
+        }
+
+        // the written files use the byte order of their format
+        let hgt_bytes = fs::read(&hgt_file).unwrap();
+        let xth_bytes = fs::read(&xth_file).unwrap();
+        assert_eq!(hgt_bytes.len(), 50);
+        assert_eq!(hgt_bytes[0..2], heights[0].to_be_bytes());
+        assert_eq!(xth_bytes[2..4], heights[1].to_le_bytes());
+    }
+
+    #[test]
+    fn writing_into_missing_directory_fails() {
+        let tile = DemTile::from_heights(0, 0, 1, &[1]);
+        assert!(matches!(
+            tile.write_hgt("target/debug/missing/N00E000.hgt"),
+            Err(SionError::CannotWriteFile { .. })
+        ));
     }
 }
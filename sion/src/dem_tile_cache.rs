@@ -0,0 +1,228 @@
+use crate::dem_tile::DemTile;
+use crate::dem_tile_id::DemTileId;
+use crate::dem_tile_source::DemTileSource;
+use crate::errors::SionError;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The cache's usage counters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DemTileCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub cached_tiles: usize,
+    pub cached_bytes: usize,
+}
+
+struct CachedTile {
+    tile: Arc<DemTile>,
+    bytes: usize,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    tiles: HashMap<DemTileId, CachedTile>,
+    /// The cached tiles' keys ordered from the least recently used one.
+    usage_order: BTreeMap<u64, DemTileId>,
+    /// Incremented on each access, used to order the tiles by their usage.
+    clock: u64,
+    stats: DemTileCacheStats,
+}
+
+/// A thread-safe cache of the tiles loaded from another source, evicting
+/// the least recently used tiles once the cached heights exceed the byte
+/// budget.
+///
+/// The tiles are loaded without holding the cache's lock, so the workers
+/// (e.g. rayon's) do not wait for each other's loading. Two workers missing
+/// the same tile at the same time may both load it.
+pub struct DemTileCache<S: DemTileSource> {
+    source: S,
+    byte_budget: usize,
+    state: Mutex<CacheState>,
+}
+
+impl<S: DemTileSource> DemTileCache<S> {
+    /// Creates a cache holding at most `byte_budget` bytes of heights (a
+    /// 3601x3601 tile takes about 26 MB). The memory-mapped tiles are
+    /// counted with their full size too.
+    pub fn new(source: S, byte_budget: usize) -> DemTileCache<S> {
+        DemTileCache {
+            source,
+            byte_budget,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    pub fn stats(&self) -> DemTileCacheStats {
+        self.lock_state().stats
+    }
+
+    /// Removes all the cached tiles (the usage counters are kept).
+    pub fn clear(&self) {
+        let mut state = self.lock_state();
+        state.tiles.clear();
+        state.usage_order.clear();
+        state.stats.cached_tiles = 0;
+        state.stats.cached_bytes = 0;
+    }
+
+    fn lock_state(&self) -> MutexGuard<CacheState> {
+        // the state is consistent after each of the cache's operations, so
+        // a poisoned lock can be used
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn insert(&self, tile_id: &DemTileId, tile: &Arc<DemTile>) {
+        let bytes = tile.size * tile.size * 2;
+        if bytes > self.byte_budget {
+            return;
+        }
+
+        let mut state = self.lock_state();
+        // another worker may have loaded the tile in the meantime
+        if state.tiles.contains_key(tile_id) {
+            return;
+        }
+
+        while state.stats.cached_bytes + bytes > self.byte_budget {
+            let Some((_, evicted_id)) = state.usage_order.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = state.tiles.remove(&evicted_id) {
+                state.stats.cached_bytes -= evicted.bytes;
+                state.stats.cached_tiles -= 1;
+                state.stats.evictions += 1;
+            }
+        }
+
+        state.clock += 1;
+        let last_used = state.clock;
+        state.usage_order.insert(last_used, *tile_id);
+        state.tiles.insert(
+            *tile_id,
+            CachedTile {
+                tile: Arc::clone(tile),
+                bytes,
+                last_used,
+            },
+        );
+        state.stats.cached_bytes += bytes;
+        state.stats.cached_tiles += 1;
+    }
+}
+
+impl<S: DemTileSource> DemTileSource for DemTileCache<S> {
+    fn tile(&self, tile_id: &DemTileId) -> Result<Arc<DemTile>, SionError> {
+        {
+            let mut state = self.lock_state();
+            state.clock += 1;
+            let now = state.clock;
+
+            if let Some(cached) = state.tiles.get_mut(tile_id) {
+                let previous_use = cached.last_used;
+                cached.last_used = now;
+                let tile = Arc::clone(&cached.tile);
+
+                state.usage_order.remove(&previous_use);
+                state.usage_order.insert(now, *tile_id);
+                state.stats.hits += 1;
+                return Ok(tile);
+            }
+
+            state.stats.misses += 1;
+        }
+
+        let tile = self.source.tile(tile_id)?;
+        self.insert(tile_id, &tile);
+        Ok(tile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dem_tile_source::InMemoryTileSource;
+    use rayon::prelude::*;
+
+    /// A source with 10x10 tiles (200 bytes each) for the given longitudes.
+    fn source(lons: std::ops::Range<i16>) -> InMemoryTileSource {
+        let mut source = InMemoryTileSource::new();
+        for lon in lons {
+            source
+                .insert(DemTile::from_heights(lon, 46, 10, &[lon; 100]))
+                .unwrap();
+        }
+        source
+    }
+
+    fn get(cache: &DemTileCache<InMemoryTileSource>, lon: i16) -> i16 {
+        cache
+            .tile(&DemTileId::new(lon, 46))
+            .unwrap()
+            .height_at(0, 0)
+    }
+
+    #[test]
+    fn least_recently_used_tiles_are_evicted() {
+        let cache = DemTileCache::new(source(0..5), 600);
+
+        assert_eq!(get(&cache, 0), 0);
+        assert_eq!(get(&cache, 1), 1);
+        assert_eq!(get(&cache, 2), 2);
+        assert_eq!(get(&cache, 0), 0);
+        // evicts tile 1, the least recently used one
+        assert_eq!(get(&cache, 3), 3);
+        assert_eq!(get(&cache, 0), 0);
+        assert_eq!(get(&cache, 2), 2);
+        assert_eq!(get(&cache, 1), 1);
+
+        assert_eq!(
+            cache.stats(),
+            DemTileCacheStats {
+                hits: 3,
+                misses: 5,
+                evictions: 2,
+                cached_tiles: 3,
+                cached_bytes: 600,
+            }
+        );
+
+        cache.clear();
+        assert_eq!(cache.stats().cached_tiles, 0);
+        assert_eq!(cache.stats().cached_bytes, 0);
+    }
+
+    #[test]
+    fn tiles_over_the_budget_and_missing_tiles_are_not_cached() {
+        let cache = DemTileCache::new(source(0..1), 100);
+
+        assert_eq!(get(&cache, 0), 0);
+        assert!(matches!(
+            cache.tile(&DemTileId::new(7, 46)),
+            Err(SionError::TileNotFound(_))
+        ));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 2));
+        assert_eq!((stats.cached_tiles, stats.cached_bytes), (0, 0));
+    }
+
+    #[test]
+    fn cache_can_be_shared_by_rayon_workers() {
+        let cache = DemTileCache::new(source(0..8), 200 * 4);
+
+        let sum: i64 = (0..1000)
+            .into_par_iter()
+            .map(|i| get(&cache, (i % 8) as i16) as i64)
+            .sum();
+        assert_eq!(sum, (0..1000).map(|i| (i % 8) as i64).sum::<i64>());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits + stats.misses, 1000);
+        assert!(stats.cached_tiles <= 4);
+        assert!(stats.cached_bytes <= 800);
+    }
+}
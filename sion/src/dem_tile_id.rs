@@ -0,0 +1,398 @@
+use crate::errors::SionError;
+use std::fmt;
+use std::str::FromStr;
+
+/// The ID of a 1x1 degree tile, given by the coordinates of its south-west
+/// corner. The IDs are named like the SRTM and WorldCover tiles, e.g.
+/// `N46E006` or `S05W123`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DemTileId {
+    pub lon: i16,
+    pub lat: i16,
+}
+
+/// A rectangle in degrees.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl BoundingBox {
+    /// Whether the point lies within the box (including its edges).
+    pub fn contains(&self, lon: f64, lat: f64) -> bool {
+        (self.min_lon..=self.max_lon).contains(&lon)
+            && (self.min_lat..=self.max_lat).contains(&lat)
+    }
+}
+
+impl DemTileId {
+    /// The valid south-west corner longitudes.
+    pub const LON_RANGE: std::ops::RangeInclusive<i16> = -180..=179;
+    /// The valid south-west corner latitudes.
+    pub const LAT_RANGE: std::ops::RangeInclusive<i16> = -90..=89;
+
+    /// Creates the ID of the tile with the given south-west corner.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the coordinates are out of range, see `try_new`.
+    pub fn new(lon: i16, lat: i16) -> Self {
+        DemTileId::try_new(lon, lat).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Creates the ID of the tile with the given south-west corner, or
+    /// returns `SionError::InvalidTileCoordinates` if the longitude is not
+    /// in `-180..=179` or the latitude is not in `-90..=89`.
+    pub fn try_new(lon: i16, lat: i16) -> Result<Self, SionError> {
+        if DemTileId::LON_RANGE.contains(&lon)
+            && DemTileId::LAT_RANGE.contains(&lat)
+        {
+            Ok(DemTileId { lon, lat })
+        } else {
+            Err(SionError::InvalidTileCoordinates { lon, lat })
+        }
+    }
+
+    /// Returns the ID of the tile covering the point (the points on the
+    /// tiles' shared edges belong to the tile to their north-east), or
+    /// `None` if the point is outside of the valid coordinates.
+    pub fn containing(lon: f64, lat: f64) -> Option<Self> {
+        if !(-180.0..=180.0).contains(&lon) || !(-90.0..=90.0).contains(&lat) {
+            return None;
+        }
+
+        // the east and north edges belong to the last tiles
+        let lon = (lon.floor() as i16).min(*DemTileId::LON_RANGE.end());
+        let lat = (lat.floor() as i16).min(*DemTileId::LAT_RANGE.end());
+        Some(DemTileId { lon, lat })
+    }
+
+    /// The tile's extent in degrees.
+    pub fn bounding_box(&self) -> BoundingBox {
+        BoundingBox {
+            min_lon: self.lon as f64,
+            min_lat: self.lat as f64,
+            max_lon: self.lon as f64 + 1.,
+            max_lat: self.lat as f64 + 1.,
+        }
+    }
+
+    /// Returns the tile `lon_offset` tiles to the east and `lat_offset`
+    /// tiles to the north, wrapping around the antimeridian, or `None` if
+    /// the tile would be beyond a pole.
+    pub fn neighbour(&self, lon_offset: i16, lat_offset: i16) -> Option<Self> {
+        // the coordinates are widened, so large offsets cannot overflow
+        let lat = i16::try_from(self.lat as i32 + lat_offset as i32)
+            .ok()
+            .filter(|lat| DemTileId::LAT_RANGE.contains(lat))?;
+
+        let lon = wrap_lon(self.lon as i32 + lon_offset as i32);
+        Some(DemTileId { lon, lat })
+    }
+
+    /// Wraps the longitude around the antimeridian into `-180..=179`.
+    pub fn wrap_lon(lon: i16) -> i16 {
+        wrap_lon(lon as i32)
+    }
+
+    /// Returns the (up to 8) surrounding tiles, row by row from the north-
+    /// west one. The tiles at the poles have only 5 neighbours.
+    pub fn neighbours(&self) -> Vec<Self> {
+        (-1..=1)
+            .rev()
+            .flat_map(|lat_offset| {
+                (-1..=1).map(move |lon_offset| (lon_offset, lat_offset))
+            })
+            .filter(|&offsets| offsets != (0, 0))
+            .filter_map(|(lon_offset, lat_offset)| {
+                self.neighbour(lon_offset, lat_offset)
+            })
+            .collect()
+    }
+
+    /// Iterates over the tiles covering the rectangle, row by row from the
+    /// south-west one. A `min_lon` greater than `max_lon` denotes a
+    /// rectangle crossing the antimeridian. The coordinates are clamped to
+    /// the valid ranges, the tiles only touching the rectangle's north or
+    /// east edge are left out.
+    pub fn covering(
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    ) -> impl Iterator<Item = DemTileId> {
+        let first_index = |min: f64, range: &std::ops::RangeInclusive<i16>| {
+            (min.floor() as i16).clamp(*range.start(), *range.end())
+        };
+        let last_index =
+            |min: f64, max: f64, range: &std::ops::RangeInclusive<i16>| {
+                // the tile only touching the edge is not covered, unless the
+                // rectangle is just a line
+                let last = if max > min {
+                    max.ceil() - 1.
+                } else {
+                    max.floor()
+                };
+                (last as i16).clamp(*range.start(), *range.end())
+            };
+
+        let west = first_index(min_lon, &DemTileId::LON_RANGE);
+        let south = first_index(min_lat, &DemTileId::LAT_RANGE);
+        let north = last_index(min_lat, max_lat, &DemTileId::LAT_RANGE);
+        let columns = if min_lon <= max_lon {
+            let east = last_index(min_lon, max_lon, &DemTileId::LON_RANGE);
+            (east - west + 1).max(0)
+        } else {
+            // across the antimeridian
+            let east = last_index(-180., max_lon, &DemTileId::LON_RANGE);
+            (180 - west) + (east + 180 + 1)
+        };
+
+        (south..=north).flat_map(move |lat| {
+            (0..columns).map(move |column| {
+                DemTileId { lon: west, lat }.neighbour(column, 0).unwrap()
+            })
+        })
+    }
+}
+
+/// Wraps the (widened) longitude around the antimeridian into
+/// `-180..=179`.
+fn wrap_lon(lon: i32) -> i16 {
+    ((lon + 180).rem_euclid(360) - 180) as i16
+}
+
+impl FromStr for DemTileId {
+    type Err = SionError;
+
+    /// Parses the tile names like `N46E006` (the letters can also be in
+    /// lowercase). The latitude must have two digits, the longitude three.
+    fn from_str(tile_name: &str) -> Result<Self, Self::Err> {
+        let invalid = || SionError::InvalidTileName(tile_name.to_string());
+
+        let bytes = tile_name.as_bytes();
+        if bytes.len() != 7
+            || !bytes[1..3].iter().all(u8::is_ascii_digit)
+            || !bytes[4..7].iter().all(u8::is_ascii_digit)
+        {
+            return Err(invalid());
+        }
+
+        let lat_sign = match bytes[0].to_ascii_uppercase() {
+            b'N' => 1,
+            b'S' => -1,
+            _ => return Err(invalid()),
+        };
+        let lon_sign = match bytes[3].to_ascii_uppercase() {
+            b'E' => 1,
+            b'W' => -1,
+            _ => return Err(invalid()),
+        };
+
+        // the digits were checked above, so the parsing cannot fail
+        let lat = lat_sign * tile_name[1..3].parse::<i16>().unwrap();
+        let lon = lon_sign * tile_name[4..7].parse::<i16>().unwrap();
+
+        DemTileId::try_new(lon, lat).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Display for DemTileId {
+    /// Formats the ID as the canonical, zero-padded tile name (e.g.
+    /// `N05E006`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{:02}{}{:03}",
+            if self.lat >= 0 { 'N' } else { 'S' },
+            self.lat.abs(),
+            if self.lon >= 0 { 'E' } else { 'W' },
+            self.lon.abs()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn parsing_and_formatting_tile_ids() {
+        let tile_id: DemTileId = "N54E168".parse().unwrap();
+        assert_eq!(tile_id.lon, 168);
+        assert_eq!(tile_id.lat, 54);
+        assert_eq!(tile_id.to_string(), "N54E168");
+
+        let tile_id: DemTileId = "n54e168".parse().unwrap();
+        assert_eq!(tile_id.lon, 168);
+        assert_eq!(tile_id.lat, 54);
+        assert_eq!(tile_id.to_string(), "N54E168");
+
+        let tile_id: DemTileId = "S54W168".parse().unwrap();
+        assert_eq!(tile_id.lon, -168);
+        assert_eq!(tile_id.lat, -54);
+        assert_eq!(tile_id.to_string(), "S54W168");
+
+        let tile_id: DemTileId = "N54W168".parse().unwrap();
+        assert_eq!(tile_id.lon, -168);
+        assert_eq!(tile_id.lat, 54);
+        assert_eq!(tile_id.to_string(), "N54W168");
+
+        let tile_id: DemTileId = "S54E168".parse().unwrap();
+        assert_eq!(tile_id.lon, 168);
+        assert_eq!(tile_id.lat, -54);
+        assert_eq!(tile_id.to_string(), "S54E168");
+    }
+
+    #[rstest]
+    #[case(6, 5, "N05E006")]
+    #[case(0, 0, "N00E000")]
+    #[case(-7, -1, "S01W007")]
+    #[case(-180, -90, "S90W180")]
+    #[case(179, 89, "N89E179")]
+    fn names_are_zero_padded(
+        #[case] lon: i16,
+        #[case] lat: i16,
+        #[case] tile_name: &str,
+    ) {
+        let tile_id = DemTileId::new(lon, lat);
+        assert_eq!(tile_id.to_string(), tile_name);
+        assert_eq!(tile_name.parse::<DemTileId>().unwrap(), tile_id);
+    }
+
+    #[rstest]
+    #[case("N54X168")]
+    #[case("X54E168")]
+    #[case("N5E6")]
+    #[case("N054E16")]
+    #[case("N+4E168")]
+    #[case("N54E-16")]
+    #[case("N90E000")]
+    #[case("S91E000")]
+    #[case("N00E180")]
+    #[case("N00W181")]
+    fn invalid_tile_names_are_rejected(#[case] tile_name: &str) {
+        assert!(matches!(
+            tile_name.parse::<DemTileId>(),
+            Err(SionError::InvalidTileName(name)) if name == tile_name
+        ));
+    }
+
+    #[test]
+    fn coordinates_are_range_checked() {
+        assert!(DemTileId::try_new(-180, -90).is_ok());
+        assert!(matches!(
+            DemTileId::try_new(180, 0),
+            Err(SionError::InvalidTileCoordinates { lon: 180, lat: 0 })
+        ));
+        assert!(DemTileId::try_new(0, 90).is_err());
+        assert!(DemTileId::try_new(0, -91).is_err());
+    }
+
+    #[test]
+    fn tiles_contain_their_points() {
+        let tile_id = DemTileId::new(-7, -5);
+        let bounding_box = tile_id.bounding_box();
+        assert_eq!(
+            bounding_box,
+            BoundingBox {
+                min_lon: -7.,
+                min_lat: -5.,
+                max_lon: -6.,
+                max_lat: -4.
+            }
+        );
+        assert!(bounding_box.contains(-6.5, -4.2));
+        assert!(!bounding_box.contains(-7.5, -4.2));
+
+        assert_eq!(DemTileId::containing(-6.5, -4.2), Some(tile_id));
+        assert_eq!(DemTileId::containing(-7., -5.), Some(tile_id));
+        assert_eq!(
+            DemTileId::containing(180., 90.),
+            Some(DemTileId::new(179, 89))
+        );
+        assert_eq!(DemTileId::containing(180.1, 0.), None);
+    }
+
+    #[test]
+    fn neighbours_wrap_around_the_antimeridian() {
+        let tile_id = DemTileId::new(179, 10);
+        assert_eq!(tile_id.neighbour(1, 0), Some(DemTileId::new(-180, 10)));
+        assert_eq!(
+            DemTileId::new(-180, 10).neighbour(-1, -1),
+            Some(DemTileId::new(179, 9))
+        );
+        assert_eq!(
+            tile_id.neighbours(),
+            vec![
+                DemTileId::new(178, 11),
+                DemTileId::new(179, 11),
+                DemTileId::new(-180, 11),
+                DemTileId::new(178, 10),
+                DemTileId::new(-180, 10),
+                DemTileId::new(178, 9),
+                DemTileId::new(179, 9),
+                DemTileId::new(-180, 9),
+            ]
+        );
+
+        // nothing beyond the poles
+        assert_eq!(DemTileId::new(0, 89).neighbour(0, 1), None);
+        assert_eq!(DemTileId::new(0, -90).neighbours().len(), 5);
+
+        // large offsets neither overflow nor wrap the latitude
+        assert_eq!(
+            DemTileId::new(179, 0).neighbour(i16::MAX, 0),
+            Some(DemTileId::new(-174, 0))
+        );
+        assert_eq!(
+            DemTileId::new(-180, 0).neighbour(i16::MIN, 0),
+            Some(DemTileId::new(172, 0))
+        );
+        assert_eq!(DemTileId::new(0, 89).neighbour(0, i16::MAX), None);
+        assert_eq!(DemTileId::new(0, -90).neighbour(0, i16::MIN), None);
+        assert_eq!(DemTileId::wrap_lon(i16::MAX), 7);
+        assert_eq!(DemTileId::wrap_lon(i16::MIN), -8);
+    }
+
+    #[test]
+    fn rectangles_are_covered_by_tiles() {
+        let tile_ids: Vec<DemTileId> =
+            DemTileId::covering(5.5, 45.2, 7., 46.1).collect();
+        assert_eq!(
+            tile_ids,
+            vec![
+                DemTileId::new(5, 45),
+                DemTileId::new(6, 45),
+                DemTileId::new(5, 46),
+                DemTileId::new(6, 46),
+            ]
+        );
+
+        // across the antimeridian
+        let tile_ids: Vec<DemTileId> =
+            DemTileId::covering(178.5, -1., -179.5, 0.).collect();
+        assert_eq!(
+            tile_ids,
+            vec![
+                DemTileId::new(178, -1),
+                DemTileId::new(179, -1),
+                DemTileId::new(-180, -1),
+            ]
+        );
+
+        // a single point
+        let tile_ids: Vec<DemTileId> =
+            DemTileId::covering(6., 46., 6., 46.).collect();
+        assert_eq!(tile_ids, vec![DemTileId::new(6, 46)]);
+
+        assert_eq!(
+            DemTileId::covering(-200., -100., 200., 100.).count(),
+            360 * 180
+        );
+    }
+}
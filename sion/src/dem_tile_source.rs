@@ -0,0 +1,296 @@
+use crate::dem_tile::DemTile;
+use crate::dem_tile_id::DemTileId;
+use crate::errors::SionError;
+use crate::grid_spacing::GridGeometry;
+use crate::hgt_zip::{hgt_entries, open_zip_archive, read_hgt_entry};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use zip::ZipArchive;
+
+/// Resolves tile IDs to DEM tiles, regardless of where the tiles are stored.
+pub trait DemTileSource: Send + Sync {
+    /// Returns the tile with the given ID, or `SionError::TileNotFound` if
+    /// the source does not have it.
+    fn tile(&self, tile_id: &DemTileId) -> Result<Arc<DemTile>, SionError>;
+}
+
+/// Loads the tiles from a directory of `N46E006.hgt`, `N46E006.xth` or
+/// `N46E006.hgt.zip` files (looked up in that order).
+pub struct DirectoryTileSource {
+    directory: PathBuf,
+    memory_mapped: bool,
+}
+
+impl DirectoryTileSource {
+    pub fn new(directory: impl Into<PathBuf>) -> DirectoryTileSource {
+        DirectoryTileSource {
+            directory: directory.into(),
+            memory_mapped: false,
+        }
+    }
+
+    /// Memory-maps the HGT and XTH files instead of reading them into
+    /// memory.
+    pub fn memory_mapped(mut self) -> DirectoryTileSource {
+        self.memory_mapped = true;
+        self
+    }
+}
+
+impl DemTileSource for DirectoryTileSource {
+    fn tile(&self, tile_id: &DemTileId) -> Result<Arc<DemTile>, SionError> {
+        let tile_name = tile_id.to_string();
+        let file = |extension: &str| {
+            self.directory
+                .join(format!("{}.{}", tile_name, extension))
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        let (hgt_file, xth_file, zip_file) =
+            (file("hgt"), file("xth"), file("hgt.zip"));
+
+        let tile = if PathBuf::from(&hgt_file).is_file() {
+            if self.memory_mapped {
+                DemTile::map_hgt_file(&hgt_file)?
+            } else {
+                DemTile::from_hgt_file(&hgt_file)?
+            }
+        } else if PathBuf::from(&xth_file).is_file() {
+            if self.memory_mapped {
+                DemTile::map_xth_file(&xth_file)?
+            } else {
+                DemTile::from_xth_file(&xth_file)?
+            }
+        } else if PathBuf::from(&zip_file).is_file() {
+            DemTile::from_hgt_zip_file(&zip_file)?
+        } else {
+            return Err(SionError::TileNotFound(tile_name));
+        };
+
+        Ok(Arc::new(tile))
+    }
+}
+
+/// Loads the tiles from a zip archive holding one or more `.hgt` entries.
+/// The archive is indexed when opened, the tiles are decompressed on
+/// request.
+pub struct ZipArchiveTileSource {
+    file: String,
+    archive: Mutex<ZipArchive<BufReader<File>>>,
+    entries: HashMap<DemTileId, (usize, String)>,
+}
+
+impl ZipArchiveTileSource {
+    pub fn open(file: &str) -> Result<ZipArchiveTileSource, SionError> {
+        let mut archive = open_zip_archive(file)?;
+
+        let mut entries = HashMap::new();
+        for (index, tile_name) in hgt_entries(file, &mut archive)? {
+            let tile_id: DemTileId = tile_name.parse()?;
+            entries.insert(tile_id, (index, tile_name));
+        }
+
+        Ok(ZipArchiveTileSource {
+            file: file.to_string(),
+            archive: Mutex::new(archive),
+            entries,
+        })
+    }
+}
+
+impl DemTileSource for ZipArchiveTileSource {
+    fn tile(&self, tile_id: &DemTileId) -> Result<Arc<DemTile>, SionError> {
+        let (index, tile_name) = self
+            .entries
+            .get(tile_id)
+            .ok_or_else(|| SionError::TileNotFound(tile_id.to_string()))?;
+
+        // a panic while reading an entry does not leave the archive in an
+        // inconsistent state, so the poisoning can be ignored
+        let mut archive = self
+            .archive
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let tile = read_hgt_entry(&self.file, &mut archive, *index, tile_name)?;
+
+        Ok(Arc::new(tile))
+    }
+}
+
+/// Serves the tiles added to it, e.g. in tests or for generated terrain.
+#[derive(Default)]
+pub struct InMemoryTileSource {
+    tiles: HashMap<DemTileId, Arc<DemTile>>,
+}
+
+impl InMemoryTileSource {
+    pub fn new() -> InMemoryTileSource {
+        InMemoryTileSource::default()
+    }
+
+    /// Adds the tile (replacing the one with the same coordinates). The
+    /// tiles are served by their degrees, so the tiles not covering a
+    /// degree on a geographic grid (like the web map tiles) are rejected.
+    pub fn insert(&mut self, tile: DemTile) -> Result<(), SionError> {
+        let tile_id = DemTileId::try_new(tile.lon, tile.lat)?;
+        if !matches!(
+            GridGeometry::of_tile(&tile),
+            GridGeometry::Geographic { .. }
+        ) {
+            return Err(SionError::UnsupportedTileGrid {
+                lon: tile.lon,
+                lat: tile.lat,
+                reason: "the tile is not on a geographic grid".to_string(),
+            });
+        }
+
+        self.tiles.insert(tile_id, Arc::new(tile));
+        Ok(())
+    }
+}
+
+impl DemTileSource for InMemoryTileSource {
+    fn tile(&self, tile_id: &DemTileId) -> Result<Arc<DemTile>, SionError> {
+        self.tiles
+            .get(tile_id)
+            .cloned()
+            .ok_or_else(|| SionError::TileNotFound(tile_id.to_string()))
+    }
+}
+
+/// Asks its sources in turn until one of them has the tile. Only the
+/// `TileNotFound` errors make it move on to the next source, other errors
+/// (like a corrupted file) are returned right away.
+pub struct FallbackTileSource {
+    sources: Vec<Box<dyn DemTileSource>>,
+}
+
+impl FallbackTileSource {
+    pub fn new(sources: Vec<Box<dyn DemTileSource>>) -> FallbackTileSource {
+        FallbackTileSource { sources }
+    }
+}
+
+impl DemTileSource for FallbackTileSource {
+    fn tile(&self, tile_id: &DemTileId) -> Result<Arc<DemTile>, SionError> {
+        for source in &self.sources {
+            match source.tile(tile_id) {
+                Err(SionError::TileNotFound(_)) => continue,
+                result => return result,
+            }
+        }
+
+        Err(SionError::TileNotFound(tile_id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn test_dir(name: &str) -> String {
+        let dir = format!("target/debug/dem_tile_source_tests/{}", name);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn directory_source_finds_hgt_and_xth_files() {
+        let dir = test_dir("directory");
+        DemTile::from_heights(6, 46, 2, &[1, 2, 3, 4])
+            .write_hgt(&format!("{}/N46E006.hgt", dir))
+            .unwrap();
+        DemTile::from_heights(-7, -5, 2, &[5, 6, 7, 8])
+            .write_xth(&format!("{}/S05W007.xth", dir))
+            .unwrap();
+
+        let source = DirectoryTileSource::new(&dir);
+        let tile = source.tile(&DemTileId::new(6, 46)).unwrap();
+        assert_eq!((tile.lon, tile.lat, tile.height_at(1, 1)), (6, 46, 4));
+        assert!(!tile.is_memory_mapped());
+
+        let source = DirectoryTileSource::new(&dir).memory_mapped();
+        let tile = source.tile(&DemTileId::new(-7, -5)).unwrap();
+        assert_eq!((tile.lon, tile.lat, tile.height_at(1, 0)), (-7, -5, 6));
+        assert!(tile.is_memory_mapped());
+
+        match source.tile(&DemTileId::new(7, 46)) {
+            Err(SionError::TileNotFound(tile_name)) => {
+                assert_eq!(tile_name, "N46E007")
+            }
+            _ => panic!("Expected a TileNotFound error"),
+        }
+    }
+
+    #[test]
+    fn zip_archive_source_reads_the_requested_entries() {
+        let file = format!("{}/tiles.zip", test_dir("zip"));
+        let mut writer = ZipWriter::new(File::create(&file).unwrap());
+        for (entry_name, heights) in [
+            ("N46E006.hgt", [1i16, 2, 3, 4]),
+            ("N46E007.hgt", [5, 6, 7, 8]),
+        ] {
+            writer
+                .start_file(entry_name, SimpleFileOptions::default())
+                .unwrap();
+            for height in heights {
+                writer.write_all(&height.to_be_bytes()).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+
+        let source = ZipArchiveTileSource::open(&file).unwrap();
+        let tile = source.tile(&DemTileId::new(7, 46)).unwrap();
+        assert_eq!((tile.lon, tile.lat, tile.height_at(0, 1)), (7, 46, 7));
+        assert!(matches!(
+            source.tile(&DemTileId::new(8, 46)),
+            Err(SionError::TileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn fallback_source_tries_the_sources_in_turn() {
+        let mut first = InMemoryTileSource::new();
+        first
+            .insert(DemTile::from_heights(6, 46, 2, &[1, 1, 1, 1]))
+            .unwrap();
+        let mut second = InMemoryTileSource::new();
+        second
+            .insert(DemTile::from_heights(6, 46, 2, &[2, 2, 2, 2]))
+            .unwrap();
+        second
+            .insert(DemTile::from_heights(7, 46, 2, &[3, 3, 3, 3]))
+            .unwrap();
+
+        // an invalid file stops the search
+        let dir = test_dir("fallback");
+        fs::write(format!("{}/N47E006.hgt", dir), [0; 6]).unwrap();
+
+        let source = FallbackTileSource::new(vec![
+            Box::new(first),
+            Box::new(DirectoryTileSource::new(&dir)),
+            Box::new(second),
+        ]);
+
+        let tile = source.tile(&DemTileId::new(6, 46)).unwrap();
+        assert_eq!(tile.height_at(0, 0), 1);
+        let tile = source.tile(&DemTileId::new(7, 46)).unwrap();
+        assert_eq!(tile.height_at(0, 0), 3);
+        assert!(matches!(
+            source.tile(&DemTileId::new(8, 46)),
+            Err(SionError::TileNotFound(_))
+        ));
+        assert!(matches!(
+            source.tile(&DemTileId::new(6, 47)),
+            Err(SionError::NonSquareHeightsCount { .. })
+        ));
+    }
+}
@@ -0,0 +1,453 @@
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+use crate::dem_tile_id::DemTileId;
+use crate::errors::SionError;
+use crate::grid_spacing::GridGeometry;
+use crate::resampling::catmull_rom_weights;
+use std::collections::HashMap;
+
+/// The interpolation used to calculate the elevation between the cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterpolationMethod {
+    /// Takes the height of the nearest cell.
+    Nearest,
+    /// Interpolates linearly between the 2x2 surrounding cells.
+    Bilinear,
+    /// Catmull-Rom interpolation of the 4x4 surrounding cells.
+    Bicubic,
+}
+
+impl DemTile {
+    /// Returns the (interpolated) elevation at the given coordinates, or
+    /// `None` if the coordinates are outside of the tile or the nearest cell
+    /// is a void. The cells are located with the tile's `GridGeometry`, a
+    /// tile on a projected grid has no elevations at geographic
+    /// coordinates (see `elevation_at_cell`).
+    ///
+    /// The voids around the nearest cell are skipped by the bilinear
+    /// interpolation (the remaining cells' weights are renormalized), and
+    /// the bicubic interpolation falls back to the bilinear one when any of
+    /// its cells is a void or outside of the tile.
+    pub fn elevation_at(
+        &self,
+        lon: f64,
+        lat: f64,
+        method: InterpolationMethod,
+    ) -> Option<f32> {
+        let (x, y) = GridGeometry::of_tile(self).cell_at(lon, lat)?;
+        self.elevation_at_cell(x, y, method)
+    }
+
+    /// Returns the (interpolated) elevation at the fractional cell
+    /// coordinates (`0, 0` being the north-west cell), e.g. when working
+    /// with sub-cell units like the ones of the maxx_sim `Grid`. See
+    /// `elevation_at` for the voids handling.
+    pub fn elevation_at_cell(
+        &self,
+        x: f64,
+        y: f64,
+        method: InterpolationMethod,
+    ) -> Option<f32> {
+        let last = (self.size - 1) as f64;
+        if !(0. ..=last).contains(&x) || !(0. ..=last).contains(&y) {
+            return None;
+        }
+
+        let last = last as i64;
+        interpolate(x, y, method, |cell_x, cell_y| {
+            if (0..=last).contains(&cell_x) && (0..=last).contains(&cell_y) {
+                let index = cell_y as usize * self.size + cell_x as usize;
+                Some(self.height_at_index(index))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// A set of equally sized DEM tiles sharing their edges (like the HGT
+/// tiles), used to look up the elevations seamlessly across the tiles'
+/// edges.
+#[derive(Default)]
+pub struct DemTileSet {
+    tile_size: Option<usize>,
+    tiles: HashMap<DemTileId, DemTile>,
+}
+
+impl DemTileSet {
+    pub fn new() -> DemTileSet {
+        DemTileSet::default()
+    }
+
+    /// Adds the tile to the set (replacing the set's tile with the same
+    /// coordinates). All the tiles in the set must have the same size (of
+    /// at least 2 cells), valid coordinates and share their edges with the
+    /// neighbouring tiles (see `DemTile::shares_edges`).
+    pub fn insert(&mut self, tile: DemTile) -> Result<(), SionError> {
+        let tile_id = DemTileId::try_new(tile.lon, tile.lat)?;
+        let unsupported = |reason: &str| SionError::UnsupportedTileGrid {
+            lon: tile.lon,
+            lat: tile.lat,
+            reason: reason.to_string(),
+        };
+        if tile.size < 2 {
+            return Err(unsupported(
+                "the tile has fewer than 2 cells per side",
+            ));
+        }
+        match GridGeometry::of_tile(&tile) {
+            GridGeometry::Geographic { .. } if tile.shares_edges() => {}
+            GridGeometry::Geographic { .. } => {
+                return Err(unsupported("the tile does not share its edges"))
+            }
+            _ => {
+                return Err(unsupported("the tile is not on a geographic grid"))
+            }
+        }
+
+        let expected_size = *self.tile_size.get_or_insert(tile.size);
+        if tile.size != expected_size {
+            return Err(SionError::TileSizeMismatch {
+                lon: tile.lon,
+                lat: tile.lat,
+                size: tile.size,
+                expected_size,
+            });
+        }
+
+        self.tiles.insert(tile_id, tile);
+        Ok(())
+    }
+
+    /// Returns the set's tile with the given ID.
+    pub fn tile(&self, tile_id: &DemTileId) -> Option<&DemTile> {
+        self.tiles.get(tile_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Returns the (interpolated) elevation at the given coordinates, or
+    /// `None` if the tile covering the coordinates is missing or the nearest
+    /// cell is a void. Near the tiles' edges the interpolation uses the
+    /// neighbouring tiles' cells (see `DemTile::elevation_at` for the voids
+    /// handling).
+    pub fn elevation_at(
+        &self,
+        lon: f64,
+        lat: f64,
+        method: InterpolationMethod,
+    ) -> Option<f32> {
+        let intervals = (self.tile_size? - 1) as i64;
+
+        // the global cell coordinates, counted from the north-west corner
+        // of the world (the tiles' shared edges have the same coordinates)
+        let x = (lon + 180.) * intervals as f64;
+        let y = (90. - lat) * intervals as f64;
+
+        interpolate(x, y, method, |cell_x, cell_y| {
+            self.global_cell_height(cell_x, cell_y, intervals)
+        })
+    }
+
+    fn global_cell_height(
+        &self,
+        cell_x: i64,
+        cell_y: i64,
+        intervals: i64,
+    ) -> Option<i16> {
+        let tile_column = cell_x.div_euclid(intervals);
+        let tile_row = cell_y.div_euclid(intervals);
+        let x = (cell_x - tile_column * intervals) as usize;
+        let y = (cell_y - tile_row * intervals) as usize;
+
+        let tile_lon = (tile_column.rem_euclid(360) - 180) as i16;
+        let tile_lat = (89 - tile_row) as i16;
+
+        // a cell on a shared edge can also be served by the western or the
+        // northern neighbour (if the tile itself is missing)
+        let mut candidates = vec![(tile_lon, tile_lat, x, y)];
+        let western_lon = if tile_lon == -180 { 179 } else { tile_lon - 1 };
+        let last = intervals as usize;
+        if x == 0 {
+            candidates.push((western_lon, tile_lat, last, y));
+        }
+        if y == 0 {
+            candidates.push((tile_lon, tile_lat + 1, x, last));
+        }
+        if x == 0 && y == 0 {
+            candidates.push((western_lon, tile_lat + 1, last, last));
+        }
+
+        // there are no tiles beyond the poles
+        candidates.into_iter().find_map(|(lon, lat, x, y)| {
+            let tile_id = DemTileId::try_new(lon, lat).ok()?;
+            self.tile(&tile_id)
+                .map(|tile| tile.height_at_index(y * tile.size + x))
+        })
+    }
+}
+
+/// Interpolates the height at the fractional cell coordinates, with the
+/// cells' heights provided by `height_at` (`None` for the cells outside of
+/// the available data).
+fn interpolate(
+    x: f64,
+    y: f64,
+    method: InterpolationMethod,
+    height_at: impl Fn(i64, i64) -> Option<i16>,
+) -> Option<f32> {
+    let valid_height_at = |cell_x, cell_y| {
+        height_at(cell_x, cell_y)
+            .filter(|&height| height != NO_DATA_HEIGHT)
+            .map(|height| height as f32)
+    };
+
+    let nearest = valid_height_at(x.round() as i64, y.round() as i64)?;
+    if method == InterpolationMethod::Nearest {
+        return Some(nearest);
+    }
+
+    let (base_x, base_y) = (x.floor(), y.floor());
+    let (fraction_x, fraction_y) = ((x - base_x) as f32, (y - base_y) as f32);
+    let (base_x, base_y) = (base_x as i64, base_y as i64);
+
+    if method == InterpolationMethod::Bicubic {
+        let weights_x = catmull_rom_weights(fraction_x);
+        let weights_y = catmull_rom_weights(fraction_y);
+
+        let bicubic = (0..4)
+            .filter(|&j| weights_y[j] != 0.)
+            .flat_map(|j| {
+                (0..4).filter(|&i| weights_x[i] != 0.).map(move |i| (i, j))
+            })
+            .try_fold(0., |sum, (i, j)| {
+                let height = valid_height_at(
+                    base_x + i as i64 - 1,
+                    base_y + j as i64 - 1,
+                )?;
+                Some(sum + weights_x[i] * weights_y[j] * height)
+            });
+
+        if bicubic.is_some() {
+            return bicubic;
+        }
+    }
+
+    let mut weighted_sum = 0.;
+    let mut weights_sum = 0.;
+    for (offset_y, weight_y) in [(0, 1. - fraction_y), (1, fraction_y)] {
+        for (offset_x, weight_x) in [(0, 1. - fraction_x), (1, fraction_x)] {
+            let weight = weight_x * weight_y;
+            if weight == 0. {
+                continue;
+            }
+
+            if let Some(height) =
+                valid_height_at(base_x + offset_x, base_y + offset_y)
+            {
+                weighted_sum += weight * height;
+                weights_sum += weight;
+            }
+        }
+    }
+
+    Some(weighted_sum / weights_sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_eq_approx;
+    use rstest::rstest;
+
+    /// Creates a tile with the heights of a plane defined in degrees.
+    fn plane_tile(lon: i16, lat: i16, size: usize) -> DemTile {
+        let intervals = (size - 1) as f64;
+        let heights: Vec<i16> = (0..size * size)
+            .map(|index| {
+                let x = lon as f64 + (index % size) as f64 / intervals;
+                let y = lat as f64 + 1. - (index / size) as f64 / intervals;
+                plane(x, y).round() as i16
+            })
+            .collect();
+        DemTile::from_heights(lon, lat, size, &heights)
+    }
+
+    fn plane(lon: f64, lat: f64) -> f64 {
+        1000. + 400. * (lon - 7.) - 800. * (lat - 46.)
+    }
+
+    #[rstest]
+    #[case(InterpolationMethod::Bilinear, 7.3, 46.2)]
+    #[case(InterpolationMethod::Bicubic, 7.3, 46.2)]
+    #[case(InterpolationMethod::Bilinear, 7.99, 46.01)]
+    #[case(InterpolationMethod::Bicubic, 7.99, 46.01)]
+    #[case(InterpolationMethod::Bicubic, 7.0, 47.0)]
+    fn interpolated_elevation_on_a_plane(
+        #[case] method: InterpolationMethod,
+        #[case] lon: f64,
+        #[case] lat: f64,
+    ) {
+        let tile = plane_tile(7, 46, 11);
+        let elevation = tile.elevation_at(lon, lat, method).unwrap();
+        assert_eq_approx(elevation, plane(lon, lat) as f32, 0.01);
+    }
+
+    #[test]
+    fn nearest_elevation_and_coordinates_outside_of_tile() {
+        let tile = plane_tile(7, 46, 11);
+        let nearest = InterpolationMethod::Nearest;
+
+        assert_eq!(tile.elevation_at(7.32, 46.18, nearest), Some(960.));
+        assert_eq!(tile.elevation_at(6.99, 46.5, nearest), None);
+        assert_eq!(tile.elevation_at(7.5, 47.01, nearest), None);
+        assert_eq!(tile.elevation_at_cell(3., 8., nearest), Some(960.));
+    }
+
+    #[test]
+    fn voids_are_skipped_or_reported() {
+        let heights = [100, 200, NO_DATA_HEIGHT, 400];
+        let tile = DemTile::from_heights(7, 46, 2, &heights);
+
+        // the nearest cell is a void
+        let bilinear = InterpolationMethod::Bilinear;
+        assert_eq!(tile.elevation_at(7.1, 46.1, bilinear), None);
+
+        // the void's weight is spread over the remaining cells
+        let elevation = tile.elevation_at(7.5, 46.75, bilinear).unwrap();
+        let expected = (0.375 * 100. + 0.375 * 200. + 0.125 * 400.) / 0.875;
+        assert_eq_approx(elevation, expected, 0.01);
+
+        // the bicubic interpolation falls back to the bilinear one
+        let bicubic = InterpolationMethod::Bicubic;
+        assert_eq!(tile.elevation_at(7.5, 46.75, bicubic), Some(elevation));
+    }
+
+    #[test]
+    fn elevation_across_tiles() {
+        let mut tiles = DemTileSet::new();
+        tiles.insert(plane_tile(7, 46, 11)).unwrap();
+        tiles.insert(plane_tile(8, 46, 11)).unwrap();
+        tiles.insert(plane_tile(7, 47, 11)).unwrap();
+        assert_eq!(tiles.len(), 3);
+
+        // the bicubic interpolation near the edges uses the neighbours' cells
+        for (lon, lat) in [(7.97, 46.5), (8.02, 46.5), (7.5, 46.98), (8., 47.)]
+        {
+            let elevation = tiles
+                .elevation_at(lon, lat, InterpolationMethod::Bicubic)
+                .unwrap();
+            assert_eq_approx(elevation, plane(lon, lat) as f32, 0.01);
+        }
+
+        // the missing tile's cells are only available on the shared edges
+        let bilinear = InterpolationMethod::Bilinear;
+        assert_eq!(tiles.elevation_at(8.5, 47.5, bilinear), None);
+        assert_eq!(
+            tiles.elevation_at(8.5, 47., bilinear),
+            Some(plane(8.5, 47.) as f32)
+        );
+    }
+
+    #[test]
+    fn tiles_of_different_sizes_cannot_be_mixed() {
+        let mut tiles = DemTileSet::new();
+        tiles.insert(plane_tile(7, 46, 11)).unwrap();
+
+        assert!(matches!(
+            tiles.insert(plane_tile(8, 46, 5)),
+            Err(SionError::TileSizeMismatch {
+                lon: 8,
+                lat: 46,
+                size: 5,
+                expected_size: 11
+            })
+        ));
+    }
+
+    #[test]
+    fn tiles_are_looked_up_by_their_ids() {
+        let mut tiles = DemTileSet::new();
+        tiles.insert(plane_tile(7, 46, 3)).unwrap();
+
+        let tile = tiles.tile(&DemTileId::new(7, 46)).unwrap();
+        assert_eq!((tile.lon, tile.lat), (7, 46));
+        assert!(tiles.tile(&DemTileId::new(8, 46)).is_none());
+        assert!(matches!(
+            tiles.insert(DemTile::from_heights(180, 46, 3, &[0; 9])),
+            Err(SionError::InvalidTileCoordinates { lon: 180, lat: 46 })
+        ));
+        assert_eq!(tiles.len(), 1);
+    }
+
+    #[test]
+    fn tiles_are_located_by_their_grid_geometry() {
+        // a Copernicus-like tile: the cells are half a cell inside the edges
+        let heights: Vec<i16> = (0..16).map(|index| index * 10).collect();
+        let tile = DemTile::from_heights(7, 46, 4, &heights)
+            .with_grid_geometry(GridGeometry::Geographic {
+                north_lat: 47. - 0.125,
+                west_lon: 7. + 0.125,
+                cell_size: 0.25,
+            });
+
+        let nearest = InterpolationMethod::Nearest;
+        assert_eq!(tile.elevation_at(7.125, 46.875, nearest), Some(0.));
+        assert_eq!(tile.elevation_at(7.875, 46.125, nearest), Some(150.));
+        assert_eq!(tile.elevation_at(7.05, 46.5, nearest), None);
+        let bilinear = InterpolationMethod::Bilinear;
+        assert_eq!(tile.elevation_at(7.25, 46.75, bilinear), Some(25.));
+
+        let projected = DemTile::from_heights(7, 46, 2, &[1, 2, 3, 4])
+            .with_grid_geometry(GridGeometry::Projected {
+                west_x: 2600000.,
+                north_y: 1200000.,
+                cell_size: 2.,
+            });
+        assert_eq!(projected.elevation_at(7.5, 46.5, nearest), None);
+    }
+
+    #[rstest]
+    #[case(DemTile::from_heights(7, 46, 1, &[100]), "fewer than 2 cells")]
+    #[case(
+        DemTile::from_heights(7, 46, 2, &[1, 2, 3, 4]).with_grid_geometry(
+            GridGeometry::Geographic {
+                north_lat: 46.75,
+                west_lon: 7.25,
+                cell_size: 0.5,
+            }
+        ),
+        "does not share its edges"
+    )]
+    #[case(
+        DemTile::from_heights(7, 46, 2, &[1, 2, 3, 4])
+            .with_grid_geometry(GridGeometry::Projected {
+                west_x: 2600000.,
+                north_y: 1200000.,
+                cell_size: 2.,
+            }),
+        "not on a geographic grid"
+    )]
+    fn tiles_without_shared_edges_are_rejected(
+        #[case] tile: DemTile,
+        #[case] reason: &str,
+    ) {
+        let mut tiles = DemTileSet::new();
+        match tiles.insert(tile) {
+            Err(error @ SionError::UnsupportedTileGrid { .. }) => {
+                assert!(error.to_string().contains(reason), "{}", error)
+            }
+            _ => panic!("Expected an UnsupportedTileGrid error"),
+        }
+        assert!(tiles.is_empty());
+        assert_eq!(
+            tiles.elevation_at(7.5, 46.5, InterpolationMethod::Nearest),
+            None
+        );
+    }
+}
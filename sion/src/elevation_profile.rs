@@ -0,0 +1,424 @@
+use crate::elevation::{DemTileSet, InterpolationMethod};
+use crate::errors::SionError;
+use crate::geo::{geodesic_distance_vincenty, geodetic_distance_approximate};
+use crate::grayscale8_bitmap::Grayscale8Bitmap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// How the ground distances between the profile's points are calculated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistanceMethod {
+    /// The haversine distance on a spherical Earth (see
+    /// `geo::geodetic_distance_approximate`), off by up to about 0.5%.
+    Approximate,
+    /// The geodesic distance on the WGS84 ellipsoid (see
+    /// `geo::geodesic_distance_vincenty`).
+    Vincenty,
+}
+
+impl DistanceMethod {
+    /// Returns the distance (in meters) between two points given in degrees.
+    pub fn distance(&self, lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+        match self {
+            DistanceMethod::Approximate => geodetic_distance_approximate(
+                lon1.to_radians() as f32,
+                lat1.to_radians() as f32,
+                lon2.to_radians() as f32,
+                lat2.to_radians() as f32,
+            ) as f64,
+            DistanceMethod::Vincenty => geodesic_distance_vincenty(
+                lon1.to_radians(),
+                lat1.to_radians(),
+                lon2.to_radians(),
+                lat2.to_radians(),
+            ),
+        }
+    }
+}
+
+/// The options for sampling an elevation profile.
+#[derive(Clone, Copy, Debug)]
+pub struct ProfileOptions {
+    /// The ground distance (in meters) between the samples.
+    pub spacing: f64,
+    pub interpolation: InterpolationMethod,
+    pub distance_method: DistanceMethod,
+}
+
+impl Default for ProfileOptions {
+    fn default() -> Self {
+        ProfileOptions {
+            spacing: 30.,
+            interpolation: InterpolationMethod::Bilinear,
+            distance_method: DistanceMethod::Approximate,
+        }
+    }
+}
+
+/// A point of the profile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProfileSample {
+    pub lon: f64,
+    pub lat: f64,
+    /// The ground distance (in meters) from the start of the polyline.
+    pub distance: f64,
+    /// The elevation (in meters), or `None` if the tile is missing or the
+    /// sample falls into a void.
+    pub elevation: Option<f32>,
+}
+
+/// The elevations along a polyline, sampled at a fixed ground spacing.
+#[derive(Clone, Debug)]
+pub struct ElevationProfile {
+    pub samples: Vec<ProfileSample>,
+}
+
+impl ElevationProfile {
+    /// Samples the elevations along the polyline given by its lon/lat
+    /// vertices (in degrees). The samples are placed every
+    /// `options.spacing` meters from the start (across the vertices), plus
+    /// one at the polyline's end. The points are interpolated linearly in
+    /// degrees within each segment, which is fine for the segments of a
+    /// track or a route.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the spacing is not positive.
+    pub fn along_polyline(
+        tiles: &DemTileSet,
+        vertices: &[(f64, f64)],
+        options: &ProfileOptions,
+    ) -> ElevationProfile {
+        if options.spacing.is_nan() || options.spacing <= 0. {
+            panic!("The profile's spacing must be positive");
+        }
+
+        let mut samples = Vec::new();
+        let mut add_sample = |lon: f64, lat: f64, distance: f64| {
+            samples.push(ProfileSample {
+                lon,
+                lat,
+                distance,
+                elevation: tiles.elevation_at(lon, lat, options.interpolation),
+            })
+        };
+
+        let Some(&(start_lon, start_lat)) = vertices.first() else {
+            return ElevationProfile { samples };
+        };
+        add_sample(start_lon, start_lat, 0.);
+
+        let mut segment_start = 0.;
+        let mut next_distance = options.spacing;
+        for segment in vertices.windows(2) {
+            let ((lon1, lat1), (lon2, lat2)) = (segment[0], segment[1]);
+            let length =
+                options.distance_method.distance(lon1, lat1, lon2, lat2);
+            let segment_end = segment_start + length;
+
+            while next_distance < segment_end {
+                let t = (next_distance - segment_start) / length;
+                add_sample(
+                    lon1 + (lon2 - lon1) * t,
+                    lat1 + (lat2 - lat1) * t,
+                    next_distance,
+                );
+                next_distance += options.spacing;
+            }
+
+            segment_start = segment_end;
+        }
+
+        if vertices.len() > 1 {
+            let (end_lon, end_lat) = vertices[vertices.len() - 1];
+            add_sample(end_lon, end_lat, segment_start);
+        }
+
+        ElevationProfile { samples }
+    }
+
+    /// The polyline's length (in meters).
+    pub fn total_distance(&self) -> f64 {
+        self.samples.last().map_or(0., |sample| sample.distance)
+    }
+
+    /// The sum of the elevation gains between the consecutive samples (the
+    /// samples without an elevation are skipped).
+    pub fn total_ascent(&self) -> f32 {
+        self.elevation_changes().filter(|&change| change > 0.).sum()
+    }
+
+    /// The sum of the elevation losses between the consecutive samples, as
+    /// a positive number (the samples without an elevation are skipped).
+    pub fn total_descent(&self) -> f32 {
+        -self
+            .elevation_changes()
+            .filter(|&change| change < 0.)
+            .sum::<f32>()
+    }
+
+    /// The lowest elevation, or `None` if no sample has an elevation.
+    pub fn min_elevation(&self) -> Option<f32> {
+        self.elevations().reduce(f32::min)
+    }
+
+    /// The highest elevation, or `None` if no sample has an elevation.
+    pub fn max_elevation(&self) -> Option<f32> {
+        self.elevations().reduce(f32::max)
+    }
+
+    fn elevations(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().filter_map(|sample| sample.elevation)
+    }
+
+    fn elevation_changes(&self) -> impl Iterator<Item = f32> + '_ {
+        self.elevations()
+            .zip(self.elevations().skip(1))
+            .map(|(previous, next)| next - previous)
+    }
+
+    /// Renders the profile as an area chart: black below the elevation
+    /// line, white above it and in the columns without an elevation. The
+    /// distance runs from the left to the right edge, the elevation from
+    /// the lowest (bottom) to the highest one (top).
+    pub fn to_chart(&self, width: u16, height: u16) -> Grayscale8Bitmap {
+        let mut bitmap = Grayscale8Bitmap::new(width, height);
+        bitmap.data_mut().fill(255);
+
+        let (Some(min), Some(max)) =
+            (self.min_elevation(), self.max_elevation())
+        else {
+            return bitmap;
+        };
+        let range = (max - min).max(1.);
+        let total_distance = self.total_distance();
+
+        for x in 0..width {
+            let distance = if width > 1 {
+                total_distance * x as f64 / (width - 1) as f64
+            } else {
+                0.
+            };
+            let Some(elevation) = self.elevation_at_distance(distance) else {
+                continue;
+            };
+
+            // the share of the chart's height filled in this column
+            let fill = (elevation - min) / range;
+            let filled_rows = 1 + (fill * (height as f32 - 1.)).round() as u16;
+            for y in height.saturating_sub(filled_rows)..height {
+                bitmap.set_pixel(x, y, 0);
+            }
+        }
+
+        bitmap
+    }
+
+    /// Interpolates the elevation linearly between the samples around the
+    /// distance, or returns `None` if either of them has no elevation.
+    fn elevation_at_distance(&self, distance: f64) -> Option<f32> {
+        let next = self
+            .samples
+            .partition_point(|sample| sample.distance < distance)
+            .min(self.samples.len() - 1);
+        let after = &self.samples[next];
+        if next == 0 || after.distance == distance {
+            return after.elevation;
+        }
+
+        let before = &self.samples[next - 1];
+        let t = ((distance - before.distance)
+            / (after.distance - before.distance)) as f32;
+        Some(before.elevation? + (after.elevation? - before.elevation?) * t)
+    }
+
+    /// Returns the samples as CSV, with a `distance,lon,lat,elevation`
+    /// header and empty elevations for the samples without one.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("distance,lon,lat,elevation\n");
+        for sample in &self.samples {
+            let elevation = sample
+                .elevation
+                .map_or(String::new(), |elevation| format!("{:.1}", elevation));
+            csv.push_str(&format!(
+                "{:.1},{:.6},{:.6},{}\n",
+                sample.distance, sample.lon, sample.lat, elevation
+            ));
+        }
+        csv
+    }
+
+    /// Writes the samples into a CSV file, see `to_csv`.
+    pub fn write_csv(&self, file: &str) -> Result<(), SionError> {
+        let cannot_write = |error| SionError::CannotWriteFile {
+            file: file.to_string(),
+            source: error,
+        };
+
+        let mut writer =
+            BufWriter::new(File::create(file).map_err(cannot_write)?);
+        writer
+            .write_all(self.to_csv().as_bytes())
+            .map_err(cannot_write)?;
+        writer.flush().map_err(cannot_write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+    use crate::testing::assert_eq_approx;
+    use std::fs;
+
+    /// A 101x101 tile at 46N 6E rising by 1 meter per cell towards the east.
+    fn east_slope() -> DemTileSet {
+        let heights: Vec<i16> = (0..101 * 101).map(|i| i % 101).collect();
+        let mut tiles = DemTileSet::new();
+        tiles
+            .insert(DemTile::from_heights(6, 46, 101, &heights))
+            .unwrap();
+        tiles
+    }
+
+    #[test]
+    fn samples_are_spaced_evenly_across_the_vertices() {
+        let tiles = east_slope();
+        // two segments along the 46.5N parallel, there and back
+        let vertices = [(6.1, 46.5), (6.5, 46.5), (6.3, 46.5)];
+        let options = ProfileOptions {
+            spacing: 1000.,
+            ..ProfileOptions::default()
+        };
+
+        let profile =
+            ElevationProfile::along_polyline(&tiles, &vertices, &options);
+
+        let leg = DistanceMethod::Approximate.distance(6.1, 46.5, 6.5, 46.5);
+        assert_eq_approx(
+            profile.total_distance() as f32,
+            1.5 * leg as f32,
+            0.1,
+        );
+
+        let samples = &profile.samples;
+        assert_eq!(samples.len(), 1 + (1.5 * leg / 1000.) as usize + 1);
+        for pair in samples[..samples.len() - 1].windows(2) {
+            assert_eq_approx(
+                (pair[1].distance - pair[0].distance) as f32,
+                1000.,
+                0.001,
+            );
+        }
+
+        assert_eq_approx(samples[0].elevation.unwrap(), 10., 0.001);
+        assert_eq_approx(
+            samples[1].lon as f32,
+            6.1 + 0.4 * 1000. / leg as f32,
+            1e-6,
+        );
+        // the highest sample is just before the turning vertex
+        let max = profile.max_elevation().unwrap();
+        assert_eq_approx(max, 50., 1.);
+        assert_eq_approx(profile.min_elevation().unwrap(), 10., 0.001);
+        assert_eq_approx(profile.total_ascent(), max - 10., 0.001);
+        assert_eq_approx(profile.total_descent(), max - 30., 0.001);
+    }
+
+    #[test]
+    fn distance_methods_agree_roughly() {
+        let approximate =
+            DistanceMethod::Approximate.distance(6.1, 46.1, 6.9, 46.8);
+        let vincenty = DistanceMethod::Vincenty.distance(6.1, 46.1, 6.9, 46.8);
+
+        assert!((approximate - vincenty).abs() / vincenty < 0.005);
+    }
+
+    #[test]
+    fn missing_elevations_are_skipped() {
+        let mut heights = vec![100i16; 3 * 3];
+        heights[4] = NO_DATA_HEIGHT;
+        let mut tiles = DemTileSet::new();
+        tiles
+            .insert(DemTile::from_heights(0, 0, 3, &heights))
+            .unwrap();
+
+        let options = ProfileOptions {
+            spacing: 20000.,
+            interpolation: InterpolationMethod::Nearest,
+            ..ProfileOptions::default()
+        };
+        // from the tile's west edge through its void center into a missing
+        // tile
+        let profile = ElevationProfile::along_polyline(
+            &tiles,
+            &[(0., 0.5), (1.5, 0.5)],
+            &options,
+        );
+
+        let elevations: Vec<Option<f32>> =
+            profile.samples.iter().map(|s| s.elevation).collect();
+        assert_eq!(elevations.first(), Some(&Some(100.)));
+        assert!(elevations.contains(&None));
+        assert_eq!(elevations.last(), Some(&None));
+        assert_eq!(profile.total_ascent(), 0.);
+        assert_eq!(profile.total_descent(), 0.);
+
+        let empty = ElevationProfile::along_polyline(&tiles, &[], &options);
+        assert!(empty.samples.is_empty());
+        assert_eq!(empty.max_elevation(), None);
+        assert!(empty
+            .to_chart(4, 4)
+            .data()
+            .iter()
+            .all(|&pixel| pixel == 255));
+    }
+
+    #[test]
+    fn chart_fills_the_area_below_the_profile() {
+        let tiles = east_slope();
+        let profile = ElevationProfile::along_polyline(
+            &tiles,
+            &[(6., 46.5), (7., 46.5)],
+            &ProfileOptions::default(),
+        );
+
+        let chart = profile.to_chart(11, 11);
+        for x in 0..11 {
+            // one more row filled per column
+            let filled =
+                (0..11).filter(|&y| chart.get_pixel(x, y) == 0).count();
+            assert_eq!(filled, x as usize + 1);
+            assert_eq!(chart.get_pixel(x, 10), 0);
+        }
+    }
+
+    #[test]
+    fn profile_is_written_as_csv() {
+        let mut tiles = DemTileSet::new();
+        tiles
+            .insert(DemTile::from_heights(0, 0, 2, &[5, 5, 5, 5]))
+            .unwrap();
+        let profile = ElevationProfile::along_polyline(
+            &tiles,
+            &[(0.5, 0.5), (0.5, -0.5)],
+            &ProfileOptions {
+                spacing: 100000.,
+                ..ProfileOptions::default()
+            },
+        );
+
+        let dir = "target/debug/elevation_profile_tests";
+        fs::create_dir_all(dir).unwrap();
+        let file = format!("{}/profile.csv", dir);
+        profile.write_csv(&file).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "distance,lon,lat,elevation\n\
+            0.0,0.500000,0.500000,5.0\n\
+            100000.0,0.500000,-0.399322,5.0\n\
+            111194.9,0.500000,-0.500000,\n"
+        );
+    }
+}
@@ -1,22 +1,149 @@
 use std::fmt;
+use std::io;
 
+/// Errors reported by the DEM tile loaders.
 #[derive(Debug)]
-pub struct SionError {
-    pub message: String,
+pub enum SionError {
+    /// The file could not be opened or its metadata could not be read.
+    CannotOpenFile { file: String, source: io::Error },
+    /// The file could not be created or written to.
+    CannotWriteFile { file: String, source: io::Error },
+    /// The file does not contain a square number of 16-bit heights.
+    NonSquareHeightsCount { file: String, heights_count: u64 },
+    /// The tile name does not follow the `N46E006` naming convention.
+    InvalidTileName(String),
+    /// The tile's south-west corner is outside of the valid coordinates.
+    InvalidTileCoordinates { lon: i16, lat: i16 },
+    /// The file ended (or failed) before all of its heights could be read.
+    TruncatedRead {
+        file: String,
+        expected_bytes: u64,
+        actual_bytes: u64,
+    },
+    /// The archive could not be read or does not contain the expected
+    /// tiles.
+    InvalidArchive { file: String, reason: String },
+    /// The GeoTIFF file could not be decoded or is not a supported DEM.
+    InvalidGeoTiff { file: String, reason: String },
+    /// The ESRI ASCII grid has an invalid header or values.
+    InvalidAsciiGrid { file: String, reason: String },
+    /// The image could not be decoded or does not hold a supported DEM.
+    InvalidImage { file: String, reason: String },
+    /// The tile source does not have the requested tile.
+    TileNotFound(String),
+    /// The tile's size differs from the size of the other tiles in the set.
+    TileSizeMismatch {
+        lon: i16,
+        lat: i16,
+        size: usize,
+        expected_size: usize,
+    },
+    /// The web map tile is not on the map at its zoom level.
+    InvalidWebTileId { zoom: u8, x: u32, y: u32 },
+    /// The tile's grid is not supported where the tiles are expected to
+    /// cover their degree with shared edges (e.g. in a `DemTileSet`).
+    UnsupportedTileGrid { lon: i16, lat: i16, reason: String },
+    /// The tile is not one of the center tile's eight neighbours.
+    TileNotAdjacent {
+        lon: i16,
+        lat: i16,
+        center_lon: i16,
+        center_lat: i16,
+    },
 }
 
-impl SionError {
-    pub fn new(message: &str) -> SionError {
-        SionError {
-            message: message.to_string(),
+impl fmt::Display for SionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SionError::CannotOpenFile { file, source } => {
+                write!(f, "Problem opening the file '{}': {}", file, source)
+            }
+            SionError::CannotWriteFile { file, source } => {
+                write!(f, "Problem writing the file '{}': {}", file, source)
+            }
+            SionError::NonSquareHeightsCount {
+                file,
+                heights_count,
+            } => write!(
+                f,
+                "The file '{}' does not contain a square number of heights \
+                ({} heights)",
+                file, heights_count
+            ),
+            SionError::InvalidTileName(tile_name) => {
+                write!(f, "Invalid tile name: {}", tile_name)
+            }
+            SionError::InvalidTileCoordinates { lon, lat } => write!(
+                f,
+                "Invalid tile coordinates: {}, {} (expected the longitude \
+                in -180..=179 and the latitude in -90..=89)",
+                lon, lat
+            ),
+            SionError::TruncatedRead {
+                file,
+                expected_bytes,
+                actual_bytes,
+            } => write!(
+                f,
+                "The file '{}' was truncated: expected {} bytes, read {}",
+                file, expected_bytes, actual_bytes
+            ),
+            SionError::InvalidArchive { file, reason } => {
+                write!(f, "Invalid archive '{}': {}", file, reason)
+            }
+            SionError::InvalidGeoTiff { file, reason } => {
+                write!(f, "Invalid GeoTIFF '{}': {}", file, reason)
+            }
+            SionError::InvalidAsciiGrid { file, reason } => {
+                write!(f, "Invalid ASCII grid '{}': {}", file, reason)
+            }
+            SionError::InvalidImage { file, reason } => {
+                write!(f, "Invalid image '{}': {}", file, reason)
+            }
+            SionError::TileNotFound(tile_name) => {
+                write!(f, "Tile not found: {}", tile_name)
+            }
+            SionError::TileSizeMismatch {
+                lon,
+                lat,
+                size,
+                expected_size,
+            } => write!(
+                f,
+                "The tile at {}, {} has {} cells per side, expected {}",
+                lon, lat, size, expected_size
+            ),
+            SionError::InvalidWebTileId { zoom, x, y } => write!(
+                f,
+                "Invalid web tile: {}/{}/{} (expected the zoom in 0..=30 \
+                and the column and row below 2^zoom)",
+                zoom, x, y
+            ),
+            SionError::UnsupportedTileGrid { lon, lat, reason } => write!(
+                f,
+                "The grid of the tile at {}, {} is not supported: {}",
+                lon, lat, reason
+            ),
+            SionError::TileNotAdjacent {
+                lon,
+                lat,
+                center_lon,
+                center_lat,
+            } => write!(
+                f,
+                "The tile at {}, {} is not a neighbour of the tile at {}, {}",
+                lon, lat, center_lon, center_lat
+            ),
         }
     }
 }
 
-impl fmt::Display for SionError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
+impl std::error::Error for SionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SionError::CannotOpenFile { source, .. }
+            | SionError::CannotWriteFile { source, .. } => Some(source),
+            _ => None,
+        }
     }
 }
-
-impl std::error::Error for SionError {}
\ No newline at end of file
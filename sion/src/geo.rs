@@ -3,7 +3,6 @@ use std::f32::consts::TAU;
 
 /// Calculates an approximate geodetic distance (in meters) between two points
 /// on Earth. Not suitable for high-precision calculations.
-#[allow(dead_code)]
 pub fn geodetic_distance_approximate(
     lon1: f32,
     lat1: f32,
@@ -20,6 +19,96 @@ pub fn geodetic_distance_approximate(
     EARTH_RADIUS_METERS * c
 }
 
+/// The WGS84 ellipsoid's semi-major axis (in meters).
+const WGS84_SEMI_MAJOR_AXIS: f64 = 6378137.0;
+/// The WGS84 ellipsoid's flattening.
+const WGS84_FLATTENING: f64 = 1. / 298.257223563;
+
+/// Calculates the geodesic distance (in meters) between two points on the
+/// WGS84 ellipsoid using Vincenty's inverse formula (with the coordinates in
+/// radians). Accurate to within millimeters, but slower than
+/// `geodetic_distance_approximate`. For the nearly antipodal points where
+/// the iteration does not converge, the last estimate is returned.
+pub fn geodesic_distance_vincenty(
+    lon1: f64,
+    lat1: f64,
+    lon2: f64,
+    lat2: f64,
+) -> f64 {
+    let a = WGS84_SEMI_MAJOR_AXIS;
+    let f = WGS84_FLATTENING;
+    let b = a * (1. - f);
+
+    let l = lon2 - lon1;
+    let u1 = ((1. - f) * lat1.tan()).atan();
+    let u2 = ((1. - f) * lat2.tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    let mut sin_sigma = 0.;
+    let mut cos_sigma = 1.;
+    let mut sigma = 0.;
+    let mut cos_sq_alpha = 1.;
+    let mut cos_2sigma_m = 0.;
+
+    for _ in 0..200 {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0. {
+            // coincident points
+            return 0.;
+        }
+
+        cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        cos_sq_alpha = 1. - sin_alpha * sin_alpha;
+        // the points on the equator have cos_sq_alpha = 0
+        cos_2sigma_m = if cos_sq_alpha == 0. {
+            0.
+        } else {
+            cos_sigma - 2. * sin_u1 * sin_u2 / cos_sq_alpha
+        };
+
+        let c = f / 16. * cos_sq_alpha * (4. + f * (4. - 3. * cos_sq_alpha));
+        let previous_lambda = lambda;
+        lambda = l
+            + (1. - c)
+                * f
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m
+                            + c * cos_sigma
+                                * (-1. + 2. * cos_2sigma_m * cos_2sigma_m)));
+
+        if (lambda - previous_lambda).abs() < 1e-12 {
+            break;
+        }
+    }
+
+    let u_sq = cos_sq_alpha * (a * a - b * b) / (b * b);
+    let big_a = 1.
+        + u_sq / 16384.
+            * (4096. + u_sq * (-768. + u_sq * (320. - 175. * u_sq)));
+    let big_b =
+        u_sq / 1024. * (256. + u_sq * (-128. + u_sq * (74. - 47. * u_sq)));
+    let delta_sigma = big_b
+        * sin_sigma
+        * (cos_2sigma_m
+            + big_b / 4.
+                * (cos_sigma * (-1. + 2. * cos_2sigma_m * cos_2sigma_m)
+                    - big_b / 6.
+                        * cos_2sigma_m
+                        * (-3. + 4. * sin_sigma * sin_sigma)
+                        * (-3. + 4. * cos_2sigma_m * cos_2sigma_m)));
+
+    b * big_a * (sigma - delta_sigma)
+}
+
 pub fn normalize_angle(angle: f32) -> f32 {
     let angle_remainder = angle % TAU;
 
@@ -43,7 +132,10 @@ pub fn difference_between_angles(a1: f32, a2: f32) -> f32 {
 
 #[cfg(test)]
 mod tests {
-    use crate::geo::difference_between_angles;
+    use crate::geo::{
+        difference_between_angles, geodesic_distance_vincenty,
+        geodetic_distance_approximate,
+    };
     use crate::testing::assert_eq_approx;
     use rstest::rstest;
     use std::f32::consts::PI;
@@ -66,4 +158,30 @@ mod tests {
     ) {
         assert_eq_approx(difference_between_angles(a1, a2), expected, 0.000001);
     }
+
+    #[test]
+    fn geodesic_distances() {
+        let deg = |degrees: f64, minutes: f64, seconds: f64| {
+            (degrees.abs() + minutes / 60. + seconds / 3600.).to_radians()
+                * degrees.signum()
+        };
+
+        // Vincenty's own test case: Flinders Peak to Buninyong
+        let distance = geodesic_distance_vincenty(
+            deg(144., 25., 29.52440),
+            deg(-37., 57., 3.72030),
+            deg(143., 55., 35.38390),
+            deg(-37., 39., 10.15610),
+        );
+        assert!((distance - 54972.271).abs() < 0.001, "{}", distance);
+
+        assert_eq!(geodesic_distance_vincenty(0.1, 0.2, 0.1, 0.2), 0.);
+
+        // one degree of latitude on the sphere
+        assert_eq_approx(
+            geodetic_distance_approximate(0., 0., 0., 1f32.to_radians()),
+            111194.9,
+            1.,
+        );
+    }
 }
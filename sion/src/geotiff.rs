@@ -0,0 +1,590 @@
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+use crate::errors::SionError;
+use crate::grid_spacing::GridGeometry;
+use std::fs::File;
+use std::io::BufReader;
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::tags::Tag;
+
+impl DemTile {
+    /// Loads a DEM tile from a single-band Int16 or Float32 GeoTIFF file
+    /// (like the Copernicus GLO-30 and GLO-90 tiles). Both stripped and tiled
+    /// files are supported, as are the deflate/LZW compressed ones.
+    ///
+    /// The tile's coordinates are taken from the GeoTIFF tie point and pixel
+    /// scale, the cells matching the `GDAL_NODATA` value (and the Float32
+    /// NaN cells) are set to `NO_DATA_HEIGHT`. Float32 heights are rounded to
+    /// whole meters.
+    ///
+    /// The rasters whose pixels do not lie on the tile's edges (like the
+    /// Copernicus 3600x3600 ones) get their `GridGeometry` set. The rasters
+    /// narrower than they are tall (like the Copernicus tiles above 50°
+    /// latitude) have their columns resampled linearly to the number of
+    /// rows, so the tile's cells span the same angle in both directions.
+    pub fn from_geotiff_file(file: &str) -> Result<DemTile, SionError> {
+        let invalid = |reason: String| SionError::InvalidGeoTiff {
+            file: file.to_string(),
+            reason,
+        };
+
+        let tiff_file =
+            File::open(file).map_err(|error| SionError::CannotOpenFile {
+                file: file.to_string(),
+                source: error,
+            })?;
+
+        let mut decoder = Decoder::new(BufReader::new(tiff_file))
+            .map_err(|e| invalid(format!("cannot decode the file: {}", e)))?
+            .with_limits(Limits::unlimited());
+
+        let (width, height) = decoder
+            .dimensions()
+            .map_err(|e| invalid(format!("cannot read dimensions: {}", e)))?;
+        let (width, height) = (width as usize, height as usize);
+
+        if width > height || width < 2 {
+            return Err(invalid(format!(
+                "expected a raster at most as wide as it is tall ({}x{})",
+                width, height
+            )));
+        }
+
+        let samples_per_pixel = decoder
+            .find_tag_unsigned::<u16>(Tag::SamplesPerPixel)
+            .map_err(|e| invalid(e.to_string()))?
+            .unwrap_or(1);
+        if samples_per_pixel != 1 {
+            return Err(invalid(format!(
+                "expected a single band, found {}",
+                samples_per_pixel
+            )));
+        }
+
+        let bounds = RasterBounds::read(&mut decoder)
+            .map_err(|e| invalid(format!("missing georeferencing: {}", e)))?;
+        let (lon, lat) = bounds
+            .tile_coordinates()
+            .map_err(|e| invalid(format!("missing georeferencing: {}", e)))?;
+        let no_data_value = read_no_data_value(&mut decoder);
+
+        let heights: Vec<i16> = match decoder
+            .read_image()
+            .map_err(|e| invalid(format!("cannot read the raster: {}", e)))?
+        {
+            DecodingResult::I16(values) => values
+                .into_iter()
+                .map(|value| {
+                    if no_data_value == Some(value as f64) {
+                        NO_DATA_HEIGHT
+                    } else {
+                        value
+                    }
+                })
+                .collect(),
+            DecodingResult::F32(values) => values
+                .into_iter()
+                .map(|value| {
+                    if value.is_nan()
+                        || no_data_value.map(|v| v as f32) == Some(value)
+                    {
+                        NO_DATA_HEIGHT
+                    } else {
+                        let min_height = (NO_DATA_HEIGHT + 1) as f32;
+                        value.round().clamp(min_height, i16::MAX as f32) as i16
+                    }
+                })
+                .collect(),
+            _ => {
+                return Err(invalid(
+                    "only Int16 and Float32 rasters are supported".to_string(),
+                ))
+            }
+        };
+
+        // the coordinates of the first row's and column's pixel centers
+        // (the columns are resampled to the number of rows below)
+        let north_lat = bounds.top - bounds.pixel_height / 2.;
+        let column_width = bounds.pixel_width * width as f64 / height as f64;
+        let west_lon = bounds.left + column_width / 2.;
+        let shares_edges =
+            (north_lat - (lat + 1) as f64).abs() < bounds.pixel_height / 4.;
+
+        let heights = if width == height {
+            heights
+        } else {
+            resample_columns(&heights, width, height, shares_edges)
+        };
+
+        let tile = DemTile::from_heights(lon, lat, height, &heights);
+        if shares_edges {
+            Ok(tile)
+        } else {
+            Ok(tile.with_grid_geometry(GridGeometry::Geographic {
+                north_lat,
+                west_lon,
+                cell_size: bounds.pixel_height,
+            }))
+        }
+    }
+}
+
+/// Resamples each row of the `width` x `height` raster to `height` columns,
+/// interpolating linearly between the two nearest source columns. The
+/// raster's first and last columns either lie on the tile's edges
+/// (`shares_edges`) or half a pixel inside them.
+fn resample_columns(
+    heights: &[i16],
+    width: usize,
+    height: usize,
+    shares_edges: bool,
+) -> Vec<i16> {
+    let last = (width - 1) as f64;
+    let columns: Vec<(usize, f32)> = (0..height)
+        .map(|column| {
+            let position = if shares_edges {
+                column as f64 * last / (height - 1) as f64
+            } else {
+                (column as f64 + 0.5) * width as f64 / height as f64 - 0.5
+            };
+            let position = position.clamp(0., last);
+            let base = (position.floor() as usize).min(width - 2);
+            (base, (position - base as f64) as f32)
+        })
+        .collect();
+
+    heights
+        .chunks_exact(width)
+        .flat_map(|row| {
+            columns.iter().map(move |&(base, fraction)| {
+                let (left, right) = (row[base], row[base + 1]);
+                if fraction == 0. {
+                    left
+                } else if fraction == 1. {
+                    right
+                } else if left == NO_DATA_HEIGHT || right == NO_DATA_HEIGHT {
+                    NO_DATA_HEIGHT
+                } else {
+                    (left as f32 * (1. - fraction) + right as f32 * fraction)
+                        .round() as i16
+                }
+            })
+        })
+        .collect()
+}
+
+/// The raster's top-left corner and pixel size, in degrees.
+struct RasterBounds {
+    left: f64,
+    top: f64,
+    pixel_width: f64,
+    pixel_height: f64,
+}
+
+impl RasterBounds {
+    /// Reads the bounds from the GeoTIFF tie point and pixel scale.
+    fn read(
+        decoder: &mut Decoder<BufReader<File>>,
+    ) -> Result<RasterBounds, String> {
+        let tie_point = decoder
+            .get_tag_f64_vec(Tag::ModelTiepointTag)
+            .map_err(|e| e.to_string())?;
+        let pixel_scale = decoder
+            .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+            .map_err(|e| e.to_string())?;
+
+        if tie_point.len() < 6 || pixel_scale.len() < 2 {
+            return Err("invalid tie point or pixel scale".to_string());
+        }
+
+        let (raster_x, raster_y) = (tie_point[0], tie_point[1]);
+        let (model_x, model_y) = (tie_point[3], tie_point[4]);
+        let (pixel_width, pixel_height) = (pixel_scale[0], pixel_scale[1]);
+
+        Ok(RasterBounds {
+            left: model_x - raster_x * pixel_width,
+            top: model_y + raster_y * pixel_height,
+            pixel_width,
+            pixel_height,
+        })
+    }
+
+    /// Calculates the (integer) coordinates of the tile's south-west
+    /// corner. Works both for the rasters whose pixels are centered on the
+    /// tile's edges (where the top-left corner is half a pixel outside the
+    /// whole degree) and the ones whose pixels lie inside the tile.
+    fn tile_coordinates(&self) -> Result<(i16, i16), String> {
+        let lon = (self.left + self.pixel_width / 2.).round();
+        let lat = (self.top - self.pixel_height / 2.).round() - 1.;
+
+        if !(-180. ..180.).contains(&lon) || !(-90. ..90.).contains(&lat) {
+            return Err(format!("coordinates out of range: {}, {}", lon, lat));
+        }
+
+        Ok((lon as i16, lat as i16))
+    }
+}
+
+fn read_no_data_value(decoder: &mut Decoder<BufReader<File>>) -> Option<f64> {
+    decoder
+        .get_tag_ascii_string(Tag::GdalNodata)
+        .ok()
+        .and_then(|value| {
+            value
+                .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+                .parse::<f64>()
+                .ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+    use crate::errors::SionError;
+    use crate::grid_spacing::GridGeometry;
+    use crate::testing::assert_eq_approx;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use tiff::encoder::colortype::{ColorType, Gray32Float, GrayI16};
+    use tiff::encoder::compression::Deflate;
+    use tiff::encoder::{DirectoryEncoder, TiffEncoder, TiffKind};
+    use tiff::tags::Tag;
+
+    /// Returns the path of the file in the `target/debug` directory.
+    fn test_file_path(file_name: &str) -> String {
+        let dir = "target/debug/geotiff_tests";
+        fs::create_dir_all(dir).unwrap();
+        format!("{}/{}", dir, file_name)
+    }
+
+    /// Writes the GeoTIFF tags into the image's directory.
+    fn write_geo_tags<K: TiffKind>(
+        directory: &mut DirectoryEncoder<File, K>,
+        pixel_scale: (f64, f64),
+        tie_point: Option<[f64; 6]>,
+        no_data: Option<&str>,
+    ) {
+        directory
+            .write_tag(
+                Tag::ModelPixelScaleTag,
+                &[pixel_scale.0, pixel_scale.1, 0.][..],
+            )
+            .unwrap();
+        if let Some(tie_point) = tie_point {
+            directory
+                .write_tag(Tag::ModelTiepointTag, &tie_point[..])
+                .unwrap();
+        }
+        if let Some(no_data) = no_data {
+            directory.write_tag(Tag::GdalNodata, no_data).unwrap();
+        }
+    }
+
+    /// Writes a deflate-compressed, stripped single-band GeoTIFF file into
+    /// the `target/debug` directory and returns its path.
+    fn write_geotiff<C: ColorType>(
+        file_name: &str,
+        (width, height): (u32, u32),
+        pixel_scale: (f64, f64),
+        heights: &[C::Inner],
+        tie_point: Option<[f64; 6]>,
+        no_data: Option<&str>,
+    ) -> String
+    where
+        [C::Inner]: tiff::encoder::TiffValue,
+    {
+        let path = test_file_path(file_name);
+
+        let mut encoder =
+            TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut image = encoder
+            .new_image_with_compression::<C, _>(
+                width,
+                height,
+                Deflate::default(),
+            )
+            .unwrap();
+        write_geo_tags(image.encoder(), pixel_scale, tie_point, no_data);
+
+        image.write_data(heights).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_int16_geotiff() {
+        // a raster whose pixels are centered on the tile's edges, like the
+        // SRTM GeoTIFFs
+        let half_pixel = 0.5 / 2.;
+        let file = write_geotiff::<GrayI16>(
+            "int16.tif",
+            (3, 3),
+            (0.5, 0.5),
+            &[100, 200, 300, -400, -9999, 600, 700, 800, 32767],
+            Some([0., 0., 0., 6. - half_pixel, 47. + half_pixel, 0.]),
+            Some("-9999"),
+        );
+
+        let tile = DemTile::from_geotiff_file(&file).unwrap();
+        assert_eq!(tile.lon, 6);
+        assert_eq!(tile.lat, 46);
+        assert_eq!(tile.size, 3);
+        assert_eq!(tile.grid_geometry(), None);
+        assert!(tile.shares_edges());
+        assert_eq!(tile.height_at(0, 0), 100);
+        assert_eq!(tile.height_at(2, 0), 300);
+        assert_eq!(tile.height_at(0, 1), -400);
+        assert_eq!(tile.height_at(1, 1), NO_DATA_HEIGHT);
+        assert_eq!(tile.height_at(2, 2), 32767);
+    }
+
+    #[test]
+    fn read_float32_geotiff() {
+        // a raster whose pixels lie inside the tile, in the south-western
+        // hemisphere
+        let file = write_geotiff::<Gray32Float>(
+            "float32.tif",
+            (2, 2),
+            (0.5, 0.5),
+            &[1234.4, f32::NAN, -3.4028235e38, -12.6],
+            Some([0., 0., 0., -123., -45., 0.]),
+            Some("-3.4028234663852886e+38"),
+        );
+
+        let tile = DemTile::from_geotiff_file(&file).unwrap();
+        assert_eq!(tile.lon, -123);
+        assert_eq!(tile.lat, -46);
+        assert_eq!(tile.size, 2);
+        assert_eq!(
+            tile.grid_geometry(),
+            Some(GridGeometry::Geographic {
+                north_lat: -45.25,
+                west_lon: -122.75,
+                cell_size: 0.5
+            })
+        );
+        assert_eq!(tile.height_at(0, 0), 1234);
+        assert_eq!(tile.height_at(1, 0), NO_DATA_HEIGHT);
+        assert_eq!(tile.height_at(0, 1), NO_DATA_HEIGHT);
+        assert_eq!(tile.height_at(1, 1), -13);
+    }
+
+    /// The Copernicus 3600x3600 tiles have no shared edges: their pixels
+    /// are half a pixel inside the tile.
+    #[test]
+    fn read_copernicus_tile() {
+        let size = 3600;
+        let heights: Vec<i16> = (0..size * size)
+            .map(|index| (index / size) as i16)
+            .collect();
+        let pixel_size = 1. / size as f64;
+        let file = write_geotiff::<GrayI16>(
+            "copernicus.tif",
+            (size as u32, size as u32),
+            (pixel_size, pixel_size),
+            &heights,
+            Some([0., 0., 0., 7., 47., 0.]),
+            None,
+        );
+
+        let tile = DemTile::from_geotiff_file(&file).unwrap();
+        assert_eq!(tile.lon, 7);
+        assert_eq!(tile.lat, 46);
+        assert_eq!(tile.size, 3600);
+        assert!(!tile.shares_edges());
+        assert_eq!(tile.height_at(0, 3599), 3599);
+
+        let grid = GridGeometry::of_tile(&tile);
+        assert!(matches!(
+            grid,
+            GridGeometry::Geographic { cell_size, .. } if cell_size == pixel_size
+        ));
+        assert_eq_approx(
+            grid.row_lat(0).unwrap() as f32,
+            47. - 0.5 / 3600.,
+            1e-6,
+        );
+        assert_eq_approx(
+            grid.row_lat(3599).unwrap() as f32,
+            46. + 0.5 / 3600.,
+            1e-6,
+        );
+    }
+
+    /// The Copernicus tiles above 50° latitude are narrower than they are
+    /// tall, their columns are resampled to the number of rows.
+    #[test]
+    fn read_narrow_geotiff() {
+        let row = [0, 100, 200, 300];
+        let mut heights: Vec<i16> = row.repeat(6);
+        heights[23] = -32767;
+        let file = write_geotiff::<GrayI16>(
+            "narrow.tif",
+            (4, 6),
+            (1. / 4., 1. / 6.),
+            &heights,
+            Some([0., 0., 0., 12., 61., 0.]),
+            Some("-32767"),
+        );
+
+        let tile = DemTile::from_geotiff_file(&file).unwrap();
+        assert_eq!(tile.lon, 12);
+        assert_eq!(tile.lat, 60);
+        assert_eq!(tile.size, 6);
+        assert_eq!(
+            tile.grid_geometry(),
+            Some(GridGeometry::Geographic {
+                north_lat: 61. - 1. / 12.,
+                west_lon: 12. + 1. / 12.,
+                cell_size: 1. / 6.
+            })
+        );
+
+        let mut resampled_row = [0; 6];
+        tile.read_row(0, &mut resampled_row);
+        // the outermost cells are half a (resampled) pixel from the edges
+        assert_eq!(resampled_row, [0, 50, 117, 183, 250, 300]);
+        tile.read_row(5, &mut resampled_row);
+        assert_eq!(
+            resampled_row,
+            [0, 50, 117, 183, NO_DATA_HEIGHT, NO_DATA_HEIGHT]
+        );
+
+        // a narrow raster with its pixels centered on the tile's edges
+        let file = write_geotiff::<GrayI16>(
+            "narrow_shared_edges.tif",
+            (3, 5),
+            (1. / 2., 1. / 4.),
+            &[0, 100, 200].repeat(5),
+            Some([0., 0., 0., 11.75, 61.125, 0.]),
+            None,
+        );
+
+        let tile = DemTile::from_geotiff_file(&file).unwrap();
+        assert_eq!((tile.lon, tile.lat, tile.size), (12, 60, 5));
+        assert_eq!(tile.grid_geometry(), None);
+        let mut resampled_row = [0; 5];
+        tile.read_row(4, &mut resampled_row);
+        assert_eq!(resampled_row, [0, 50, 100, 150, 200]);
+    }
+
+    #[test]
+    fn read_tiled_geotiff() {
+        let (size, tile_size) = (20, 16);
+        let height = |x: usize, y: usize| (x * 100 + y) as i16;
+
+        let path = test_file_path("tiled.tif");
+        let mut encoder =
+            TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut directory = encoder.new_directory().unwrap();
+
+        // the deflate-compressed 16x16 tiles, padded beyond the raster
+        let (mut offsets, mut byte_counts) = (Vec::new(), Vec::new());
+        for tile_y in (0..size).step_by(tile_size) {
+            for tile_x in (0..size).step_by(tile_size) {
+                let tile_heights: Vec<i16> = (0..tile_size * tile_size)
+                    .map(|index| {
+                        let x = tile_x + index % tile_size;
+                        let y = tile_y + index / tile_size;
+                        if x < size && y < size {
+                            height(x, y)
+                        } else {
+                            0
+                        }
+                    })
+                    .collect();
+                let bytes: Vec<u8> =
+                    tile_heights.iter().flat_map(|h| h.to_ne_bytes()).collect();
+                let mut deflate =
+                    ZlibEncoder::new(Vec::new(), Compression::default());
+                deflate.write_all(&bytes).unwrap();
+                let compressed = deflate.finish().unwrap();
+
+                offsets.push(directory.write_data(&compressed[..]).unwrap()
+                    as u32);
+                byte_counts.push(compressed.len() as u32);
+            }
+        }
+
+        directory.write_tag(Tag::ImageWidth, size as u32).unwrap();
+        directory.write_tag(Tag::ImageLength, size as u32).unwrap();
+        directory.write_tag(Tag::BitsPerSample, 16u16).unwrap();
+        directory.write_tag(Tag::SampleFormat, 2u16).unwrap();
+        directory.write_tag(Tag::SamplesPerPixel, 1u16).unwrap();
+        directory.write_tag(Tag::Compression, 8u16).unwrap();
+        directory
+            .write_tag(Tag::PhotometricInterpretation, 1u16)
+            .unwrap();
+        directory
+            .write_tag(Tag::TileWidth, tile_size as u32)
+            .unwrap();
+        directory
+            .write_tag(Tag::TileLength, tile_size as u32)
+            .unwrap();
+        directory.write_tag(Tag::TileOffsets, &offsets[..]).unwrap();
+        directory
+            .write_tag(Tag::TileByteCounts, &byte_counts[..])
+            .unwrap();
+        let pixel_size = 1. / size as f64;
+        write_geo_tags(
+            &mut directory,
+            (pixel_size, pixel_size),
+            Some([0., 0., 0., 8., -10., 0.]),
+            None,
+        );
+        directory.finish().unwrap();
+
+        let tile = DemTile::from_geotiff_file(&path).unwrap();
+        assert_eq!((tile.lon, tile.lat, tile.size), (8, -11, 20));
+        for y in 0..size {
+            for x in 0..size {
+                assert_eq!(tile.height_at(x as u16, y as u16), height(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn geotiff_without_tie_point_is_rejected() {
+        let file = write_geotiff::<GrayI16>(
+            "no_tie_point.tif",
+            (2, 2),
+            (0.5, 0.5),
+            &[1, 2, 3, 4],
+            None,
+            None,
+        );
+
+        match DemTile::from_geotiff_file(&file) {
+            Err(SionError::InvalidGeoTiff { reason, .. }) => {
+                assert!(reason.starts_with("missing georeferencing"))
+            }
+            _ => panic!("Expected an InvalidGeoTiff error"),
+        }
+    }
+
+    #[test]
+    fn geotiff_wider_than_tall_is_rejected() {
+        let file = write_geotiff::<GrayI16>(
+            "wide.tif",
+            (3, 2),
+            (1. / 3., 0.5),
+            &[1, 2, 3, 4, 5, 6],
+            Some([0., 0., 0., 6., 47., 0.]),
+            None,
+        );
+
+        assert!(matches!(
+            DemTile::from_geotiff_file(&file),
+            Err(SionError::InvalidGeoTiff { .. })
+        ));
+    }
+
+    #[test]
+    fn missing_geotiff_cannot_be_opened() {
+        assert!(matches!(
+            DemTile::from_geotiff_file("target/debug/missing/dem.tif"),
+            Err(SionError::CannotOpenFile { .. })
+        ));
+    }
+}
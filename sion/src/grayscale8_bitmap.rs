@@ -43,9 +43,9 @@ impl Grayscale8Bitmap {
             panic!("Pixel coordinates out of bounds");
         }
 
-        let index = (x as u32)
+        let index = (y as u32)
             .checked_mul(self.width as u32)
-            .and_then(|w| w.checked_add(y as u32))
+            .and_then(|w| w.checked_add(x as u32))
             .expect("Overflow in pixel index calculation");
         self.data[index as usize] = value;
     }
@@ -154,6 +154,20 @@ mod tests {
         assert_eq!(bitmap.get_pixel(3, 4), 255);
     }
 
+    /// The pixels are stored row by row, also in a non-square bitmap (the
+    /// set pixels used to be transposed).
+    #[test]
+    fn pixels_are_set_row_by_row() {
+        let mut bitmap = Grayscale8Bitmap::new(3, 2);
+        bitmap.set_pixel(2, 0, 10);
+        bitmap.set_pixel(0, 1, 20);
+        bitmap.set_pixel(2, 1, 30);
+
+        assert_eq!(bitmap.data(), [0, 0, 10, 20, 0, 30]);
+        assert_eq!(bitmap.get_pixel(2, 0), 10);
+        assert_eq!(bitmap.get_pixel(0, 1), 20);
+    }
+
     /// The bitmap can be written to a PNG file.
     #[test]
     fn write_to_png() {
@@ -0,0 +1,308 @@
+use crate::dem_tile::DemTile;
+use std::f64::consts::PI;
+
+/// Where a DEM's cells lie on the globe.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GridGeometry {
+    /// The cells are spaced evenly in degrees of latitude and longitude:
+    /// the latitude of the northernmost row, the longitude of the
+    /// westernmost column and the distance between the cells (in degrees).
+    Geographic {
+        north_lat: f64,
+        west_lon: f64,
+        cell_size: f64,
+    },
+    /// The cells are the pixels of a Web Mercator map tile: the distances
+    /// of the first row's and the first column's pixel centers from the
+    /// map's north and west edges and the map's width, all in pixels at
+    /// the tile's zoom level.
+    WebMercator {
+        north_row: f64,
+        west_column: f64,
+        world_size: f64,
+    },
+    /// The cells are spaced evenly in a projected reference system (e.g. a
+    /// national grid): the easting of the westernmost column's and the
+    /// northing of the northernmost row's cell centers and the distance
+    /// between the cells, all in the heights' units.
+    Projected {
+        west_x: f64,
+        north_y: f64,
+        cell_size: f64,
+    },
+}
+
+impl GridGeometry {
+    /// The tile's geometry: the one set by `DemTile::with_grid_geometry`
+    /// or, for a regular tile, the `regular` one.
+    pub fn of_tile(dem: &DemTile) -> GridGeometry {
+        dem.grid_geometry().unwrap_or_else(|| {
+            GridGeometry::regular(dem.lon, dem.lat, dem.size)
+        })
+    }
+
+    /// The geometry of a regular tile of `size` cells per side: covering a
+    /// degree from its coordinates with `size - 1` intervals in each
+    /// direction.
+    pub fn regular(lon: i16, lat: i16, size: usize) -> GridGeometry {
+        GridGeometry::Geographic {
+            north_lat: lat as f64 + 1.,
+            west_lon: lon as f64,
+            cell_size: 1. / (size - 1) as f64,
+        }
+    }
+
+    /// The fractional cell coordinates (`0, 0` being the north-west cell)
+    /// of the point, `None` for a projected grid.
+    pub fn cell_at(&self, lon: f64, lat: f64) -> Option<(f64, f64)> {
+        match *self {
+            GridGeometry::Geographic {
+                north_lat,
+                west_lon,
+                cell_size,
+            } => Some((
+                (lon - west_lon) / cell_size,
+                (north_lat - lat) / cell_size,
+            )),
+            GridGeometry::WebMercator {
+                north_row,
+                west_column,
+                world_size,
+            } => {
+                let mercator_y = lat.to_radians().tan().asinh();
+                Some((
+                    (lon + 180.) / 360. * world_size - west_column,
+                    (1. - mercator_y / PI) / 2. * world_size - north_row,
+                ))
+            }
+            GridGeometry::Projected { .. } => None,
+        }
+    }
+
+    /// The latitude (in degrees) of the row, `None` for a projected grid.
+    pub fn row_lat(&self, y: usize) -> Option<f64> {
+        match *self {
+            GridGeometry::Geographic {
+                north_lat,
+                cell_size,
+                ..
+            } => Some(north_lat - y as f64 * cell_size),
+            GridGeometry::WebMercator {
+                north_row,
+                world_size,
+                ..
+            } => {
+                let mercator_y =
+                    PI * (1. - 2. * (north_row + y as f64) / world_size);
+                Some(mercator_y.sinh().atan().to_degrees())
+            }
+            GridGeometry::Projected { .. } => None,
+        }
+    }
+
+    /// The geometry of the grid padded by `cells` cells on each side.
+    pub fn padded(&self, cells: usize) -> GridGeometry {
+        let padding = cells as f64;
+        match *self {
+            GridGeometry::Geographic {
+                north_lat,
+                west_lon,
+                cell_size,
+            } => GridGeometry::Geographic {
+                north_lat: north_lat + padding * cell_size,
+                west_lon: west_lon - padding * cell_size,
+                cell_size,
+            },
+            GridGeometry::WebMercator {
+                north_row,
+                west_column,
+                world_size,
+            } => GridGeometry::WebMercator {
+                north_row: north_row - padding,
+                west_column: west_column - padding,
+                world_size,
+            },
+            GridGeometry::Projected {
+                west_x,
+                north_y,
+                cell_size,
+            } => GridGeometry::Projected {
+                west_x: west_x - padding * cell_size,
+                north_y: north_y + padding * cell_size,
+                cell_size,
+            },
+        }
+    }
+
+    /// The geometry of the grid resampled from `size` to `new_size` cells
+    /// per side, keeping its edge cells in place.
+    pub fn resampled(&self, size: usize, new_size: usize) -> GridGeometry {
+        let scale = (new_size - 1) as f64 / (size - 1) as f64;
+        match *self {
+            GridGeometry::Geographic {
+                north_lat,
+                west_lon,
+                cell_size,
+            } => GridGeometry::Geographic {
+                north_lat,
+                west_lon,
+                cell_size: cell_size / scale,
+            },
+            GridGeometry::WebMercator {
+                north_row,
+                west_column,
+                world_size,
+            } => GridGeometry::WebMercator {
+                north_row: north_row * scale,
+                west_column: west_column * scale,
+                world_size: world_size * scale,
+            },
+            GridGeometry::Projected {
+                west_x,
+                north_y,
+                cell_size,
+            } => GridGeometry::Projected {
+                west_x,
+                north_y,
+                cell_size: cell_size / scale,
+            },
+        }
+    }
+}
+
+/// The ground distances between the neighbouring cells of a DEM on a
+/// spherical Earth, for each of the DEM's rows. On a geographic grid the
+/// north-south spacing is the same for all the rows and the east-west one
+/// shrinks with the cosine of each row's latitude, on a Web Mercator grid
+/// both shrink with it. On a projected grid both are the cell size.
+pub struct GridSpacing {
+    north_south: Vec<f32>,
+    east_west: Vec<f32>,
+}
+
+impl GridSpacing {
+    /// Calculates the spacing (in the units of `length_of_degree`, the
+    /// length of a degree of latitude) for each of the grid's `rows`. A
+    /// projected grid's spacing is its cell size, `length_of_degree` is
+    /// not used for it.
+    pub fn new(
+        geometry: &GridGeometry,
+        rows: usize,
+        length_of_degree: f32,
+    ) -> GridSpacing {
+        let cos_lat = |y| {
+            geometry.row_lat(y).map_or(1., |lat| lat.to_radians().cos()) as f32
+        };
+
+        let (north_south, east_west) = match *geometry {
+            GridGeometry::Geographic { cell_size, .. } => {
+                let north_south = cell_size as f32 * length_of_degree;
+                (
+                    vec![north_south; rows],
+                    (0..rows).map(|y| north_south * cos_lat(y)).collect(),
+                )
+            }
+            GridGeometry::WebMercator { world_size, .. } => {
+                // the pixel size at the equator
+                let equator = (360. / world_size) as f32 * length_of_degree;
+                let spacing: Vec<f32> =
+                    (0..rows).map(|y| equator * cos_lat(y)).collect();
+                (spacing.clone(), spacing)
+            }
+            GridGeometry::Projected { cell_size, .. } => {
+                let spacing = vec![cell_size as f32; rows];
+                (spacing.clone(), spacing)
+            }
+        };
+
+        GridSpacing {
+            north_south,
+            east_west,
+        }
+    }
+
+    /// The north-south spacing of the row.
+    pub fn north_south(&self, y: usize) -> f32 {
+        self.north_south[y]
+    }
+
+    /// The east-west spacing of the row.
+    pub fn east_west(&self, y: usize) -> f32 {
+        self.east_west[y]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_eq_approx;
+
+    #[test]
+    fn east_west_spacing_shrinks_towards_the_poles() {
+        let dem = DemTile::from_heights(10, 59, 3, &[0; 9]);
+        let geometry = GridGeometry::of_tile(&dem);
+        assert_eq!(geometry.row_lat(0), Some(60.));
+        assert_eq!(geometry.row_lat(2), Some(59.));
+
+        let spacing = GridSpacing::new(&geometry, 3, 111120.);
+        assert_eq_approx(spacing.north_south(2), 55560., 0.01);
+        assert_eq_approx(spacing.east_west(0), 27780., 0.01);
+        assert_eq_approx(
+            spacing.east_west(2),
+            55560. * 59f32.to_radians().cos(),
+            0.01,
+        );
+    }
+
+    #[test]
+    fn web_mercator_spacing_shrinks_in_both_directions() {
+        // the whole map in a single tile with 2 pixels per side
+        let geometry = GridGeometry::WebMercator {
+            north_row: 0.5,
+            west_column: 0.5,
+            world_size: 2.,
+        };
+        let north_lat = (PI / 2.).sinh().atan().to_degrees();
+        assert_eq!(geometry.row_lat(0), Some(north_lat));
+        assert_eq!(geometry.row_lat(1), Some(-north_lat));
+        assert_eq!(geometry.padded(1).row_lat(1), Some(north_lat));
+        let (x, y) = geometry.cell_at(90., -north_lat).unwrap();
+        assert_eq_approx(x, 1., 1e-9);
+        assert_eq_approx(y, 1., 1e-9);
+
+        let spacing = GridSpacing::new(&geometry, 2, 100.);
+        let cos_lat = north_lat.to_radians().cos() as f32;
+        assert_eq_approx(spacing.north_south(0), 18000. * cos_lat, 0.01);
+        assert_eq_approx(spacing.east_west(1), 18000. * cos_lat, 0.01);
+    }
+
+    #[test]
+    fn projected_spacing_is_the_cell_size() {
+        let geometry = GridGeometry::Projected {
+            west_x: 2600001.,
+            north_y: 1199999.,
+            cell_size: 2.,
+        };
+        assert_eq!(geometry.row_lat(0), None);
+        assert_eq!(geometry.cell_at(7., 46.), None);
+        assert_eq!(
+            geometry.resampled(11, 21),
+            GridGeometry::Projected {
+                west_x: 2600001.,
+                north_y: 1199999.,
+                cell_size: 1.,
+            }
+        );
+        assert_eq!(
+            geometry.padded(1),
+            GridGeometry::Projected {
+                west_x: 2599999.,
+                north_y: 1200001.,
+                cell_size: 2.,
+            }
+        );
+
+        let spacing = GridSpacing::new(&geometry, 2, 111120.);
+        assert_eq!((spacing.north_south(1), spacing.east_west(1)), (2., 2.));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+use crate::errors::SionError;
+use image::{ColorType, ImageBuffer, ImageError, Luma};
+use std::io;
+
+/// A 16-bit grayscale image.
+pub type Gray16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+/// The mapping of the heights to the 16-bit heightmap values:
+/// `value = round((height + offset) * scale)`. The value 0 is reserved for
+/// the voids, the heights outside the encoding's range are clamped to
+/// `1..=65535`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeightmapEncoding {
+    /// The offset (in meters) added to the heights, so that the negative
+    /// heights can be stored.
+    pub offset: f32,
+    /// The values per meter.
+    pub scale: f32,
+}
+
+impl Default for HeightmapEncoding {
+    /// The lossless encoding of all the `i16` heights, with `NO_DATA_HEIGHT`
+    /// mapped to 0 (the void value).
+    fn default() -> Self {
+        HeightmapEncoding {
+            offset: 32768.,
+            scale: 1.,
+        }
+    }
+}
+
+impl HeightmapEncoding {
+    pub fn new(offset: f32, scale: f32) -> HeightmapEncoding {
+        HeightmapEncoding { offset, scale }
+    }
+
+    /// Creates an encoding stretching the heights from `min_height` to
+    /// `max_height` over the whole range of the values (`1..=65535`), e.g.
+    /// for the game engines' terrain heightmaps.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_height` is not above `min_height`.
+    pub fn for_height_range(
+        min_height: f32,
+        max_height: f32,
+    ) -> HeightmapEncoding {
+        if max_height <= min_height {
+            panic!("The maximum height must be above the minimum height");
+        }
+
+        let scale = (u16::MAX - 1) as f32 / (max_height - min_height);
+        HeightmapEncoding {
+            offset: 1. / scale - min_height,
+            scale,
+        }
+    }
+
+    /// Encodes the height (in meters), or `None` for a void.
+    pub fn encode(&self, height: Option<f32>) -> u16 {
+        match height {
+            Some(height) => ((height + self.offset) * self.scale)
+                .round()
+                .clamp(1., u16::MAX as f32) as u16,
+            None => 0,
+        }
+    }
+
+    /// Decodes the height (in meters), or `None` for a void.
+    pub fn decode(&self, value: u16) -> Option<f32> {
+        if value == 0 {
+            None
+        } else {
+            Some(value as f32 / self.scale - self.offset)
+        }
+    }
+}
+
+impl DemTile {
+    /// Encodes the tile's heights into a 16-bit grayscale image.
+    pub fn to_heightmap_image(
+        &self,
+        encoding: HeightmapEncoding,
+    ) -> Gray16Image {
+        let heights = self.heights();
+        let size = self.size as u32;
+        Gray16Image::from_fn(size, size, |x, y| {
+            let height = heights[(y * size + x) as usize];
+            Luma([encoding
+                .encode((height != NO_DATA_HEIGHT).then_some(height as f32))])
+        })
+    }
+
+    /// Writes the tile as a 16-bit grayscale PNG heightmap.
+    pub fn write_heightmap_png(
+        &self,
+        file: &str,
+        encoding: HeightmapEncoding,
+    ) -> Result<(), SionError> {
+        self.to_heightmap_image(encoding)
+            .save(file)
+            .map_err(|error| SionError::CannotWriteFile {
+                file: file.to_string(),
+                source: match error {
+                    ImageError::IoError(error) => error,
+                    error => io::Error::new(io::ErrorKind::Other, error),
+                },
+            })
+    }
+
+    /// Creates a tile from a (square) 16-bit heightmap image. The heights are
+    /// rounded to whole meters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is not square.
+    pub fn from_heightmap_image(
+        image: &Gray16Image,
+        encoding: HeightmapEncoding,
+        lon: i16,
+        lat: i16,
+    ) -> DemTile {
+        if image.width() != image.height() {
+            panic!("The heightmap image is not square");
+        }
+
+        let min_height = (NO_DATA_HEIGHT + 1) as f32;
+        let heights: Vec<i16> = image
+            .pixels()
+            .map(|&Luma([value])| match encoding.decode(value) {
+                Some(height) => {
+                    height.round().clamp(min_height, i16::MAX as f32) as i16
+                }
+                None => NO_DATA_HEIGHT,
+            })
+            .collect();
+
+        DemTile::from_heights(lon, lat, image.width() as usize, &heights)
+    }
+
+    /// Loads a tile from a (square) 16-bit grayscale PNG heightmap, see
+    /// `from_heightmap_image`. The 8-bit images are rejected, since they
+    /// cannot hold the heights' precision.
+    pub fn from_heightmap_png(
+        file: &str,
+        encoding: HeightmapEncoding,
+        lon: i16,
+        lat: i16,
+    ) -> Result<DemTile, SionError> {
+        let invalid_image = |reason: String| SionError::InvalidImage {
+            file: file.to_string(),
+            reason,
+        };
+
+        let image = image::open(file).map_err(|error| match error {
+            ImageError::IoError(error) => SionError::CannotOpenFile {
+                file: file.to_string(),
+                source: error,
+            },
+            error => invalid_image(error.to_string()),
+        })?;
+
+        if image.color() != ColorType::L16 {
+            return Err(invalid_image(format!(
+                "not a 16-bit grayscale image ({:?})",
+                image.color()
+            )));
+        }
+        if image.width() != image.height() {
+            return Err(invalid_image(format!(
+                "the image is not square ({}x{})",
+                image.width(),
+                image.height()
+            )));
+        }
+
+        Ok(DemTile::from_heightmap_image(
+            &image.into_luma16(),
+            encoding,
+            lon,
+            lat,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_eq_approx;
+    use image::GrayImage;
+    use std::fs;
+
+    const TEST_DIR: &str = "target/debug/heightmap_png_tests";
+
+    #[test]
+    fn default_encoding_is_lossless() {
+        let heights = [
+            NO_DATA_HEIGHT + 1,
+            -418,
+            -1,
+            0,
+            1,
+            NO_DATA_HEIGHT,
+            4808,
+            8848,
+            i16::MAX,
+        ];
+        let tile = DemTile::from_heights(-17, -33, 3, &heights);
+
+        fs::create_dir_all(TEST_DIR).unwrap();
+        let file = format!("{}/lossless.png", TEST_DIR);
+        tile.write_heightmap_png(&file, HeightmapEncoding::default())
+            .unwrap();
+
+        let read_tile = DemTile::from_heightmap_png(
+            &file,
+            HeightmapEncoding::default(),
+            -17,
+            -33,
+        )
+        .unwrap();
+        assert_eq!((read_tile.lon, read_tile.lat), (-17, -33));
+        assert_eq!(read_tile.heights(), heights);
+    }
+
+    #[test]
+    fn height_range_is_stretched_over_the_values() {
+        let encoding = HeightmapEncoding::for_height_range(-100., 900.);
+
+        assert_eq!(encoding.encode(Some(-100.)), 1);
+        assert_eq!(encoding.encode(Some(900.)), u16::MAX);
+        assert_eq!(encoding.encode(Some(-200.)), 1);
+        assert_eq!(encoding.encode(None), 0);
+        assert_eq!(encoding.decode(0), None);
+        assert_eq_approx(encoding.decode(1).unwrap(), -100., 0.001);
+        assert_eq_approx(
+            encoding.decode(encoding.encode(Some(400.))).unwrap(),
+            400.,
+            0.01,
+        );
+    }
+
+    #[test]
+    fn only_square_16_bit_images_are_read() {
+        fs::create_dir_all(TEST_DIR).unwrap();
+        let encoding = HeightmapEncoding::default();
+
+        let file = format!("{}/8_bit.png", TEST_DIR);
+        GrayImage::new(2, 2).save(&file).unwrap();
+        assert!(matches!(
+            DemTile::from_heightmap_png(&file, encoding, 0, 0),
+            Err(SionError::InvalidImage { .. })
+        ));
+
+        let file = format!("{}/non_square.png", TEST_DIR);
+        Gray16Image::new(2, 1).save(&file).unwrap();
+        assert!(matches!(
+            DemTile::from_heightmap_png(&file, encoding, 0, 0),
+            Err(SionError::InvalidImage { .. })
+        ));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::dem_tile::DemTile;
+use crate::errors::SionError;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+impl DemTile {
+    /// Loads a DEM tile from a zipped HGT file (e.g. `N46E006.hgt.zip`, as
+    /// shipped by most of the SRTM mirrors). The archive must contain exactly
+    /// one `.hgt` entry, the tile's coordinates are taken from its name.
+    pub fn from_hgt_zip_file(file: &str) -> Result<DemTile, SionError> {
+        let mut archive = open_zip_archive(file)?;
+        let entries = hgt_entries(file, &mut archive)?;
+
+        match entries.as_slice() {
+            [(index, tile_name)] => {
+                read_hgt_entry(file, &mut archive, *index, tile_name)
+            }
+            [] => Err(SionError::InvalidArchive {
+                file: file.to_string(),
+                reason: "the archive contains no HGT file".to_string(),
+            }),
+            _ => Err(SionError::InvalidArchive {
+                file: file.to_string(),
+                reason: format!(
+                    "the archive contains {} HGT files, expected just one",
+                    entries.len()
+                ),
+            }),
+        }
+    }
+
+    /// Loads all the DEM tiles from a zip archive containing one or more
+    /// `.hgt` entries (the entries can also be in subdirectories).
+    pub fn all_from_hgt_zip_file(
+        file: &str,
+    ) -> Result<Vec<DemTile>, SionError> {
+        let mut archive = open_zip_archive(file)?;
+        let entries = hgt_entries(file, &mut archive)?;
+
+        entries
+            .iter()
+            .map(|(index, tile_name)| {
+                read_hgt_entry(file, &mut archive, *index, tile_name)
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn open_zip_archive(
+    file_name: &str,
+) -> Result<ZipArchive<BufReader<File>>, SionError> {
+    let file =
+        File::open(file_name).map_err(|error| SionError::CannotOpenFile {
+            file: file_name.to_string(),
+            source: error,
+        })?;
+
+    ZipArchive::new(BufReader::new(file)).map_err(|error| {
+        SionError::InvalidArchive {
+            file: file_name.to_string(),
+            reason: error.to_string(),
+        }
+    })
+}
+
+/// Lists the indices and the tile names (file stems) of all the `.hgt`
+/// entries in the archive.
+pub(crate) fn hgt_entries<R: Read + Seek>(
+    file_name: &str,
+    archive: &mut ZipArchive<R>,
+) -> Result<Vec<(usize, String)>, SionError> {
+    let mut entries = Vec::new();
+
+    for index in 0..archive.len() {
+        let entry_name = archive.name_for_index(index).ok_or_else(|| {
+            SionError::InvalidArchive {
+                file: file_name.to_string(),
+                reason: format!("missing entry #{}", index),
+            }
+        })?;
+
+        let entry_path = Path::new(entry_name);
+        let is_hgt = entry_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hgt"));
+
+        if is_hgt {
+            let tile_name = entry_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| {
+                    SionError::InvalidTileName(entry_name.to_string())
+                })?;
+            entries.push((index, tile_name.to_string()));
+        }
+    }
+
+    Ok(entries)
+}
+
+pub(crate) fn read_hgt_entry<R: Read + Seek>(
+    file_name: &str,
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    tile_name: &str,
+) -> Result<DemTile, SionError> {
+    let (lon, lat) = DemTile::parse_tile_name(tile_name)?;
+
+    let entry_file_name = format!("{}/{}.hgt", file_name, tile_name);
+
+    let entry =
+        archive
+            .by_index(index)
+            .map_err(|error| SionError::InvalidArchive {
+                file: file_name.to_string(),
+                reason: error.to_string(),
+            })?;
+
+    let entry_size = entry.size();
+    let tile_size =
+        DemTile::tile_size_from_file_size(&entry_file_name, entry_size)?;
+
+    // the declared size can be anything (up to gigabytes for a 65535 cells
+    // tile), so the buffer grows with the bytes actually read instead of
+    // being allocated from it up front
+    let mut byte_array: Vec<u8> = Vec::new();
+    let read_result = entry.take(entry_size).read_to_end(&mut byte_array);
+
+    match read_result {
+        Ok(_) if byte_array.len() as u64 == entry_size => {
+            Ok(DemTile::new(lon, lat, tile_size, byte_array))
+        }
+        _ => Err(SionError::TruncatedRead {
+            file: entry_file_name,
+            expected_bytes: entry_size,
+            actual_bytes: byte_array.len() as u64,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dem_tile::DemTile;
+    use crate::errors::SionError;
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    /// Writes a zip archive with the given entries (name and heights) into
+    /// the `target/debug` directory and returns its path.
+    fn write_zip_archive(
+        file_name: &str,
+        entries: &[(&str, &[i16])],
+    ) -> String {
+        let dir = "target/debug/hgt_zip_tests";
+        fs::create_dir_all(dir).unwrap();
+        let path = format!("{}/{}", dir, file_name);
+
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated);
+
+        for (entry_name, heights) in entries {
+            writer.start_file(*entry_name, options).unwrap();
+            for height in heights.iter() {
+                writer.write_all(&height.to_be_bytes()).unwrap();
+            }
+        }
+
+        writer.finish().unwrap();
+        path
+    }
+
+    /// Writes a zip archive with a single uncompressed entry.
+    fn write_zip_archive_stored(
+        file_name: &str,
+        entry_name: &str,
+        heights: &[i16],
+    ) -> String {
+        let dir = "target/debug/hgt_zip_tests";
+        fs::create_dir_all(dir).unwrap();
+        let path = format!("{}/{}", dir, file_name);
+
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored);
+        writer.start_file(entry_name, options).unwrap();
+        for height in heights {
+            writer.write_all(&height.to_be_bytes()).unwrap();
+        }
+
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn read_single_tile_archive() {
+        let file = write_zip_archive(
+            "N46E006.hgt.zip",
+            &[("N46E006.hgt", &[10, 20, -30, 400])],
+        );
+
+        let tile = DemTile::from_hgt_zip_file(&file).unwrap();
+        assert_eq!(tile.lon, 6);
+        assert_eq!(tile.lat, 46);
+        assert_eq!(tile.size, 2);
+        assert_eq!(tile.height_at(0, 0), 10);
+        assert_eq!(tile.height_at(1, 0), 20);
+        assert_eq!(tile.height_at(0, 1), -30);
+        assert_eq!(tile.height_at(1, 1), 400);
+    }
+
+    #[test]
+    fn read_multiple_tiles_archive() {
+        let file = write_zip_archive(
+            "multiple.zip",
+            &[
+                ("readme.txt", &[0]),
+                ("N46/N46E006.hgt", &[1, 2, 3, 4]),
+                ("S10W120.HGT", &[5, 6, 7, 8, 9, 10, 11, 12, 13]),
+            ],
+        );
+
+        let tiles = DemTile::all_from_hgt_zip_file(&file).unwrap();
+        assert_eq!(tiles.len(), 2);
+        assert_eq!((tiles[0].lon, tiles[0].lat, tiles[0].size), (6, 46, 2));
+        assert_eq!(tiles[0].height_at(1, 1), 4);
+        assert_eq!((tiles[1].lon, tiles[1].lat, tiles[1].size), (-120, -10, 3));
+        assert_eq!(tiles[1].height_at(2, 2), 13);
+
+        match DemTile::from_hgt_zip_file(&file) {
+            Err(SionError::InvalidArchive { reason, .. }) => assert_eq!(
+                reason,
+                "the archive contains 2 HGT files, expected just one"
+            ),
+            _ => panic!("Expected an InvalidArchive error"),
+        }
+    }
+
+    #[test]
+    fn archive_without_tiles_is_rejected() {
+        let file = write_zip_archive("empty.zip", &[("readme.txt", &[0])]);
+        assert!(matches!(
+            DemTile::from_hgt_zip_file(&file),
+            Err(SionError::InvalidArchive { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_entries_are_reported() {
+        let file = write_zip_archive(
+            "invalid_entries.zip",
+            &[("N46E006.hgt", &[1, 2, 3])],
+        );
+        assert!(matches!(
+            DemTile::from_hgt_zip_file(&file),
+            Err(SionError::NonSquareHeightsCount {
+                heights_count: 3,
+                ..
+            })
+        ));
+
+        let file = write_zip_archive("invalid_name.zip", &[("tile.hgt", &[1])]);
+        assert!(matches!(
+            DemTile::from_hgt_zip_file(&file),
+            Err(SionError::InvalidTileName(_))
+        ));
+    }
+
+    #[test]
+    fn entry_shorter_than_its_declared_size_is_reported() {
+        let file = write_zip_archive_stored(
+            "oversized_entry.zip",
+            "N46E006.hgt",
+            &[1, 2, 3, 4],
+        );
+
+        // declare the entry as a 46340x46340 tile (4.3 GB) in both the
+        // local and the central directory headers
+        let declared_size: u32 = 46340 * 46340 * 2;
+        let mut bytes = fs::read(&file).unwrap();
+        let central_directory = bytes
+            .windows(4)
+            .position(|window| window == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        for offset in [22, central_directory + 24] {
+            bytes[offset..offset + 4]
+                .copy_from_slice(&declared_size.to_le_bytes());
+        }
+        fs::write(&file, bytes).unwrap();
+
+        match DemTile::from_hgt_zip_file(&file) {
+            Err(SionError::TruncatedRead {
+                expected_bytes,
+                actual_bytes,
+                ..
+            }) => assert_eq!(
+                (expected_bytes, actual_bytes),
+                (declared_size as u64, 8)
+            ),
+            _ => panic!("Expected a TruncatedRead error"),
+        }
+    }
+
+    #[test]
+    fn file_that_is_not_an_archive_is_rejected() {
+        let dir = "target/debug/hgt_zip_tests";
+        fs::create_dir_all(dir).unwrap();
+        let path = format!("{}/not_a_zip.zip", dir);
+        fs::write(&path, [1, 2, 3, 4]).unwrap();
+
+        assert!(matches!(
+            DemTile::from_hgt_zip_file(&path),
+            Err(SionError::InvalidArchive { .. })
+        ));
+    }
+}
@@ -0,0 +1,504 @@
+use crate::dem_tile::DemTile;
+use crate::grayscale8_bitmap::Grayscale8Bitmap;
+use crate::hillshading::parameters::HillshadingParameters;
+use crate::hillshading::{
+    igor_hillshading_fixed, igor_hillshading_opt1, igor_hillshading_orig,
+    igor_hillshading_simd, lambertian_hillshading,
+};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+
+/// The hillshading functions' signature (e.g.
+/// `igor_hillshading_opt1::hillshade`).
+pub type HillshadeFn =
+    fn(&DemTile, &HillshadingParameters, &mut Grayscale8Bitmap);
+
+/// The signature of the functions shading a band of the bitmap's rows (e.g.
+/// `igor_hillshading_opt1::hillshade_rows`): the band's pixels are the
+/// bitmap's data from the row `first_row` on, whole rows of the DEM's size.
+/// The band's pixels are shaded following the `Hillshader` output contract,
+/// except that the DEM's voids are not filled (see `hillshade_by_rows`).
+pub type HillshadeRowsFn =
+    fn(&DemTile, &HillshadingParameters, usize, &mut [u8]);
+
+/// Shades the DEM into the bitmap with the rows function, as a single band.
+///
+/// # Panics
+///
+/// Panics if the bitmap's size does not match the DEM's size.
+pub fn hillshade_by_rows(
+    rows_function: HillshadeRowsFn,
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    bitmap: &mut Grayscale8Bitmap,
+) {
+    if bitmap.width as usize != dem.size || bitmap.height as usize != dem.size {
+        panic!("bitmap size does not match DEM size");
+    }
+
+    let dem = parameters.prepare_dem(dem);
+    rows_function(dem.as_ref(), parameters, 0, bitmap.data_mut());
+}
+
+/// Shades the DEM into the bitmap with the rows function, splitting the
+/// bitmap into bands of rows shaded in parallel on the thread pool. The
+/// bitmap is identical to the one shaded by `hillshade_by_rows`.
+///
+/// # Panics
+///
+/// Panics if the bitmap's size does not match the DEM's size.
+pub fn hillshade_by_rows_parallel(
+    rows_function: HillshadeRowsFn,
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    bitmap: &mut Grayscale8Bitmap,
+    pool: &ThreadPool,
+) {
+    if bitmap.width as usize != dem.size || bitmap.height as usize != dem.size {
+        panic!("bitmap size does not match DEM size");
+    }
+
+    let dem = parameters.prepare_dem(dem);
+    let dem = dem.as_ref();
+    shade_bands_parallel(dem.size, bitmap, pool, |first_row, band| {
+        rows_function(dem, parameters, first_row, band)
+    });
+}
+
+/// Splits the bitmap (of the DEM's size) into bands of rows and shades them
+/// in parallel on the thread pool with the function, which gets each band's
+/// first row and pixels like a `HillshadeRowsFn`.
+pub fn shade_bands_parallel(
+    dem_size: usize,
+    bitmap: &mut Grayscale8Bitmap,
+    pool: &ThreadPool,
+    shade_band: impl Fn(usize, &mut [u8]) + Sync,
+) {
+    // several bands per thread, so the threads finishing early can help out
+    let bands = pool.current_num_threads() * 4;
+    let band_rows = ((dem_size + bands - 1) / bands).max(1);
+    pool.install(|| {
+        bitmap
+            .data_mut()
+            .par_chunks_mut(band_rows * dem_size)
+            .enumerate()
+            .for_each(|(band_index, band)| {
+                shade_band(band_index * band_rows, band)
+            });
+    });
+}
+
+/// Iterates over the band's rows which have their neighbours in the DEM
+/// (all but the DEM's first and last row), with their indices in the bitmap.
+pub fn interior_rows(
+    dem_size: usize,
+    first_row: usize,
+    band: &mut [u8],
+) -> impl Iterator<Item = (usize, &mut [u8])> {
+    band.chunks_exact_mut(dem_size)
+        .enumerate()
+        .map(move |(index, row)| (first_row + index, row))
+        .filter(move |(y, _)| *y > 0 && *y < dem_size - 1)
+}
+
+/// A hillshading algorithm.
+///
+/// All the hillshaders follow the same output contract:
+/// - the bitmap must have the DEM's size, otherwise the hillshader panics;
+/// - each cell with all of its eight neighbours in the DEM gets a shade from
+///   0 (darkest) to 255 (brightest), the same shade for the same slope and
+///   aspect; the pixels on the bitmap's border are left untouched (see
+///   `hillshade_seamless` for shading them too);
+/// - the DEM's voids are filled first if `parameters.void_filling` is set.
+pub trait Hillshader: Send + Sync {
+    /// The name the hillshader is registered under, e.g. `igor_opt1`.
+    fn name(&self) -> &str;
+
+    /// Shades the DEM into the bitmap, see the trait's output contract.
+    fn hillshade(
+        &self,
+        dem: &DemTile,
+        parameters: &HillshadingParameters,
+        bitmap: &mut Grayscale8Bitmap,
+    );
+
+    /// Shades the DEM like `hillshade`, using the thread pool's threads. The
+    /// bitmap is identical to the one shaded by `hillshade`. By default, the
+    /// DEM is shaded by `hillshade` on the current thread.
+    fn hillshade_parallel(
+        &self,
+        dem: &DemTile,
+        parameters: &HillshadingParameters,
+        bitmap: &mut Grayscale8Bitmap,
+        _pool: &ThreadPool,
+    ) {
+        self.hillshade(dem, parameters, bitmap);
+    }
+
+    /// Shades the DEM into a new bitmap, with its border pixels white.
+    fn hillshade_to_bitmap(
+        &self,
+        dem: &DemTile,
+        parameters: &HillshadingParameters,
+    ) -> Grayscale8Bitmap {
+        let mut bitmap =
+            Grayscale8Bitmap::new(dem.size as u16, dem.size as u16);
+        bitmap.data_mut().fill(255);
+        self.hillshade(dem, parameters, &mut bitmap);
+        bitmap
+    }
+}
+
+/// A hillshader implemented by a `HillshadeFn` function.
+pub struct FnHillshader {
+    name: String,
+    function: HillshadeFn,
+}
+
+impl FnHillshader {
+    pub fn new(name: &str, function: HillshadeFn) -> FnHillshader {
+        FnHillshader {
+            name: name.to_string(),
+            function,
+        }
+    }
+}
+
+impl Hillshader for FnHillshader {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn hillshade(
+        &self,
+        dem: &DemTile,
+        parameters: &HillshadingParameters,
+        bitmap: &mut Grayscale8Bitmap,
+    ) {
+        (self.function)(dem, parameters, bitmap)
+    }
+}
+
+/// A hillshader implemented by a `HillshadeRowsFn` function, shading in
+/// parallel by bands of rows.
+pub struct RowsHillshader {
+    name: String,
+    rows_function: HillshadeRowsFn,
+}
+
+impl RowsHillshader {
+    pub fn new(name: &str, rows_function: HillshadeRowsFn) -> RowsHillshader {
+        RowsHillshader {
+            name: name.to_string(),
+            rows_function,
+        }
+    }
+}
+
+impl Hillshader for RowsHillshader {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn hillshade(
+        &self,
+        dem: &DemTile,
+        parameters: &HillshadingParameters,
+        bitmap: &mut Grayscale8Bitmap,
+    ) {
+        hillshade_by_rows(self.rows_function, dem, parameters, bitmap)
+    }
+
+    fn hillshade_parallel(
+        &self,
+        dem: &DemTile,
+        parameters: &HillshadingParameters,
+        bitmap: &mut Grayscale8Bitmap,
+        pool: &ThreadPool,
+    ) {
+        hillshade_by_rows_parallel(
+            self.rows_function,
+            dem,
+            parameters,
+            bitmap,
+            pool,
+        )
+    }
+}
+
+/// The hillshaders available by name, in the order of their registration.
+pub struct HillshaderRegistry {
+    hillshaders: Vec<Box<dyn Hillshader>>,
+}
+
+impl Default for HillshaderRegistry {
+    /// Creates a registry with the built-in hillshaders.
+    fn default() -> Self {
+        let mut registry = HillshaderRegistry::empty();
+        registry.register(Box::new(RowsHillshader::new(
+            "igor_orig",
+            igor_hillshading_orig::hillshade_rows,
+        )));
+        registry.register(Box::new(RowsHillshader::new(
+            "igor_opt1",
+            igor_hillshading_opt1::hillshade_rows,
+        )));
+        registry.register(Box::new(RowsHillshader::new(
+            "igor_simd",
+            igor_hillshading_simd::hillshade_rows,
+        )));
+        registry
+            .register(Box::new(igor_hillshading_fixed::FixedPointHillshader));
+        registry.register(Box::new(RowsHillshader::new(
+            "lambertian",
+            lambertian_hillshading::hillshade_rows,
+        )));
+        registry.register(Box::new(RowsHillshader::new(
+            "mdow",
+            lambertian_hillshading::hillshade_multidirectional_rows,
+        )));
+        registry
+    }
+}
+
+impl HillshaderRegistry {
+    /// Creates a registry with the built-in hillshaders.
+    pub fn new() -> HillshaderRegistry {
+        HillshaderRegistry::default()
+    }
+
+    /// Creates a registry without any hillshaders.
+    pub fn empty() -> HillshaderRegistry {
+        HillshaderRegistry {
+            hillshaders: Vec::new(),
+        }
+    }
+
+    /// Adds the hillshader, replacing the one registered under the same
+    /// name.
+    pub fn register(&mut self, hillshader: Box<dyn Hillshader>) {
+        match self
+            .hillshaders
+            .iter_mut()
+            .find(|registered| registered.name() == hillshader.name())
+        {
+            Some(registered) => *registered = hillshader,
+            None => self.hillshaders.push(hillshader),
+        }
+    }
+
+    /// Returns the hillshader registered under the name.
+    pub fn get(&self, name: &str) -> Option<&dyn Hillshader> {
+        self.hillshaders
+            .iter()
+            .find(|hillshader| hillshader.name() == name)
+            .map(|hillshader| hillshader.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.hillshaders
+            .iter()
+            .map(|hillshader| hillshader.name())
+            .collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Hillshader> {
+        self.hillshaders
+            .iter()
+            .map(|hillshader| hillshader.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Surface, SyntheticTerrain};
+    use crate::void_filling::VoidFillMethod;
+    use rayon::ThreadPoolBuilder;
+    use rstest::rstest;
+
+    #[test]
+    fn hillshaders_are_found_by_name() {
+        let mut registry = HillshaderRegistry::new();
+        assert_eq!(
+            registry.names(),
+            vec![
+                "igor_orig",
+                "igor_opt1",
+                "igor_simd",
+                "igor_fixed",
+                "lambertian",
+                "mdow"
+            ]
+        );
+        assert!(registry.get("igor_opt1").is_some());
+        assert!(registry.get("missing").is_none());
+
+        fn black(
+            _: &DemTile,
+            _: &HillshadingParameters,
+            bitmap: &mut Grayscale8Bitmap,
+        ) {
+            bitmap.data_mut().fill(0);
+        }
+        registry.register(Box::new(FnHillshader::new("igor_orig", black)));
+        registry.register(Box::new(FnHillshader::new("black", black)));
+        assert_eq!(
+            registry.names(),
+            vec![
+                "igor_orig",
+                "igor_opt1",
+                "igor_simd",
+                "igor_fixed",
+                "lambertian",
+                "mdow",
+                "black"
+            ]
+        );
+
+        let dem = DemTile::from_heights(0, 0, 3, &[0; 9]);
+        let bitmap = registry
+            .get("igor_orig")
+            .unwrap()
+            .hillshade_to_bitmap(&dem, &HillshadingParameters::default());
+        assert!(bitmap.data().iter().all(|&pixel| pixel == 0));
+    }
+
+    /// All the built-in hillshaders follow the output contract.
+    #[test]
+    fn hillshaders_follow_the_output_contract() {
+        let parameters = HillshadingParameters::default();
+        let flat = SyntheticTerrain::new(6, 46, 11)
+            .with(Surface::Plane {
+                height: 500.,
+                slope: 0.,
+                aspect: 0.,
+            })
+            .build();
+        let facing_away = SyntheticTerrain::new(6, 46, 11)
+            .with(Surface::Plane {
+                height: 500.,
+                slope: 30.,
+                aspect: parameters.sun_azimuth + 180.,
+            })
+            .build();
+
+        for hillshader in HillshaderRegistry::new().iter() {
+            let mut bitmap = Grayscale8Bitmap::new(11, 11);
+            bitmap.data_mut().fill(7);
+            hillshader.hillshade(&flat, &parameters, &mut bitmap);
+
+            let flat_shade = bitmap.get_pixel(5, 5);
+            for y in 0..11 {
+                for x in 0..11 {
+                    let border = x == 0 || y == 0 || x == 10 || y == 10;
+                    let expected = if border { 7 } else { flat_shade };
+                    assert_eq!(
+                        bitmap.get_pixel(x, y),
+                        expected,
+                        "{} at {}, {}",
+                        hillshader.name(),
+                        x,
+                        y
+                    );
+                }
+            }
+
+            let bitmap =
+                hillshader.hillshade_to_bitmap(&facing_away, &parameters);
+            assert_eq!(bitmap.get_pixel(0, 0), 255);
+            assert!(
+                bitmap.get_pixel(5, 5) < flat_shade,
+                "{}",
+                hillshader.name()
+            );
+        }
+    }
+
+    /// The same slope is shaded the same from the equator to the north,
+    /// where the cells are much narrower than they are tall.
+    #[test]
+    fn hillshaders_shade_slopes_the_same_at_all_latitudes() {
+        let parameters = HillshadingParameters::default();
+        let plane = |lat, aspect| {
+            SyntheticTerrain::new(10, lat, 21)
+                // gentle enough for the heights to fit a tile at the equator
+                .with(Surface::Plane {
+                    height: 0.,
+                    slope: 15.,
+                    aspect,
+                })
+                .build()
+        };
+
+        for hillshader in HillshaderRegistry::new().iter() {
+            for aspect in [0., 70., 135., 200., 315.] {
+                let equator = hillshader
+                    .hillshade_to_bitmap(&plane(0, aspect), &parameters);
+                let north = hillshader
+                    .hillshade_to_bitmap(&plane(60, aspect), &parameters);
+                for y in 1..20 {
+                    for x in 1..20 {
+                        let (a, b) =
+                            (equator.get_pixel(x, y), north.get_pixel(x, y));
+                        assert!(
+                            a.abs_diff(b) <= 1,
+                            "{} at {}°: {} vs {} at {}, {}",
+                            hillshader.name(),
+                            aspect,
+                            a,
+                            b,
+                            x,
+                            y
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(3)]
+    #[case(8)]
+    fn parallel_hillshading_matches_the_serial_one(#[case] threads: usize) {
+        let dem = SyntheticTerrain::new(6, 46, 97)
+            .with(Surface::FractalNoise {
+                seed: 22,
+                amplitude: 800.,
+                wavelength: 30.,
+                octaves: 5,
+            })
+            .with_void(40, 50)
+            .build();
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        for void_filling in [None, Some(VoidFillMethod::Laplacian)] {
+            let parameters = HillshadingParameters {
+                void_filling,
+                ..HillshadingParameters::default()
+            };
+            for hillshader in HillshaderRegistry::new().iter() {
+                let serial = hillshader.hillshade_to_bitmap(&dem, &parameters);
+                let mut parallel = Grayscale8Bitmap::new(97, 97);
+                parallel.data_mut().fill(255);
+                hillshader.hillshade_parallel(
+                    &dem,
+                    &parameters,
+                    &mut parallel,
+                    &pool,
+                );
+
+                assert_eq!(
+                    parallel.data(),
+                    serial.data(),
+                    "{}",
+                    hillshader.name()
+                );
+            }
+        }
+    }
+}
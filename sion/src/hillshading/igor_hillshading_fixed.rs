@@ -0,0 +1,540 @@
+use crate::dem_tile::DemTile;
+use crate::grayscale8_bitmap::Grayscale8Bitmap;
+use crate::grid_spacing::GridSpacing;
+use crate::hillshading::hillshader::{
+    hillshade_by_rows, interior_rows, shade_bands_parallel, Hillshader,
+};
+use crate::hillshading::parameters::HillshadingParameters;
+use rayon::ThreadPool;
+
+/// The fixed-point numbers' fraction bits (the values are multiplied by
+/// 65536).
+const FRACTION_BITS: u32 = 16;
+const ONE: i64 = 1 << FRACTION_BITS;
+
+/// The angles are binary angles: a full turn is 65536.
+const FULL_TURN: i32 = 1 << 16;
+const HALF_TURN: i32 = FULL_TURN / 2;
+const QUARTER_TURN: i32 = FULL_TURN / 4;
+
+/// The limit of the gradient's components (in the fixed point), so that
+/// their squares' sum fits 64 bits. The slopes steeper than this (89.996°)
+/// are as dark as the ones at the limit.
+const MAX_GRADIENT: i64 = 1 << 30;
+
+/// The slope's share of a right angle (in the fixed point) for the 257
+/// values of `t` from 0 to 1 (in steps of 1/256), where the gradient is
+/// `t / (1 - t)`, i.e. `atan(t / (1 - t)) / (π/2) * 65536`.
+const SLOPE_TABLE: [u32; 257] = [
+    0, 164, 329, 495, 662, 831, 1001, 1173, 1345, 1520, 1695, 1872, 2050, 2230,
+    2411, 2593, 2777, 2963, 3149, 3338, 3527, 3718, 3911, 4105, 4301, 4498,
+    4696, 4897, 5098, 5301, 5506, 5712, 5920, 6130, 6341, 6553, 6767, 6983,
+    7200, 7419, 7640, 7862, 8086, 8311, 8538, 8767, 8997, 9229, 9462, 9698,
+    9934, 10173, 10413, 10655, 10898, 11144, 11390, 11639, 11889, 12141, 12394,
+    12649, 12906, 13164, 13424, 13685, 13949, 14214, 14480, 14748, 15018,
+    15289, 15562, 15836, 16112, 16389, 16668, 16949, 17231, 17515, 17800,
+    18086, 18374, 18663, 18954, 19246, 19540, 19835, 20131, 20429, 20728,
+    21028, 21329, 21632, 21936, 22241, 22547, 22854, 23163, 23472, 23783,
+    24095, 24407, 24721, 25035, 25350, 25667, 25984, 26301, 26620, 26939,
+    27259, 27580, 27901, 28223, 28545, 28868, 29191, 29515, 29839, 30164,
+    30489, 30814, 31139, 31465, 31790, 32116, 32442, 32768, 33094, 33420,
+    33746, 34071, 34397, 34722, 35047, 35372, 35697, 36021, 36345, 36668,
+    36991, 37313, 37635, 37956, 38277, 38597, 38916, 39235, 39552, 39869,
+    40186, 40501, 40815, 41129, 41441, 41753, 42064, 42373, 42682, 42989,
+    43295, 43600, 43904, 44207, 44508, 44808, 45107, 45405, 45701, 45996,
+    46290, 46582, 46873, 47162, 47450, 47736, 48021, 48305, 48587, 48868,
+    49147, 49424, 49700, 49974, 50247, 50518, 50788, 51056, 51322, 51587,
+    51851, 52112, 52372, 52630, 52887, 53142, 53395, 53647, 53897, 54146,
+    54392, 54638, 54881, 55123, 55363, 55602, 55838, 56074, 56307, 56539,
+    56769, 56998, 57225, 57450, 57674, 57896, 58117, 58336, 58553, 58769,
+    58983, 59195, 59406, 59616, 59824, 60030, 60235, 60438, 60639, 60840,
+    61038, 61235, 61431, 61625, 61818, 62009, 62198, 62387, 62573, 62759,
+    62943, 63125, 63306, 63486, 63664, 63841, 64016, 64191, 64363, 64535,
+    64705, 64874, 65041, 65207, 65372, 65536,
+];
+
+/// The arctangent (as a binary angle) of the 257 ratios from 0 to 1 (in
+/// steps of 1/256), i.e. `atan(r) / 2π * 65536`.
+const ATAN_TABLE: [u32; 257] = [
+    0, 41, 81, 122, 163, 204, 244, 285, 326, 367, 407, 448, 489, 529, 570, 610,
+    651, 692, 732, 773, 813, 854, 894, 935, 975, 1015, 1056, 1096, 1136, 1177,
+    1217, 1257, 1297, 1337, 1377, 1417, 1457, 1497, 1537, 1577, 1617, 1656,
+    1696, 1736, 1775, 1815, 1854, 1894, 1933, 1973, 2012, 2051, 2090, 2129,
+    2168, 2207, 2246, 2285, 2324, 2363, 2401, 2440, 2478, 2517, 2555, 2594,
+    2632, 2670, 2708, 2746, 2784, 2822, 2860, 2897, 2935, 2973, 3010, 3047,
+    3085, 3122, 3159, 3196, 3233, 3270, 3307, 3344, 3380, 3417, 3453, 3490,
+    3526, 3562, 3599, 3635, 3670, 3706, 3742, 3778, 3813, 3849, 3884, 3920,
+    3955, 3990, 4025, 4060, 4095, 4129, 4164, 4199, 4233, 4267, 4302, 4336,
+    4370, 4404, 4438, 4471, 4505, 4539, 4572, 4605, 4639, 4672, 4705, 4738,
+    4771, 4803, 4836, 4869, 4901, 4933, 4966, 4998, 5030, 5062, 5094, 5125,
+    5157, 5188, 5220, 5251, 5282, 5313, 5344, 5375, 5406, 5437, 5467, 5498,
+    5528, 5559, 5589, 5619, 5649, 5679, 5708, 5738, 5768, 5797, 5826, 5856,
+    5885, 5914, 5943, 5972, 6000, 6029, 6058, 6086, 6114, 6142, 6171, 6199,
+    6227, 6254, 6282, 6310, 6337, 6365, 6392, 6419, 6446, 6473, 6500, 6527,
+    6554, 6580, 6607, 6633, 6660, 6686, 6712, 6738, 6764, 6790, 6815, 6841,
+    6867, 6892, 6917, 6943, 6968, 6993, 7018, 7043, 7068, 7092, 7117, 7141,
+    7166, 7190, 7214, 7238, 7262, 7286, 7310, 7334, 7358, 7381, 7405, 7428,
+    7451, 7475, 7498, 7521, 7544, 7566, 7589, 7612, 7635, 7657, 7679, 7702,
+    7724, 7746, 7768, 7790, 7812, 7834, 7856, 7877, 7899, 7920, 7942, 7963,
+    7984, 8005, 8026, 8047, 8068, 8089, 8110, 8131, 8151, 8172, 8192,
+];
+
+/// The settings of the fixed-point hillshading, in the fixed-point formats
+/// the shading works with. They are prepared once (see `new`), after that
+/// the hillshading uses no floating point at all.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedPointHillshading {
+    /// The sun's direction as a binary angle (a full turn is 65536).
+    pub sun_azimuth: i32,
+    /// The intensity multiplied by 65536.
+    pub intensity: i64,
+    /// `2^32 / (8 * north_south_spacing)` for each of the rows.
+    pub north_south_scales: Vec<i64>,
+    /// `2^32 / (8 * east_west_spacing)` for each of the rows.
+    pub east_west_scales: Vec<i64>,
+}
+
+impl FixedPointHillshading {
+    /// Converts the parameters and the grid spacing into the fixed point.
+    pub fn new(
+        parameters: &HillshadingParameters,
+        spacing: &GridSpacing,
+        rows: usize,
+    ) -> FixedPointHillshading {
+        let scale =
+            |spacing: f32| (2f64.powi(32) / (8. * spacing as f64)) as i64;
+        let sun_azimuth = (parameters.sun_azimuth as f64 / 360.
+            * FULL_TURN as f64)
+            .round() as i32;
+
+        FixedPointHillshading {
+            sun_azimuth: sun_azimuth.rem_euclid(FULL_TURN),
+            intensity: (parameters.intensity as f64 * ONE as f64).round()
+                as i64,
+            north_south_scales: (0..rows)
+                .map(|y| scale(spacing.north_south(y)))
+                .collect(),
+            east_west_scales: (0..rows)
+                .map(|y| scale(spacing.east_west(y)))
+                .collect(),
+        }
+    }
+
+    /// Creates the settings from integers only, e.g. to shade raw heights
+    /// with `hillshade_heights` without any floating point: the sun's
+    /// direction as a binary angle, the intensity multiplied by 65536 and
+    /// the north-south and east-west grid spacing of each row in
+    /// millimeters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the spacings' counts differ or a spacing is zero.
+    pub fn from_integers(
+        sun_azimuth: i32,
+        intensity: i64,
+        north_south_spacings: &[u32],
+        east_west_spacings: &[u32],
+    ) -> FixedPointHillshading {
+        if north_south_spacings.len() != east_west_spacings.len() {
+            panic!("north-south and east-west spacings count differs");
+        }
+
+        // 2^32 / (8 * spacing / 1000)
+        let scale = |&spacing: &u32| (1000 << 29) / spacing as i64;
+        FixedPointHillshading {
+            sun_azimuth: sun_azimuth.rem_euclid(FULL_TURN),
+            intensity,
+            north_south_scales: north_south_spacings
+                .iter()
+                .map(scale)
+                .collect(),
+            east_west_scales: east_west_spacings.iter().map(scale).collect(),
+        }
+    }
+
+    /// Shades the pixel with the Sobel sums of the height differences
+    /// towards the east (`p`) and the south (`q`) in the row `y`, the same
+    /// way as `igor_hillshading_opt1`.
+    pub fn shade(&self, p: i32, q: i32, y: usize) -> u8 {
+        let gradient = |sum: i32, scale: i64| {
+            ((sum as i64 * scale) >> FRACTION_BITS)
+                .clamp(-MAX_GRADIENT, MAX_GRADIENT)
+        };
+        let (p, q) = (
+            gradient(p, self.east_west_scales[y]),
+            gradient(q, self.north_south_scales[y]),
+        );
+
+        let slope = slope_share(sqrt((p * p + q * q) as u64) as i64);
+        let aspect = (atan2(q, p) - QUARTER_TURN).rem_euclid(FULL_TURN);
+
+        // the same difference as `difference_between_angles`
+        let diff = aspect - self.sun_azimuth;
+        let aspect_diff = if diff < -HALF_TURN {
+            diff + FULL_TURN
+        } else if diff > HALF_TURN {
+            diff - FULL_TURN
+        } else {
+            diff.abs()
+        };
+        // the share of a half turn
+        let aspect_share = (aspect_diff as i64) << 1;
+
+        let darkness = (((slope * aspect_share) >> FRACTION_BITS)
+            * self.intensity)
+            >> FRACTION_BITS;
+        let lightness = ONE - darkness.min(ONE);
+        ((255 * lightness) >> FRACTION_BITS).clamp(0, 255) as u8
+    }
+}
+
+/// Interpolates the table linearly at the fixed-point position (from 0 to
+/// 65536).
+fn interpolate(table: &[u32; 257], position: i64) -> i64 {
+    let index = (position >> 8) as usize;
+    if index >= 256 {
+        return table[256] as i64;
+    }
+    let (low, high) = (table[index] as i64, table[index + 1] as i64);
+    low + (((high - low) * (position & 0xff)) >> 8)
+}
+
+/// The slope's share of a right angle (in the fixed point) for the
+/// gradient's magnitude (in the fixed point).
+fn slope_share(gradient: i64) -> i64 {
+    interpolate(&SLOPE_TABLE, (gradient << FRACTION_BITS) / (ONE + gradient))
+}
+
+/// The four-quadrant arctangent of `y / x` as a binary angle (from minus to
+/// plus a half turn).
+fn atan2(y: i64, x: i64) -> i32 {
+    let (abs_x, abs_y) = (x.abs(), y.abs());
+    if abs_x == 0 && abs_y == 0 {
+        return 0;
+    }
+
+    let ratio = (abs_x.min(abs_y) << FRACTION_BITS) / abs_x.max(abs_y);
+    let mut angle = interpolate(&ATAN_TABLE, ratio) as i32;
+    if abs_y > abs_x {
+        angle = QUARTER_TURN - angle;
+    }
+    if x < 0 {
+        angle = HALF_TURN - angle;
+    }
+    if y < 0 {
+        -angle
+    } else {
+        angle
+    }
+}
+
+/// The integer square root (rounded down).
+fn sqrt(value: u64) -> u64 {
+    let mut remainder = value;
+    let mut root = 0;
+    let mut bit = 1 << 62;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/// The Sobel sums of the height differences towards the east and the south
+/// at the cell, from the rows above, at and below it.
+fn calculate_pq(rows: [&[i16]; 3], x: usize) -> (i32, i32) {
+    let [top, middle, bottom] = rows;
+    let height = |row: &[i16], x: usize| row[x] as i32;
+
+    let p = (height(bottom, x + 1)
+        + 2 * height(middle, x + 1)
+        + height(top, x + 1))
+        - (height(bottom, x - 1)
+            + 2 * height(middle, x - 1)
+            + height(top, x - 1));
+    let q =
+        (height(bottom, x + 1) + 2 * height(bottom, x) + height(bottom, x - 1))
+            - (height(top, x + 1) + 2 * height(top, x) + height(top, x - 1));
+    (p, q)
+}
+
+/// Shades the row's pixels (all but the first and the last one).
+fn shade_row(
+    settings: &FixedPointHillshading,
+    rows: [&[i16]; 3],
+    y: usize,
+    row: &mut [u8],
+) {
+    let size = row.len();
+    for (x, pixel) in row.iter_mut().enumerate().take(size - 1).skip(1) {
+        let (p, q) = calculate_pq(rows, x);
+        *pixel = settings.shade(p, q, y);
+    }
+}
+
+/// Hillshades the heights (`size` rows of `size` heights, from the north)
+/// into the pixels (of the same size) with integer arithmetic only. Like
+/// the other hillshaders, the border pixels are left untouched and the
+/// voids are shaded as if they were real heights.
+///
+/// # Panics
+///
+/// Panics if the heights or the pixels do not match the size.
+pub fn hillshade_heights(
+    heights: &[i16],
+    size: usize,
+    settings: &FixedPointHillshading,
+    pixels: &mut [u8],
+) {
+    if heights.len() != size * size || pixels.len() != size * size {
+        panic!("heights or pixels count does not match the size");
+    }
+
+    for (y, row) in interior_rows(size, 0, pixels) {
+        let rows = [
+            &heights[(y - 1) * size..y * size],
+            &heights[y * size..(y + 1) * size],
+            &heights[(y + 1) * size..(y + 2) * size],
+        ];
+        shade_row(settings, rows, y, row);
+    }
+}
+
+/// Hillshades the DEM like `igor_hillshading_opt1::hillshade` (within one
+/// gray level of it), with the fixed-point arithmetic of
+/// `hillshade_heights`.
+pub fn hillshade(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    bitmap: &mut Grayscale8Bitmap,
+) {
+    hillshade_by_rows(hillshade_rows, dem, parameters, bitmap);
+}
+
+/// Shades the band of the bitmap's rows, see `HillshadeRowsFn`. The
+/// settings are prepared on each call, `FixedPointHillshader` prepares them
+/// just once for all the bands of a tile.
+pub fn hillshade_rows(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    first_row: usize,
+    band: &mut [u8],
+) {
+    let settings = FixedPointHillshading::new(
+        parameters,
+        &parameters.grid_spacing(dem),
+        dem.size,
+    );
+    shade_rows(dem, &settings, first_row, band);
+}
+
+/// Shades the band of the bitmap's rows like `hillshade_rows`, with the
+/// prepared settings.
+pub fn shade_rows(
+    dem: &DemTile,
+    settings: &FixedPointHillshading,
+    first_row: usize,
+    band: &mut [u8],
+) {
+    let mut rows = [vec![0; dem.size], vec![0; dem.size], vec![0; dem.size]];
+    for (y, row) in interior_rows(dem.size, first_row, band) {
+        for (index, heights) in rows.iter_mut().enumerate() {
+            dem.read_row(y + index - 1, heights);
+        }
+        let rows = [&rows[0][..], &rows[1][..], &rows[2][..]];
+        shade_row(settings, rows, y, row);
+    }
+}
+
+/// The fixed-point hillshader (registered as `igor_fixed`), shading in
+/// parallel by bands of rows which share the tile's settings.
+pub struct FixedPointHillshader;
+
+impl Hillshader for FixedPointHillshader {
+    fn name(&self) -> &str {
+        "igor_fixed"
+    }
+
+    fn hillshade(
+        &self,
+        dem: &DemTile,
+        parameters: &HillshadingParameters,
+        bitmap: &mut Grayscale8Bitmap,
+    ) {
+        hillshade(dem, parameters, bitmap)
+    }
+
+    fn hillshade_parallel(
+        &self,
+        dem: &DemTile,
+        parameters: &HillshadingParameters,
+        bitmap: &mut Grayscale8Bitmap,
+        pool: &ThreadPool,
+    ) {
+        if bitmap.width as usize != dem.size
+            || bitmap.height as usize != dem.size
+        {
+            panic!("bitmap size does not match DEM size");
+        }
+
+        let dem = parameters.prepare_dem(dem);
+        let dem = dem.as_ref();
+        let settings = FixedPointHillshading::new(
+            parameters,
+            &parameters.grid_spacing(dem),
+            dem.size,
+        );
+        shade_bands_parallel(dem.size, bitmap, pool, |first_row, band| {
+            shade_rows(dem, &settings, first_row, band)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hillshading::igor_hillshading_opt1;
+    use crate::testing::{Surface, SyntheticTerrain};
+    use rstest::rstest;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn tables_hold_the_arctangents() {
+        for i in 0..=256 {
+            let t = i as f64 / 256.;
+            let slope = if i == 256 {
+                1.
+            } else {
+                (t / (1. - t)).atan() / (PI / 2.)
+            };
+            assert_eq!(SLOPE_TABLE[i], (slope * 65536.).round() as u32);
+            let angle = t.atan() / (2. * PI);
+            assert_eq!(ATAN_TABLE[i], (angle * 65536.).round() as u32);
+        }
+    }
+
+    #[test]
+    fn arctangents_and_roots_are_close() {
+        for (y, x) in [(0, 5), (3, 4), (-7, 2), (100, -1), (-5, -5), (0, -9)] {
+            let expected =
+                (y as f64).atan2(x as f64) / (2. * PI) * FULL_TURN as f64;
+            let angle = atan2(y * 1000, x * 1000) as f64;
+            assert!((angle - expected).abs() <= 2., "{}, {}", y, x);
+        }
+
+        for value in [0, 1, 2, 3, 4, 99, 100, 1 << 40, u64::MAX >> 2] {
+            let root = sqrt(value);
+            assert!(root * root <= value && (root + 1) * (root + 1) > value);
+        }
+    }
+
+    #[rstest]
+    #[case(315., 1.)]
+    #[case(45., 0.6)]
+    #[case(200., 2.)]
+    fn shades_are_within_one_level_of_the_float_ones(
+        #[case] sun_azimuth: f32,
+        #[case] intensity: f32,
+    ) {
+        let dem = SyntheticTerrain::new(13, 62, 101)
+            .with(Surface::FractalNoise {
+                seed: 24,
+                amplitude: 1200.,
+                wavelength: 25.,
+                octaves: 5,
+            })
+            .with(Surface::Cone {
+                center: (60., 30.),
+                radius: 20.,
+                height: 3000.,
+            })
+            .build();
+        let parameters = HillshadingParameters {
+            sun_azimuth,
+            intensity,
+            ..HillshadingParameters::default()
+        };
+
+        let mut expected = Grayscale8Bitmap::new(101, 101);
+        igor_hillshading_opt1::hillshade(&dem, &parameters, &mut expected);
+        let mut bitmap = Grayscale8Bitmap::new(101, 101);
+        hillshade(&dem, &parameters, &mut bitmap);
+
+        for (a, b) in expected.data().iter().zip(bitmap.data()) {
+            assert!(a.abs_diff(*b) <= 1, "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn raw_heights_are_shaded_like_the_tile() {
+        let dem = SyntheticTerrain::new(7, 46, 33)
+            .with(Surface::GaussianHill {
+                center: (12., 20.),
+                sigma: 6.,
+                height: 2500.,
+            })
+            .build();
+        let parameters = HillshadingParameters::default();
+        let settings = FixedPointHillshading::new(
+            &parameters,
+            &parameters.grid_spacing(&dem),
+            dem.size,
+        );
+
+        let mut pixels = vec![0; 33 * 33];
+        hillshade_heights(&dem.heights(), 33, &settings, &mut pixels);
+        let mut bitmap = Grayscale8Bitmap::new(33, 33);
+        hillshade(&dem, &parameters, &mut bitmap);
+
+        assert_eq!(pixels, bitmap.data());
+    }
+
+    #[test]
+    fn integer_settings_match_the_converted_parameters() {
+        let dem = SyntheticTerrain::new(7, 46, 5).build();
+        let parameters = HillshadingParameters {
+            sun_azimuth: 315.,
+            intensity: 0.75,
+            ..HillshadingParameters::default()
+        };
+        let spacing = parameters.grid_spacing(&dem);
+        let expected = FixedPointHillshading::new(&parameters, &spacing, 5);
+
+        let millimeters = |spacing: f32| (spacing * 1000.).round() as u32;
+        let north_south: Vec<u32> = (0..5)
+            .map(|y| millimeters(spacing.north_south(y)))
+            .collect();
+        let east_west: Vec<u32> =
+            (0..5).map(|y| millimeters(spacing.east_west(y))).collect();
+        let settings = FixedPointHillshading::from_integers(
+            -8192,
+            49152,
+            &north_south,
+            &east_west,
+        );
+
+        assert_eq!(settings.sun_azimuth, expected.sun_azimuth);
+        assert_eq!(settings.intensity, expected.intensity);
+        let scales = settings
+            .north_south_scales
+            .iter()
+            .zip(&expected.north_south_scales)
+            .chain(
+                settings
+                    .east_west_scales
+                    .iter()
+                    .zip(&expected.east_west_scales),
+            );
+        for (&scale, &expected_scale) in scales {
+            assert!(scale.abs_diff(expected_scale) <= 1, "{}", scale);
+        }
+    }
+}
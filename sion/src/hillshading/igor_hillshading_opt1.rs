@@ -1,10 +1,13 @@
 use crate::dem_tile::DemTile;
 use crate::geo::{difference_between_angles, normalize_angle};
 use crate::grayscale8_bitmap::Grayscale8Bitmap;
+use crate::hillshading::hillshader::{hillshade_by_rows, interior_rows};
 use crate::hillshading::parameters::HillshadingParameters;
 use crate::trig::deg_to_rad;
 use std::f32::consts::{FRAC_PI_2, PI};
 
+/// Calculates the (unscaled) Sobel sums of the height differences towards
+/// the east and the south, eight times the gradient times the grid spacing.
 pub fn calculate_pq(dem_tile: &DemTile, x: usize, y: usize) -> (f32, f32) {
     let center_index = y * dem_tile.size + x;
     let top_center_index = center_index - dem_tile.size;
@@ -31,8 +34,10 @@ pub fn calculate_pq(dem_tile: &DemTile, x: usize, y: usize) -> (f32, f32) {
     (p, q)
 }
 
+/// Calculates the slope and aspect (in radians) from the Sobel sums scaled
+/// by the grid spacing (see `hillshade`).
 pub fn calculate_slope_and_aspect(p: f32, q: f32) -> (f32, f32) {
-    let max_slope = (p * p + q * q).sqrt() / 240.;
+    let max_slope = (p * p + q * q).sqrt();
     let slope = max_slope.atan();
     let aspect = normalize_angle(q.atan2(p) - FRAC_PI_2);
 
@@ -44,15 +49,26 @@ pub fn hillshade(
     parameters: &HillshadingParameters,
     bitmap: &mut Grayscale8Bitmap,
 ) {
-    if bitmap.width as usize != dem.size || bitmap.height as usize != dem.size {
-        panic!("bitmap size does not match DEM size");
-    }
+    hillshade_by_rows(hillshade_rows, dem, parameters, bitmap);
+}
 
+/// Shades the band of the bitmap's rows, see `HillshadeRowsFn`.
+pub fn hillshade_rows(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    first_row: usize,
+    band: &mut [u8],
+) {
     let sun_azimuth = deg_to_rad(parameters.sun_azimuth);
+    let spacing = parameters.grid_spacing(dem);
 
-    for y in 1..dem.size - 1 {
-        for x in 1..dem.size - 1 {
+    for (y, row) in interior_rows(dem.size, first_row, band) {
+        let horizontal_scale = 1. / (8. * spacing.east_west(y));
+        let vertical_scale = 1. / (8. * spacing.north_south(y));
+        let pixels = row.iter_mut().enumerate().take(dem.size - 1).skip(1);
+        for (x, pixel) in pixels {
             let (p, q) = calculate_pq(dem, x, y);
+            let (p, q) = (p * horizontal_scale, q * vertical_scale);
 
             let (slope, aspect) = calculate_slope_and_aspect(p, q);
 
@@ -64,7 +80,7 @@ pub fn hillshade(
                     .min(1.);
             let darkness_shade = (255.0 * darkness) as u8;
 
-            bitmap.set_pixel(x as u16, y as u16, darkness_shade);
+            *pixel = darkness_shade;
         }
     }
 }
@@ -72,12 +88,27 @@ pub fn hillshade(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::dem_tile::DemTile;
+    use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+    use crate::errors::SionError;
     use crate::grayscale8_bitmap::Grayscale8Bitmap;
+    use crate::testing::SyntheticTerrain;
+    use crate::void_filling::VoidFillMethod;
+    use std::fs;
+
+    /// Writes the synthetic mountains into the test directory, with the
+    /// given writer, and returns the file's path.
+    fn write_mountains(
+        write: impl Fn(&SyntheticTerrain, &str) -> Result<String, SionError>,
+    ) -> String {
+        let dir = "target/debug/igor_hillshading_opt1_tests";
+        fs::create_dir_all(dir).unwrap();
+        write(&SyntheticTerrain::mountains(6, 46, 1201), dir).unwrap()
+    }
 
     #[test]
     fn hillshade_of_whole_dem_hgt() {
-        let dem = DemTile::from_hgt_file("tests/data/N46E006.hgt");
+        let file = write_mountains(SyntheticTerrain::write_hgt);
+        let dem = DemTile::from_hgt_file(&file).unwrap();
         let mut bitmap =
             Grayscale8Bitmap::new(dem.size as u16, dem.size as u16);
         let parameters = HillshadingParameters::default();
@@ -90,7 +121,8 @@ mod tests {
 
     #[test]
     fn hillshade_of_whole_dem_xth() {
-        let dem = DemTile::from_xth_file("tests/data/N46E006.xth");
+        let file = write_mountains(SyntheticTerrain::write_xth);
+        let dem = DemTile::from_xth_file(&file).unwrap();
         let mut bitmap =
             Grayscale8Bitmap::new(dem.size as u16, dem.size as u16);
         let parameters = HillshadingParameters::default();
@@ -100,4 +132,35 @@ mod tests {
             .write_to_png("target/debug/igor_hillshading_opt1_xth.png")
             .unwrap()
     }
+
+    #[test]
+    fn voids_are_filled_before_shading_when_requested() {
+        // a flat tile (shaded all white) with a single void cell
+        let mut heights = vec![500; 25];
+        heights[12] = NO_DATA_HEIGHT;
+        let dem = DemTile::from_heights(7, 46, 5, &heights);
+
+        let shade = |void_filling| {
+            let mut bitmap = Grayscale8Bitmap::new(5, 5);
+            let parameters = HillshadingParameters {
+                void_filling,
+                ..HillshadingParameters::default()
+            };
+            hillshade(&dem, &parameters, &mut bitmap);
+            bitmap
+        };
+
+        let unfilled = shade(None);
+        assert!((1..4).any(|x| unfilled.get_pixel(x, 1) != 255));
+
+        let filled = shade(Some(VoidFillMethod::Laplacian));
+        for y in 1..4 {
+            for x in 1..4 {
+                assert_eq!(filled.get_pixel(x, y), 255);
+            }
+        }
+
+        // the DEM itself is left untouched
+        assert_eq!(dem.void_count(), 1);
+    }
 }
@@ -1,7 +1,7 @@
-use crate::consts::EARTH_CIRCUMFERENCE_METERS;
 use crate::dem_tile::DemTile;
 use crate::geo::{difference_between_angles, normalize_angle};
 use crate::grayscale8_bitmap::Grayscale8Bitmap;
+use crate::hillshading::hillshader::{hillshade_by_rows, interior_rows};
 use crate::hillshading::parameters::HillshadingParameters;
 use crate::trig::deg_to_rad;
 use std::f32::consts::{FRAC_PI_2, PI};
@@ -54,28 +54,25 @@ pub fn hillshade(
     parameters: &HillshadingParameters,
     bitmap: &mut Grayscale8Bitmap,
 ) {
-    if bitmap.width as usize != dem.size || bitmap.height as usize != dem.size {
-        panic!("bitmap size does not match DEM size");
-    }
+    hillshade_by_rows(hillshade_rows, dem, parameters, bitmap);
+}
 
+/// Shades the band of the bitmap's rows, see `HillshadeRowsFn`.
+pub fn hillshade_rows(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    first_row: usize,
+    band: &mut [u8],
+) {
     let sun_azimuth = deg_to_rad(parameters.sun_azimuth);
 
-    // Calculate the (approximate) horizontal and vertical grid
-    // spacing (in meters). Note that for latitude, we add 0.5 degrees
-    // to the calculation so the spacing is calculated for the center
-    // of the DEM tile.
-    let horizontal_grid_spacing_meters = deg_to_rad(dem.lon as f32 + 0.5).cos()
-        * EARTH_CIRCUMFERENCE_METERS
-        / 360.
-        / dem.size as f32;
-    let horizontal_spacing_mul8 = 8.0 * horizontal_grid_spacing_meters;
-
-    let vertical_grid_spacing_meters =
-        EARTH_CIRCUMFERENCE_METERS / 360. / dem.size as f32;
-    let vertical_spacing_mul8 = 8.0 * vertical_grid_spacing_meters;
-
-    for y in 1..dem.size - 1 {
-        for x in 1..dem.size - 1 {
+    let spacing = parameters.grid_spacing(dem);
+
+    for (y, row) in interior_rows(dem.size, first_row, band) {
+        let horizontal_spacing_mul8 = 8.0 * spacing.east_west(y);
+        let vertical_spacing_mul8 = 8.0 * spacing.north_south(y);
+        let pixels = row.iter_mut().enumerate().take(dem.size - 1).skip(1);
+        for (x, pixel) in pixels {
             let (p, q) = calculate_pq(
                 dem,
                 x,
@@ -94,7 +91,7 @@ pub fn hillshade(
                     .min(1.);
             let darkness_shade = (255.0 * darkness) as u8;
 
-            bitmap.set_pixel(x as u16, y as u16, darkness_shade);
+            *pixel = darkness_shade;
         }
     }
 }
@@ -102,12 +99,12 @@ pub fn hillshade(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::dem_tile::DemTile;
     use crate::grayscale8_bitmap::Grayscale8Bitmap;
+    use crate::testing::{Surface, SyntheticTerrain};
 
     #[test]
     fn hillshade_of_whole_dem() {
-        let dem = DemTile::from_hgt_file("tests/data/N46E006.hgt");
+        let dem = SyntheticTerrain::mountains(6, 46, 1201).build();
         let mut bitmap =
             Grayscale8Bitmap::new(dem.size as u16, dem.size as u16);
         let parameters = HillshadingParameters::default();
@@ -117,4 +114,38 @@ mod tests {
             .write_to_png("target/debug/igor_hillshading_orig.png")
             .unwrap()
     }
+
+    #[test]
+    fn planes_are_shaded_uniformly_by_their_aspect() {
+        let shade = |aspect| {
+            let dem = SyntheticTerrain::new(6, 46, 21)
+                .with(Surface::Plane {
+                    height: 1000.,
+                    slope: 20.,
+                    aspect,
+                })
+                .build();
+            let mut bitmap = Grayscale8Bitmap::new(21, 21);
+            hillshade(&dem, &HillshadingParameters::default(), &mut bitmap);
+
+            let shades: Vec<u8> = (1..20)
+                .flat_map(|y| (1..20).map(move |x| (x, y)))
+                .map(|(x, y)| bitmap.get_pixel(x, y))
+                .collect();
+            assert!(shades.iter().all(|&shade| shade.abs_diff(shades[0]) <= 1));
+            shades[0]
+        };
+
+        let sun_azimuth = HillshadingParameters::default().sun_azimuth;
+        let facing_the_sun = shade(sun_azimuth);
+        let facing_away = shade(sun_azimuth + 180.);
+        assert!(facing_the_sun >= 254, "{}", facing_the_sun);
+        // darkened by the slope's share of a right angle
+        let expected = 255. * (1. - 20. / 90.);
+        assert!(
+            (facing_away as f32 - expected).abs() <= 1.,
+            "{}",
+            facing_away
+        );
+    }
 }
@@ -0,0 +1,294 @@
+use crate::dem_tile::DemTile;
+use crate::grayscale8_bitmap::Grayscale8Bitmap;
+use crate::hillshading::hillshader::{hillshade_by_rows, interior_rows};
+use crate::hillshading::parameters::HillshadingParameters;
+use crate::trig::deg_to_rad;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use wide::{f32x8, CmpGt, CmpLt};
+
+/// The number of pixels shaded at once.
+const LANES: usize = 8;
+
+/// The coefficients of the odd polynomial approximating atan on [0, 1],
+/// from the lowest power up.
+const ATAN_COEFFICIENTS: [f32; 6] = [
+    0.999_977_26,
+    -0.332_623_47,
+    0.193_543_46,
+    -0.116_432_87,
+    0.052_653_32,
+    -0.011_721_2,
+];
+
+/// Approximates the arctangent for the values in [0, 1] with a polynomial.
+fn atan_unit(x: f32x8) -> f32x8 {
+    let x2 = x * x;
+    let mut polynomial = f32x8::splat(ATAN_COEFFICIENTS[5]);
+    for coefficient in ATAN_COEFFICIENTS[..5].iter().rev() {
+        polynomial = polynomial.mul_add(x2, f32x8::splat(*coefficient));
+    }
+    polynomial * x
+}
+
+/// Approximates the arctangent (in radians, within 1e-5 of `f32::atan`).
+pub fn atan(x: f32x8) -> f32x8 {
+    let abs = x.abs();
+    let above_one = abs.cmp_gt(f32x8::ONE);
+    // atan(x) = π/2 - atan(1/x) for x > 1
+    let reduced = above_one.blend(f32x8::ONE / abs, abs);
+    let angle = atan_unit(reduced);
+    let angle = above_one.blend(f32x8::splat(FRAC_PI_2) - angle, angle);
+    x.cmp_lt(f32x8::ZERO).blend(-angle, angle)
+}
+
+/// Approximates the four-quadrant arctangent of `y / x` (in radians, within
+/// 1e-5 of `f32::atan2`, except for the sign of the zeros).
+pub fn atan2(y: f32x8, x: f32x8) -> f32x8 {
+    let (abs_x, abs_y) = (x.abs(), y.abs());
+    let ratio = abs_x.min(abs_y)
+        / abs_x.max(abs_y).max(f32x8::splat(f32::MIN_POSITIVE));
+    let angle = atan_unit(ratio);
+    let angle = abs_y
+        .cmp_gt(abs_x)
+        .blend(f32x8::splat(FRAC_PI_2) - angle, angle);
+    let angle = x.cmp_lt(f32x8::ZERO).blend(f32x8::splat(PI) - angle, angle);
+    y.cmp_lt(f32x8::ZERO).blend(-angle, angle)
+}
+
+/// Hillshades the DEM like `igor_hillshading_opt1::hillshade`, shading
+/// eight pixels at once with SIMD instructions. The shades are within one
+/// gray level of the `igor_hillshading_opt1` ones (the arctangents are
+/// approximated).
+pub fn hillshade(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    bitmap: &mut Grayscale8Bitmap,
+) {
+    hillshade_by_rows(hillshade_rows, dem, parameters, bitmap);
+}
+
+/// Shades the band of the bitmap's rows, see `HillshadeRowsFn`.
+pub fn hillshade_rows(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    first_row: usize,
+    band: &mut [u8],
+) {
+    // a tile without interior cells leaves the whole bitmap untouched
+    if dem.size < 3 {
+        return;
+    }
+
+    let spacing = parameters.grid_spacing(dem);
+
+    // the rows are padded so that each chunk of pixels has all of its
+    // neighbours
+    let chunks = (dem.size - 2 + LANES - 1) / LANES;
+    let mut kernel = Kernel {
+        sun_azimuth: deg_to_rad(parameters.sun_azimuth),
+        intensity: parameters.intensity,
+        heights: vec![0; dem.size],
+        rows: [
+            vec![0.; chunks * LANES + 2],
+            vec![0.; chunks * LANES + 2],
+            vec![0.; chunks * LANES + 2],
+        ],
+        shades: vec![0; chunks * LANES],
+    };
+
+    let mut loaded_row = None;
+    for (y, row) in interior_rows(dem.size, first_row, band) {
+        if loaded_row == Some(y - 1) {
+            kernel.rows.rotate_left(1);
+            kernel.load_row(dem, 2, y + 1);
+        } else {
+            for (index, dem_row) in (y - 1..=y + 1).enumerate() {
+                kernel.load_row(dem, index, dem_row);
+            }
+        }
+        loaded_row = Some(y);
+
+        let horizontal_scale = 1. / (8. * spacing.east_west(y));
+        let vertical_scale = 1. / (8. * spacing.north_south(y));
+        kernel.shade_row(horizontal_scale, vertical_scale);
+        row[1..dem.size - 1].copy_from_slice(&kernel.shades[..dem.size - 2]);
+    }
+}
+
+/// The kernel's state: the DEM's rows around the shaded one (converted to
+/// floats) and the shaded row.
+struct Kernel {
+    sun_azimuth: f32,
+    intensity: f32,
+    heights: Vec<i16>,
+    rows: [Vec<f32>; 3],
+    shades: Vec<u8>,
+}
+
+impl Kernel {
+    fn load_row(&mut self, dem: &DemTile, index: usize, y: usize) {
+        dem.read_row(y, &mut self.heights);
+        for (value, height) in self.rows[index].iter_mut().zip(&self.heights) {
+            *value = *height as f32;
+        }
+    }
+
+    fn shade_row(&mut self, horizontal_scale: f32, vertical_scale: f32) {
+        let load = |row: &[f32], offset: usize| {
+            let lanes: [f32; LANES] =
+                row[offset..offset + LANES].try_into().unwrap();
+            f32x8::from(lanes)
+        };
+        let two = f32x8::splat(2.);
+
+        for (chunk, shades) in self.shades.chunks_exact_mut(LANES).enumerate() {
+            let x = chunk * LANES;
+            let [top, middle, bottom] = &self.rows;
+            let (tl, t, tr) =
+                (load(top, x), load(top, x + 1), load(top, x + 2));
+            let (l, r) = (load(middle, x), load(middle, x + 2));
+            let (bl, b, br) =
+                (load(bottom, x), load(bottom, x + 1), load(bottom, x + 2));
+
+            let p = ((br + two * r + tr) - (bl + two * l + tl))
+                * f32x8::splat(horizontal_scale);
+            let q = ((br + two * b + bl) - (tr + two * t + tl))
+                * f32x8::splat(vertical_scale);
+
+            let slope = atan((p * p + q * q).sqrt());
+            let aspect = atan2(q, p) - f32x8::splat(FRAC_PI_2);
+            let aspect = aspect
+                .cmp_lt(f32x8::ZERO)
+                .blend(aspect + f32x8::splat(TAU), aspect);
+
+            // the same difference as `difference_between_angles`
+            let diff = aspect - f32x8::splat(self.sun_azimuth);
+            let aspect_diff = diff.cmp_lt(f32x8::splat(-PI)).blend(
+                diff + f32x8::splat(TAU),
+                diff.cmp_gt(f32x8::splat(PI))
+                    .blend(diff - f32x8::splat(TAU), diff.abs()),
+            );
+
+            let darkness = f32x8::ONE
+                - (slope / f32x8::splat(FRAC_PI_2) * aspect_diff
+                    / f32x8::splat(PI)
+                    * f32x8::splat(self.intensity))
+                .min(f32x8::ONE);
+            let darkness_shades = (f32x8::splat(255.) * darkness).to_array();
+
+            for (shade, darkness_shade) in
+                shades.iter_mut().zip(darkness_shades)
+            {
+                *shade = darkness_shade as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hillshading::igor_hillshading_opt1;
+    use crate::testing::{Surface, SyntheticTerrain};
+    use rstest::rstest;
+
+    #[test]
+    fn arctangents_are_approximated_closely() {
+        let values: Vec<f32> =
+            (-4000..=4000).map(|i| (i as f32 / 100.).powi(3)).collect();
+
+        for chunk in values.chunks_exact(LANES) {
+            let x = f32x8::from(<[f32; LANES]>::try_from(chunk).unwrap());
+            for (value, approximation) in chunk.iter().zip(atan(x).to_array()) {
+                assert!(
+                    (value.atan() - approximation).abs() < 1e-5,
+                    "{}",
+                    value
+                );
+            }
+        }
+
+        for (y, x) in values.iter().zip(values.iter().rev().skip(3)) {
+            let approximation =
+                atan2(f32x8::splat(*y), f32x8::splat(*x)).to_array()[0];
+            assert!((y.atan2(*x) - approximation).abs() < 1e-5, "{}, {}", y, x);
+        }
+        assert_eq!(atan2(f32x8::ZERO, f32x8::ZERO).to_array()[0], 0.);
+    }
+
+    #[rstest]
+    #[case(315., 1.)]
+    #[case(45., 0.6)]
+    #[case(200., 2.)]
+    fn shades_are_within_one_level_of_the_scalar_ones(
+        #[case] sun_azimuth: f32,
+        #[case] intensity: f32,
+    ) {
+        let dem = SyntheticTerrain::new(13, 62, 101)
+            .with(Surface::FractalNoise {
+                seed: 23,
+                amplitude: 1200.,
+                wavelength: 25.,
+                octaves: 5,
+            })
+            .with(Surface::Cone {
+                center: (60., 30.),
+                radius: 20.,
+                height: 3000.,
+            })
+            .build();
+        let parameters = HillshadingParameters {
+            sun_azimuth,
+            intensity,
+            ..HillshadingParameters::default()
+        };
+
+        let mut expected = Grayscale8Bitmap::new(101, 101);
+        igor_hillshading_opt1::hillshade(&dem, &parameters, &mut expected);
+        let mut bitmap = Grayscale8Bitmap::new(101, 101);
+        hillshade(&dem, &parameters, &mut bitmap);
+
+        let differences = expected
+            .data()
+            .iter()
+            .zip(bitmap.data())
+            .map(|(a, b)| a.abs_diff(*b));
+        assert!(differences.clone().all(|difference| difference <= 1));
+        // just the truncation of the shades very close to whole levels
+        assert!(differences.filter(|&difference| difference > 0).count() < 50);
+    }
+
+    #[test]
+    fn tiles_narrower_than_the_lanes_are_shaded() {
+        let dem = SyntheticTerrain::new(0, 0, 5)
+            .with(Surface::Plane {
+                height: 100.,
+                slope: 10.,
+                aspect: 135.,
+            })
+            .build();
+        let parameters = HillshadingParameters::default();
+
+        let mut expected = Grayscale8Bitmap::new(5, 5);
+        igor_hillshading_opt1::hillshade(&dem, &parameters, &mut expected);
+        let mut bitmap = Grayscale8Bitmap::new(5, 5);
+        hillshade(&dem, &parameters, &mut bitmap);
+
+        assert_eq!(bitmap.data(), expected.data());
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    fn tiles_without_interior_cells_are_left_untouched(#[case] size: usize) {
+        let dem = DemTile::from_heights(0, 0, size, &vec![100; size * size]);
+        let parameters = HillshadingParameters::default();
+
+        let mut expected = Grayscale8Bitmap::new(size as u16, size as u16);
+        igor_hillshading_opt1::hillshade(&dem, &parameters, &mut expected);
+        let mut bitmap = Grayscale8Bitmap::new(size as u16, size as u16);
+        hillshade(&dem, &parameters, &mut bitmap);
+
+        assert_eq!(bitmap.data(), expected.data());
+    }
+}
@@ -0,0 +1,456 @@
+use crate::dem_tile::DemTile;
+use crate::grayscale8_bitmap::Grayscale8Bitmap;
+use crate::hillshading::hillshader::{hillshade_by_rows, interior_rows};
+use crate::hillshading::parameters::{
+    HillshadingParameters, MultidirectionalLighting,
+};
+use crate::trig::deg_to_rad;
+
+/// Calculates the terrain's gradient at the cell using Horn's method: the
+/// height change per horizontal unit towards the east and the north.
+pub fn calculate_gradient(
+    dem_tile: &DemTile,
+    x: usize,
+    y: usize,
+    east_west_spacing: f32,
+    north_south_spacing: f32,
+) -> (f32, f32) {
+    let center_index = y * dem_tile.size + x;
+    let top_center_index = center_index - dem_tile.size;
+    let bottom_center_index = center_index + dem_tile.size;
+    let height = |index: usize| dem_tile.height_at_index(index) as f32;
+
+    let (tl, t, tr) = (
+        height(top_center_index - 1),
+        height(top_center_index),
+        height(top_center_index + 1),
+    );
+    let (l, r) = (height(center_index - 1), height(center_index + 1));
+    let (bl, b, br) = (
+        height(bottom_center_index - 1),
+        height(bottom_center_index),
+        height(bottom_center_index + 1),
+    );
+
+    let towards_east =
+        ((tr + 2. * r + br) - (tl + 2. * l + bl)) / (8. * east_west_spacing);
+    let towards_north =
+        ((tl + 2. * t + tr) - (bl + 2. * b + br)) / (8. * north_south_spacing);
+
+    (towards_east, towards_north)
+}
+
+/// Hillshades the DEM with the classic Lambertian reflectance model (the
+/// cosine of the angle between the terrain's normal and the sun's
+/// direction) using Horn's gradient, the way gdaldem's and ESRI's
+/// `hillshade` do. Like gdaldem, the shades range from 1 (facing away from
+/// the sun) to 255 (facing the sun), flat terrain is shaded by the sun's
+/// altitude. If `parameters.multidirectional` is set, the shades of its
+/// light sources are combined instead, see `hillshade_multidirectional`.
+///
+/// The grid spacing is calculated from the DEM's `GridGeometry` and
+/// `parameters.scale` (like gdaldem's `-s`), see `GridSpacing`.
+pub fn hillshade(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    bitmap: &mut Grayscale8Bitmap,
+) {
+    hillshade_by_rows(hillshade_rows, dem, parameters, bitmap);
+}
+
+/// Shades the band of the bitmap's rows like `hillshade`, see
+/// `HillshadeRowsFn`.
+pub fn hillshade_rows(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    first_row: usize,
+    band: &mut [u8],
+) {
+    match parameters.multidirectional {
+        Some(lighting) => {
+            shade(dem, parameters, Some(&lighting), first_row, band)
+        }
+        None => shade(dem, parameters, None, first_row, band),
+    }
+}
+
+/// Hillshades the DEM like `hillshade`, combining the shades of several
+/// light sources with aspect-dependent weights (see
+/// `MultidirectionalLighting`). Without `parameters.multidirectional`, the
+/// MDOW lights are used (like gdaldem's `-multidirectional`).
+pub fn hillshade_multidirectional(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    bitmap: &mut Grayscale8Bitmap,
+) {
+    hillshade_by_rows(hillshade_multidirectional_rows, dem, parameters, bitmap);
+}
+
+/// Shades the band of the bitmap's rows like `hillshade_multidirectional`,
+/// see `HillshadeRowsFn`.
+pub fn hillshade_multidirectional_rows(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    first_row: usize,
+    band: &mut [u8],
+) {
+    let lighting = parameters.multidirectional.unwrap_or_default();
+    shade(dem, parameters, Some(&lighting), first_row, band);
+}
+
+/// A light source's direction, as (east, north, up) components, and its
+/// horizontal direction (east, north).
+struct Light {
+    direction: (f32, f32, f32),
+    horizontal: (f32, f32),
+    weight: f32,
+}
+
+impl Light {
+    fn new(azimuth: f32, altitude: f32, weight: f32) -> Light {
+        let (azimuth, altitude) = (deg_to_rad(azimuth), deg_to_rad(altitude));
+        Light {
+            direction: (
+                azimuth.sin() * altitude.cos(),
+                azimuth.cos() * altitude.cos(),
+                altitude.sin(),
+            ),
+            horizontal: (azimuth.sin(), azimuth.cos()),
+            weight,
+        }
+    }
+
+    /// The cosine of the angle between the light's direction and the
+    /// terrain's normal, (-towards_east, -towards_north, 1) normalized.
+    fn cosine(&self, towards_east: f32, towards_north: f32, norm: f32) -> f32 {
+        let (east, north, up) = self.direction;
+        (up - east * towards_east - north * towards_north) / norm
+    }
+}
+
+fn shade(
+    dem: &DemTile,
+    parameters: &HillshadingParameters,
+    lighting: Option<&MultidirectionalLighting>,
+    first_row: usize,
+    band: &mut [u8],
+) {
+    let spacing = parameters.grid_spacing(dem);
+    let z_factor = parameters.z_factor;
+
+    let lights: Vec<Light> = match lighting {
+        Some(lighting) => lighting
+            .light_sources()
+            .iter()
+            .map(|light| {
+                Light::new(light.azimuth, parameters.sun_altitude, light.weight)
+            })
+            .collect(),
+        None => {
+            vec![Light::new(
+                parameters.sun_azimuth,
+                parameters.sun_altitude,
+                1.,
+            )]
+        }
+    };
+
+    for (y, row) in interior_rows(dem.size, first_row, band) {
+        let east_west_spacing = spacing.east_west(y);
+        let pixels = row.iter_mut().enumerate().take(dem.size - 1).skip(1);
+        for (x, pixel) in pixels {
+            let (towards_east, towards_north) = calculate_gradient(
+                dem,
+                x,
+                y,
+                east_west_spacing,
+                spacing.north_south(y),
+            );
+            let (towards_east, towards_north) =
+                (towards_east * z_factor, towards_north * z_factor);
+            let slope_squared =
+                towards_east * towards_east + towards_north * towards_north;
+            let norm = (1. + slope_squared).sqrt();
+
+            let cosine = if lighting.is_none() {
+                lights[0].cosine(towards_east, towards_north, norm)
+            } else {
+                multidirectional_cosine(
+                    &lights,
+                    towards_east,
+                    towards_north,
+                    slope_squared,
+                    norm,
+                )
+            };
+            let shade = 1. + 254. * cosine.max(0.);
+
+            *pixel = shade.round() as u8;
+        }
+    }
+}
+
+/// Combines the lights' (non-negative) cosines, each light weighted by the
+/// squared cosine of the angle between its horizontal direction and the
+/// gradient. On flat terrain, or if the lights are all perpendicular to the
+/// gradient, just the lights' own weights are used.
+fn multidirectional_cosine(
+    lights: &[Light],
+    towards_east: f32,
+    towards_north: f32,
+    slope_squared: f32,
+    norm: f32,
+) -> f32 {
+    let mut weighted_sum = 0.;
+    let mut weights_sum = 0.;
+    let mut plain_weighted_sum = 0.;
+    let mut plain_weights_sum = 0.;
+
+    for light in lights {
+        let cosine = light.cosine(towards_east, towards_north, norm).max(0.);
+        let (east, north) = light.horizontal;
+        let along_gradient = east * towards_east + north * towards_north;
+        if slope_squared > 0. {
+            let weight =
+                light.weight * along_gradient * along_gradient / slope_squared;
+            weighted_sum += weight * cosine;
+            weights_sum += weight;
+        }
+        plain_weighted_sum += light.weight * cosine;
+        plain_weights_sum += light.weight;
+    }
+
+    if weights_sum > 1e-6 {
+        weighted_sum / weights_sum
+    } else {
+        plain_weighted_sum / plain_weights_sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hillshading::parameters::LightSource;
+    use crate::testing::{Surface, SyntheticTerrain};
+    use rstest::rstest;
+
+    /// gdaldem's hillshade (the `GDALHillshadeAlg` with the Horn gradient)
+    /// of the 3x3 window, transcribed with its own conventions: the window
+    /// listed row by row from the north-west, the north-south resolution
+    /// negative (north-up rasters), the result written into a byte band.
+    fn gdaldem_hillshade(
+        window: [f64; 9],
+        ewres: f64,
+        nsres: f64,
+        azimuth: f64,
+        altitude: f64,
+        z: f64,
+    ) -> u8 {
+        let inv_ewres = 1. / (8. * ewres);
+        let inv_nsres = 1. / (8. * nsres);
+        let x = ((window[0] + window[3] + window[3] + window[6])
+            - (window[2] + window[5] + window[5] + window[8]))
+            * inv_ewres;
+        let y = ((window[6] + window[7] + window[7] + window[8])
+            - (window[0] + window[1] + window[1] + window[2]))
+            * inv_nsres;
+
+        let (az, alt) = (azimuth.to_radians(), altitude.to_radians());
+        let cang_mul_254 = (254. * alt.sin()
+            - (y * az.cos() * alt.cos() * z * 254.
+                - x * az.sin() * alt.cos() * z * 254.))
+            / (1. + z * z * (x * x + y * y)).sqrt();
+        let cang = if cang_mul_254 <= 0. {
+            1.
+        } else {
+            1. + cang_mul_254
+        };
+        cang.round() as u8
+    }
+
+    #[rstest]
+    #[case(315., 45., 1.)]
+    #[case(0., 30., 1.)]
+    #[case(135., 60., 3.)]
+    fn matches_gdaldem_on_a_synthetic_terrain(
+        #[case] sun_azimuth: f32,
+        #[case] sun_altitude: f32,
+        #[case] z_factor: f32,
+    ) {
+        let size = 65;
+        let dem = SyntheticTerrain::new(6, 46, size)
+            .with(Surface::FractalNoise {
+                seed: 19,
+                amplitude: 400.,
+                wavelength: 20.,
+                octaves: 4,
+            })
+            .with(Surface::GaussianHill {
+                center: (20., 40.),
+                sigma: 8.,
+                height: 1500.,
+            })
+            .build();
+        let parameters = HillshadingParameters {
+            sun_azimuth,
+            sun_altitude,
+            z_factor,
+            ..HillshadingParameters::default()
+        };
+
+        let mut bitmap = Grayscale8Bitmap::new(size as u16, size as u16);
+        hillshade(&dem, &parameters, &mut bitmap);
+
+        let resolution = parameters.scale as f64 / (size - 1) as f64;
+        for y in 1..size - 1 {
+            // the row's east-west resolution, as if the tile was reprojected
+            let lat = 47. - y as f64 / (size - 1) as f64;
+            let ewres = resolution * lat.to_radians().cos();
+            for x in 1..size - 1 {
+                let mut window = [0.; 9];
+                for (i, height) in window.iter_mut().enumerate() {
+                    *height = dem.height_at(
+                        (x + i % 3 - 1) as u16,
+                        (y + i / 3 - 1) as u16,
+                    ) as f64;
+                }
+                let expected = gdaldem_hillshade(
+                    window,
+                    ewres,
+                    -resolution,
+                    sun_azimuth as f64,
+                    sun_altitude as f64,
+                    z_factor as f64,
+                );
+
+                let shade = bitmap.get_pixel(x as u16, y as u16);
+                assert!(
+                    shade.abs_diff(expected) <= 1,
+                    "{} vs {} at {}, {}",
+                    shade,
+                    expected,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[rstest]
+    // flat terrain is shaded by the sun's altitude: 1 + 254 * sin(45°)
+    #[case(0., 0., 181)]
+    // facing the sun, perpendicular to its rays
+    #[case(45., 315., 255)]
+    // facing away from the sun, in the shadow
+    #[case(50., 135., 1)]
+    fn planes_have_the_lambertian_shades(
+        #[case] slope: f32,
+        #[case] aspect: f32,
+        #[case] expected: u8,
+    ) {
+        let terrain = SyntheticTerrain::new(0, 0, 11).with(Surface::Plane {
+            height: 5000.,
+            slope,
+            aspect,
+        });
+        // gdaldem's scale for a square grid (the plane uses the tile's
+        // actual spacing)
+        let spacing = terrain.grid_spacing().north_south(0);
+        let parameters = HillshadingParameters {
+            scale: spacing * 10.,
+            ..HillshadingParameters::default()
+        };
+
+        let mut bitmap = Grayscale8Bitmap::new(11, 11);
+        hillshade(&terrain.build(), &parameters, &mut bitmap);
+
+        assert!(bitmap.get_pixel(5, 5).abs_diff(expected) <= 1);
+    }
+
+    /// The MDOW shade of a plane: the lights' cosines weighted by the squared
+    /// cosine of the angle between each light's azimuth and the aspect.
+    fn mdow_shade(slope: f32, aspect: f32, altitude: f32) -> f32 {
+        let (slope, altitude) = (slope.to_radians(), altitude.to_radians());
+        let (mut weighted_sum, mut weights_sum) = (0., 0.);
+        for azimuth in [225f32, 270., 315., 360.] {
+            let angle = (azimuth - aspect).to_radians();
+            let cosine = (altitude.sin() * slope.cos()
+                + altitude.cos() * slope.sin() * angle.cos())
+            .max(0.);
+            let weight = angle.cos().powi(2);
+            weighted_sum += weight * cosine;
+            weights_sum += weight;
+        }
+        1. + 254. * weighted_sum / weights_sum
+    }
+
+    #[rstest]
+    #[case(0., 0.)]
+    #[case(30., 45.)]
+    #[case(30., 135.)]
+    #[case(60., 200.)]
+    #[case(20., 300.)]
+    fn planes_have_the_mdow_shades(#[case] slope: f32, #[case] aspect: f32) {
+        let terrain = SyntheticTerrain::new(0, 0, 11).with(Surface::Plane {
+            height: 5000.,
+            slope,
+            aspect,
+        });
+        let spacing = terrain.grid_spacing().north_south(0);
+        let parameters = HillshadingParameters {
+            scale: spacing * 10.,
+            ..HillshadingParameters::default()
+        };
+
+        let mut bitmap = Grayscale8Bitmap::new(11, 11);
+        hillshade_multidirectional(&terrain.build(), &parameters, &mut bitmap);
+
+        let expected = mdow_shade(slope, aspect, 45.);
+        let shade = bitmap.get_pixel(5, 5) as f32;
+        assert!((shade - expected).abs() <= 1.5, "{} vs {}", shade, expected);
+    }
+
+    #[test]
+    fn ridges_parallel_to_the_sun_show_with_several_lights() {
+        // a ridge running from the north-west to the south-east, i.e. along
+        // the default sun's azimuth
+        let terrain = |aspect| {
+            SyntheticTerrain::new(0, 0, 11)
+                .with(Surface::Plane {
+                    height: 3000.,
+                    slope: 30.,
+                    aspect,
+                })
+                .build()
+        };
+        let (north_east_flank, south_west_flank) =
+            (terrain(45.), terrain(225.));
+
+        let shades = |parameters: &HillshadingParameters| {
+            let mut bitmap = Grayscale8Bitmap::new(11, 11);
+            hillshade(&north_east_flank, parameters, &mut bitmap);
+            let north_east = bitmap.get_pixel(5, 5);
+            hillshade(&south_west_flank, parameters, &mut bitmap);
+            (north_east, bitmap.get_pixel(5, 5))
+        };
+
+        let single = shades(&HillshadingParameters::default());
+        assert_eq!(single.0, single.1);
+
+        let lighting = MultidirectionalLighting::new(&[
+            LightSource {
+                azimuth: 270.,
+                weight: 1.,
+            },
+            LightSource {
+                azimuth: 315.,
+                weight: 2.,
+            },
+        ]);
+        let multiple = shades(&HillshadingParameters {
+            multidirectional: Some(lighting),
+            ..HillshadingParameters::default()
+        });
+        assert!(multiple.1 > multiple.0 + 20, "{:?}", multiple);
+    }
+}
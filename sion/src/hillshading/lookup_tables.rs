@@ -0,0 +1,288 @@
+use crate::errors::SionError;
+use crate::geo::normalize_angle;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// The lookup tables' values are angles in hundredths of a degree.
+const VALUES_PER_DEGREE: f64 = 100.;
+
+/// The number of values on a line of the generated source code.
+const VALUES_PER_LINE: usize = 12;
+
+/// The slope and aspect lookup tables for hillshading without the
+/// trigonometric functions (e.g. on microcontrollers), built analytically
+/// for the given resolution. Each table divides its range into `resolution`
+/// steps and has `resolution + 1` angles (in hundredths of a degree):
+/// - `slopes[i]` is the slope of the gradient `t / (1 - t)` for
+///   `t = i / resolution`, so the gradient `g` is looked up at
+///   `t = g / (1 + g)`;
+/// - `arctangents[i]` is `atan(i / resolution)`, the aspect of the gradient
+///   is looked up by the ratio of its smaller and larger components (see
+///   `slope_and_aspect`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LookupTables {
+    pub resolution: usize,
+    pub slopes: Vec<u16>,
+    pub arctangents: Vec<u16>,
+}
+
+impl LookupTables {
+    /// Builds the tables for the resolution.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resolution is 0.
+    pub fn new(resolution: usize) -> LookupTables {
+        if resolution == 0 {
+            panic!("The lookup tables' resolution must be at least 1");
+        }
+
+        let angle = |radians: f64| {
+            (radians.to_degrees() * VALUES_PER_DEGREE).round() as u16
+        };
+        let steps = (0..=resolution).map(|i| i as f64 / resolution as f64);
+
+        LookupTables {
+            resolution,
+            slopes: steps
+                .clone()
+                .map(|t| {
+                    if t < 1. {
+                        angle((t / (1. - t)).atan())
+                    } else {
+                        angle(std::f64::consts::FRAC_PI_2)
+                    }
+                })
+                .collect(),
+            arctangents: steps.map(|ratio| angle(ratio.atan())).collect(),
+        }
+    }
+
+    /// Looks up the slope and the aspect (in radians) of the gradient
+    /// towards the east (`p`) and the south (`q`), like
+    /// `igor_hillshading_opt1::calculate_slope_and_aspect` calculates them.
+    pub fn slope_and_aspect(&self, p: f32, q: f32) -> (f32, f32) {
+        let gradient = (p * p + q * q).sqrt();
+        let slope = self.look_up(&self.slopes, gradient / (1. + gradient));
+
+        let (abs_p, abs_q) = (p.abs(), q.abs());
+        let ratio = if abs_p.max(abs_q) > 0. {
+            abs_p.min(abs_q) / abs_p.max(abs_q)
+        } else {
+            0.
+        };
+        // the four-quadrant arctangent from the first octant's one
+        let mut angle = self.look_up(&self.arctangents, ratio);
+        if abs_q > abs_p {
+            angle = FRAC_PI_2 - angle;
+        }
+        if p < 0. {
+            angle = PI - angle;
+        }
+        if q < 0. {
+            angle = -angle;
+        }
+
+        (slope, normalize_angle(angle - FRAC_PI_2))
+    }
+
+    /// The table's angle (in radians) nearest to the position (from 0 to 1).
+    fn look_up(&self, table: &[u16], position: f32) -> f32 {
+        let index = (position * self.resolution as f32).round() as usize;
+        let value = table[index.min(self.resolution)];
+        (value as f64 / VALUES_PER_DEGREE).to_radians() as f32
+    }
+
+    /// Renders the tables as a C/C++ header with `uint16_t` arrays.
+    pub fn to_c_header(&self) -> String {
+        let guard = format!("SION_LOOKUP_TABLES_{}_H", self.resolution);
+        let mut header = String::new();
+        header.push_str("// Generated by `sion lookup-tables`.\n");
+        header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+        header.push_str("#include <stdint.h>\n\n");
+        header.push_str(&format!(
+            "#define LOOKUP_RESOLUTION_{} {}\n\n",
+            self.resolution, self.resolution
+        ));
+        for (name, comment, table) in self.described_tables() {
+            header.push_str(&format!(
+                "// {}\nstatic const uint16_t {}_lookup_{}[{}] = {{\n{}}};\n\n",
+                comment.replace('\n', "\n// "),
+                name,
+                self.resolution,
+                table.len(),
+                render_values(table)
+            ));
+        }
+        header.push_str(&format!("#endif // {}\n", guard));
+        header
+    }
+
+    /// Renders the tables as Rust `const` arrays.
+    pub fn to_rust(&self) -> String {
+        let mut source = String::new();
+        source.push_str("// Generated by `sion lookup-tables`.\n\n");
+        source.push_str(&format!(
+            "pub const LOOKUP_RESOLUTION: usize = {};\n\n",
+            self.resolution
+        ));
+        for (name, comment, table) in self.described_tables() {
+            source.push_str(&format!(
+                "/// {}\npub const {}_LOOKUP: [u16; {}] = [\n{}];\n\n",
+                comment.replace('\n', "\n/// "),
+                name.to_uppercase(),
+                table.len(),
+                render_values(table)
+            ));
+        }
+        source.truncate(source.len() - 1);
+        source
+    }
+
+    /// Writes the tables into a C/C++ header file, see `to_c_header`.
+    pub fn write_c_header(&self, file: &str) -> Result<(), SionError> {
+        write_file(file, &self.to_c_header())
+    }
+
+    /// Writes the tables into a Rust source file, see `to_rust`.
+    pub fn write_rust(&self, file: &str) -> Result<(), SionError> {
+        write_file(file, &self.to_rust())
+    }
+
+    /// The tables with their names and (multi-line) descriptions.
+    fn described_tables(&self) -> [(&str, String, &[u16]); 2] {
+        [
+            (
+                "slope",
+                format!(
+                    "The slope (in hundredths of a degree) of the gradient\n\
+                    t / (1 - t), for t = i / {}.",
+                    self.resolution
+                ),
+                &self.slopes,
+            ),
+            (
+                "arctangent",
+                format!(
+                    "atan(i / {}) (in hundredths of a degree).",
+                    self.resolution
+                ),
+                &self.arctangents,
+            ),
+        ]
+    }
+}
+
+/// Renders the values as indented lines, each value followed by a comma.
+fn render_values(values: &[u16]) -> String {
+    let mut rendered = String::new();
+    for line in values.chunks(VALUES_PER_LINE) {
+        let line: Vec<String> =
+            line.iter().map(|value| format!("{},", value)).collect();
+        rendered.push_str("    ");
+        rendered.push_str(&line.join(" "));
+        rendered.push('\n');
+    }
+    rendered
+}
+
+fn write_file(file: &str, contents: &str) -> Result<(), SionError> {
+    let cannot_write = |error| SionError::CannotWriteFile {
+        file: file.to_string(),
+        source: error,
+    };
+
+    let mut writer = BufWriter::new(File::create(file).map_err(cannot_write)?);
+    writer
+        .write_all(contents.as_bytes())
+        .map_err(cannot_write)?;
+    writer.flush().map_err(cannot_write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::difference_between_angles;
+    use crate::hillshading::igor_hillshading_opt1::calculate_slope_and_aspect;
+    use rstest::rstest;
+    use std::fs;
+
+    #[rstest]
+    #[case(64)]
+    #[case(256)]
+    #[case(1024)]
+    fn tables_reproduce_the_calculated_slopes_and_aspects(
+        #[case] resolution: usize,
+    ) {
+        let tables = LookupTables::new(resolution);
+        // the nearest entry is at most half a step away, the slopes change
+        // at most twice as fast as their positions (plus the values'
+        // rounding)
+        let tolerance = 1. / resolution as f32 + 1e-4;
+
+        for p in -40..=40 {
+            for q in -40..=40 {
+                // gradients from flat to very steep
+                let (p, q) = (
+                    p as f32 * (p as f32).abs() / 200.,
+                    q as f32 * (q as f32).abs() / 200.,
+                );
+                let (slope, aspect) = calculate_slope_and_aspect(p, q);
+                let (table_slope, table_aspect) = tables.slope_and_aspect(p, q);
+
+                assert!(
+                    (slope - table_slope).abs() <= tolerance,
+                    "slope of {}, {}",
+                    p,
+                    q
+                );
+                if slope > 0. {
+                    assert!(
+                        difference_between_angles(aspect, table_aspect).abs()
+                            <= tolerance,
+                        "aspect of {}, {}",
+                        p,
+                        q
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tables_span_the_right_angle_and_the_octant() {
+        let tables = LookupTables::new(4);
+        assert_eq!(tables.slopes, [0, 1843, 4500, 7157, 9000]);
+        assert_eq!(tables.arctangents, [0, 1404, 2657, 3687, 4500]);
+    }
+
+    #[test]
+    fn tables_are_rendered_as_c_and_rust_source() {
+        let tables = LookupTables::new(4);
+
+        let header = tables.to_c_header();
+        assert!(header.contains("#ifndef SION_LOOKUP_TABLES_4_H"));
+        assert!(header.contains(
+            "static const uint16_t slope_lookup_4[5] = {\n    \
+            0, 1843, 4500, 7157, 9000,\n};"
+        ));
+        assert!(header.contains("arctangent_lookup_4[5]"));
+        assert!(header.ends_with("#endif // SION_LOOKUP_TABLES_4_H\n"));
+
+        let source = tables.to_rust();
+        assert!(source.contains("pub const LOOKUP_RESOLUTION: usize = 4;"));
+        assert!(source.contains(
+            "pub const ARCTANGENT_LOOKUP: [u16; 5] = [\n    \
+            0, 1404, 2657, 3687, 4500,\n];\n"
+        ));
+
+        let long_lines = LookupTables::new(100).to_rust();
+        assert!(long_lines.lines().all(|line| line.len() <= 80));
+
+        fs::create_dir_all("target/debug/lookup_tables_tests").unwrap();
+        let file = "target/debug/lookup_tables_tests/lookup_tables_4.h";
+        tables.write_c_header(file).unwrap();
+        assert_eq!(fs::read_to_string(file).unwrap(), header);
+    }
+}
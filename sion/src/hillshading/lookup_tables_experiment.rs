@@ -6,9 +6,8 @@ use std::f32::consts::FRAC_PI_2;
 use std::fs::File;
 use std::io::Write;
 
-// todo: instead of using DEM data to fetch the slope and aspect values,
-//    simply generate ones for a given lookup table resolution - use the other
-//    way around - from the degree value to the original value?
+// note: see `lookup_tables` for the tables generated analytically for a given
+//    resolution (without any DEM data).
 
 #[allow(dead_code)]
 pub fn calculate_pq(dem_tile: &DemTile, x: usize, y: usize) -> (i32, i32) {
@@ -96,7 +95,7 @@ fn save_lookup_table(
     .unwrap();
 
     writeln!(file, "#include <stdint.h>").unwrap();
-    writeln!(file, "").unwrap();
+    writeln!(file).unwrap();
     writeln!(
         file,
         "int16_t {}_lookup_{}[] = {{",
@@ -216,19 +215,19 @@ fn render_lookup_table_array(
         }
     }
 
-    writeln!(file, "").unwrap();
+    writeln!(file).unwrap();
     writeln!(file, "}};").unwrap();
-    writeln!(file, "").unwrap();
+    writeln!(file).unwrap();
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::dem_tile::DemTile;
+    use crate::testing::SyntheticTerrain;
 
     #[test]
     fn calculate_lookup_tables() {
-        let dem = DemTile::from_hgt_file("tests/data/N46E006.hgt");
+        let dem = SyntheticTerrain::mountains(6, 46, 1201).build();
         let aspect_resolution = 100;
         let (slope_lookup_table, aspect_lookup_table) =
             construct_lookup_tables(&dem, aspect_resolution);
@@ -1,6 +1,12 @@
+pub mod hillshader;
+pub mod igor_hillshading_fixed;
 pub mod igor_hillshading_opt1;
 pub mod igor_hillshading_orig;
+pub mod igor_hillshading_simd;
+pub mod lambertian_hillshading;
+pub mod lookup_tables;
 mod lookup_tables_experiment;
 pub mod parameters;
+pub mod seamless;
 mod some_experimental_calculations;
 mod xas_experiment;
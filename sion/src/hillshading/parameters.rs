@@ -1,13 +1,44 @@
+use crate::dem_tile::DemTile;
+use crate::grid_spacing::{GridGeometry, GridSpacing};
+use crate::void_filling::VoidFillMethod;
+use std::borrow::Cow;
+
+#[derive(Clone, Copy)]
 pub struct HillshadingParameters {
+    /// The sun's direction (in degrees clockwise from the north).
     pub sun_azimuth: f32,
+    /// The sun's angle above the horizon (in degrees), used by the
+    /// Lambertian hillshading (the Igor method ignores it).
+    pub sun_altitude: f32,
     pub intensity: f32,
+    /// The heights' exaggeration factor, used by the Lambertian hillshading.
+    pub z_factor: f32,
+    /// The length of a degree of latitude in the heights' units, used to
+    /// calculate the grid spacing (see `grid_spacing`). For the heights in
+    /// meters this is like gdaldem's `-s 111120`.
+    pub scale: f32,
+    /// Where the DEM's cells lie, if the DEM is not a regular tile (e.g. a
+    /// padded one). By default, `GridGeometry::of_tile` is used.
+    pub grid: Option<GridGeometry>,
+    /// When set, the Lambertian hillshading combines several light sources
+    /// (ignoring `sun_azimuth`), see `MultidirectionalLighting`.
+    pub multidirectional: Option<MultidirectionalLighting>,
+    /// When set, the DEM's voids are filled (on a copy of the DEM) before
+    /// shading, otherwise the voids are shaded as if they were real heights.
+    pub void_filling: Option<VoidFillMethod>,
 }
 
 impl Default for HillshadingParameters {
     fn default() -> Self {
         Self {
             sun_azimuth: 315.0,
+            sun_altitude: 45.0,
             intensity: 1.0,
+            z_factor: 1.0,
+            scale: 111120.0,
+            grid: None,
+            multidirectional: None,
+            void_filling: None,
         }
     }
 }
@@ -18,6 +49,98 @@ impl HillshadingParameters {
         Self {
             sun_azimuth,
             intensity,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the DEM to be shaded: the DEM itself or, if void filling is
+    /// enabled and the DEM has voids, its filled copy.
+    pub fn prepare_dem<'a>(&self, dem: &'a DemTile) -> Cow<'a, DemTile> {
+        match self.void_filling {
+            Some(method) if dem.void_count() > 0 => {
+                let mut filled_dem = dem.clone();
+                filled_dem.fill_voids(method);
+                Cow::Owned(filled_dem)
+            }
+            _ => Cow::Borrowed(dem),
+        }
+    }
+
+    /// Calculates the ground distances between the DEM's cells (in the
+    /// heights' units), for each of its rows' latitude.
+    pub fn grid_spacing(&self, dem: &DemTile) -> GridSpacing {
+        let geometry = self.grid.unwrap_or_else(|| GridGeometry::of_tile(dem));
+        GridSpacing::new(&geometry, dem.size, self.scale)
+    }
+}
+
+/// The most light sources of a `MultidirectionalLighting`.
+pub const MAX_LIGHT_SOURCES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightSource {
+    /// The light's direction (in degrees clockwise from the north).
+    pub azimuth: f32,
+    /// The light's weight relative to the other light sources.
+    pub weight: f32,
+}
+
+/// Several light sources (all at the sun's altitude) whose shades are
+/// combined with aspect-dependent weights: each light's weight is scaled by
+/// the squared cosine of the angle between its azimuth and the slope's
+/// direction, so the lights shining along the slope (and across the ridges)
+/// dominate. With the default lights this is the USGS MDOW method, also used
+/// by gdaldem's `-multidirectional`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MultidirectionalLighting {
+    light_sources: [LightSource; MAX_LIGHT_SOURCES],
+    count: usize,
+}
+
+impl Default for MultidirectionalLighting {
+    /// The MDOW lights, at 225, 270, 315 and 360 degrees with equal weights.
+    fn default() -> Self {
+        let light = |azimuth| LightSource {
+            azimuth,
+            weight: 1.,
+        };
+        MultidirectionalLighting::new(&[
+            light(225.),
+            light(270.),
+            light(315.),
+            light(360.),
+        ])
+    }
+}
+
+impl MultidirectionalLighting {
+    /// Creates the lighting from the light sources.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no light sources or more than
+    /// `MAX_LIGHT_SOURCES`.
+    pub fn new(light_sources: &[LightSource]) -> Self {
+        if light_sources.is_empty() || light_sources.len() > MAX_LIGHT_SOURCES {
+            panic!(
+                "The lighting needs 1 to {} light sources",
+                MAX_LIGHT_SOURCES
+            );
         }
+
+        let mut lighting = MultidirectionalLighting {
+            light_sources: [LightSource {
+                azimuth: 0.,
+                weight: 0.,
+            }; MAX_LIGHT_SOURCES],
+            count: light_sources.len(),
+        };
+        lighting.light_sources[..light_sources.len()]
+            .copy_from_slice(light_sources);
+        lighting
+    }
+
+    pub fn light_sources(&self) -> &[LightSource] {
+        &self.light_sources[..self.count]
     }
 }
@@ -0,0 +1,475 @@
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+use crate::dem_tile_id::DemTileId;
+use crate::dem_tile_source::DemTileSource;
+use crate::elevation::DemTileSet;
+use crate::errors::SionError;
+use crate::grayscale8_bitmap::Grayscale8Bitmap;
+use crate::grid_spacing::GridGeometry;
+use crate::hillshading::hillshader::Hillshader;
+use crate::hillshading::parameters::HillshadingParameters;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// A DEM tile together with (some of) its eight neighbouring tiles, used to
+/// look up the heights just beyond the tile's edges.
+pub struct DemTileNeighbourhood<'a> {
+    /// The tiles from the north-west to the south-east one, row by row, with
+    /// the center tile in the middle.
+    tiles: [[Option<&'a DemTile>; 3]; 3],
+}
+
+impl<'a> DemTileNeighbourhood<'a> {
+    /// Creates a neighbourhood without any neighbours.
+    pub fn new(center: &'a DemTile) -> DemTileNeighbourhood<'a> {
+        let mut tiles = [[None; 3]; 3];
+        tiles[1][1] = Some(center);
+        DemTileNeighbourhood { tiles }
+    }
+
+    /// Creates the neighbourhood of the set's tile with the given ID, or
+    /// returns `None` if the set does not contain the tile.
+    pub fn from_tile_set(
+        tiles: &'a DemTileSet,
+        tile_id: &DemTileId,
+    ) -> Option<DemTileNeighbourhood<'a>> {
+        let mut neighbourhood = DemTileNeighbourhood::new(tiles.tile(tile_id)?);
+
+        for row in 0..3 {
+            for column in 0..3 {
+                if (row, column) != (1, 1) {
+                    neighbourhood.tiles[row][column] = tile_id
+                        .neighbour(column as i16 - 1, 1 - row as i16)
+                        .and_then(|neighbour_id| tiles.tile(&neighbour_id));
+                }
+            }
+        }
+
+        Some(neighbourhood)
+    }
+
+    pub fn center(&self) -> &'a DemTile {
+        self.tiles[1][1].unwrap()
+    }
+
+    /// Adds the neighbouring tile, which must have the same size as the
+    /// center tile and be one of its eight neighbours.
+    pub fn add_neighbour(
+        &mut self,
+        tile: &'a DemTile,
+    ) -> Result<(), SionError> {
+        let center = self.center();
+        if tile.size != center.size {
+            return Err(SionError::TileSizeMismatch {
+                lon: tile.lon,
+                lat: tile.lat,
+                size: tile.size,
+                expected_size: center.size,
+            });
+        }
+
+        let column = (-1..=1)
+            .find(|offset| DemTileId::wrap_lon(center.lon + offset) == tile.lon)
+            .map(|offset| (offset + 1) as usize);
+        let row = (-1..=1)
+            .find(|offset| center.lat - offset == tile.lat)
+            .map(|offset| (offset + 1) as usize);
+
+        match (row, column) {
+            (Some(row), Some(column)) if (row, column) != (1, 1) => {
+                self.tiles[row][column] = Some(tile);
+                Ok(())
+            }
+            _ => Err(SionError::TileNotAdjacent {
+                lon: tile.lon,
+                lat: tile.lat,
+                center_lon: center.lon,
+                center_lat: center.lat,
+            }),
+        }
+    }
+
+    /// Returns the height at the center tile's cell coordinates, which can
+    /// extend one cell beyond the center tile (`-1..=size`). The heights
+    /// beyond the edges are taken from the neighbouring tiles (skipping the
+    /// shared edges, see `DemTile::shares_edges`), or extrapolated linearly
+    /// from the center tile when the neighbour is missing (a single-cell
+    /// center tile's height is just repeated).
+    pub fn height_at(&self, x: isize, y: isize) -> i16 {
+        let size = self.center().size as isize;
+        let last = size - 1;
+        let stride = if self.center().shares_edges() {
+            last
+        } else {
+            size
+        };
+        let tile_offset = |cell: isize| match cell {
+            cell if cell < 0 => -1,
+            cell if cell > last => 1,
+            _ => 0,
+        };
+        let (offset_x, offset_y) = (tile_offset(x), tile_offset(y));
+
+        match self.tiles[(offset_y + 1) as usize][(offset_x + 1) as usize] {
+            Some(tile) => {
+                let local_x = (x - offset_x * stride) as usize;
+                let local_y = (y - offset_y * stride) as usize;
+                tile.height_at_index(local_y * tile.size + local_x)
+            }
+            None => {
+                // h[-1] = 2 h[0] - h[1], vertically towards the center
+                // tile's row (which may still be in a neighbouring tile) or
+                // horizontally within the center tile's rows
+                let (step_x, step_y) = if offset_y != 0 {
+                    (0, offset_y)
+                } else {
+                    (offset_x, 0)
+                };
+                let edge = self.height_at(x - step_x, y - step_y);
+                if size < 2 {
+                    return edge;
+                }
+                let inner = self.height_at(x - 2 * step_x, y - 2 * step_y);
+                if edge == NO_DATA_HEIGHT || inner == NO_DATA_HEIGHT {
+                    NO_DATA_HEIGHT
+                } else {
+                    let min_height = NO_DATA_HEIGHT as i32 + 1;
+                    (2 * edge as i32 - inner as i32)
+                        .clamp(min_height, i16::MAX as i32)
+                        as i16
+                }
+            }
+        }
+    }
+
+    /// Creates a tile one cell larger on each side than the center tile,
+    /// with the extra cells taken from the neighbourhood.
+    pub fn padded_tile(&self) -> DemTile {
+        let center = self.center();
+        let size = center.size as isize;
+
+        let heights: Vec<i16> = (-1..=size)
+            .flat_map(|y| (-1..=size).map(move |x| self.height_at(x, y)))
+            .collect();
+
+        DemTile::from_heights(center.lon, center.lat, center.size + 2, &heights)
+    }
+}
+
+/// Hillshades the neighbourhood's center tile, including its edge rows and
+/// columns, using the neighbouring tiles' heights beyond the edges. The
+/// adjacent tiles' hillshades therefore join without any seams.
+///
+/// If the void filling is enabled, the voids are filled in each of the
+/// tiles separately (just like when the tiles are shaded on their own).
+pub fn hillshade_seamless(
+    neighbourhood: &DemTileNeighbourhood,
+    hillshader: &dyn Hillshader,
+    parameters: &HillshadingParameters,
+    bitmap: &mut Grayscale8Bitmap,
+) {
+    let size = neighbourhood.center().size;
+    if bitmap.width as usize != size || bitmap.height as usize != size {
+        panic!("bitmap size does not match DEM size");
+    }
+
+    let prepared_tiles: Vec<Vec<Option<Cow<DemTile>>>> = neighbourhood
+        .tiles
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|tile| tile.map(|tile| parameters.prepare_dem(tile)))
+                .collect()
+        })
+        .collect();
+    let mut prepared = neighbourhood.tiles;
+    for (row, prepared_row) in prepared.iter_mut().zip(&prepared_tiles) {
+        for (tile, prepared_tile) in row.iter_mut().zip(prepared_row) {
+            *tile = prepared_tile.as_deref();
+        }
+    }
+
+    let padded_tile = DemTileNeighbourhood { tiles: prepared }.padded_tile();
+    let mut padded_bitmap =
+        Grayscale8Bitmap::new(size as u16 + 2, size as u16 + 2);
+    // the padded tile starts a cell north-west of the center tile
+    let grid = parameters
+        .grid
+        .unwrap_or_else(|| GridGeometry::of_tile(neighbourhood.center()));
+    let padded_parameters = HillshadingParameters {
+        grid: Some(grid.padded(1)),
+        void_filling: None,
+        ..*parameters
+    };
+    hillshader.hillshade(&padded_tile, &padded_parameters, &mut padded_bitmap);
+
+    let shaded = padded_bitmap.extract(1, 1, size as u16, size as u16);
+    bitmap.data_mut().copy_from_slice(shaded.data());
+}
+
+/// Loads the tile and its neighbours from the source and hillshades the
+/// tile seamlessly (see `hillshade_seamless`). The neighbours missing from
+/// the source are extrapolated, the other errors are reported.
+pub fn hillshade_from_source(
+    source: &dyn DemTileSource,
+    tile_id: &DemTileId,
+    hillshader: &dyn Hillshader,
+    parameters: &HillshadingParameters,
+    bitmap: &mut Grayscale8Bitmap,
+) -> Result<(), SionError> {
+    let center = source.tile(tile_id)?;
+
+    let mut neighbours: Vec<Arc<DemTile>> = Vec::new();
+    for lat_offset in -1..=1 {
+        for lon_offset in -1..=1 {
+            if (lon_offset, lat_offset) == (0, 0) {
+                continue;
+            }
+
+            // there are no tiles beyond the poles
+            let Some(neighbour_id) = tile_id.neighbour(lon_offset, lat_offset)
+            else {
+                continue;
+            };
+            match source.tile(&neighbour_id) {
+                Ok(neighbour) => neighbours.push(neighbour),
+                Err(SionError::TileNotFound(_)) => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    let mut neighbourhood = DemTileNeighbourhood::new(&center);
+    for neighbour in &neighbours {
+        neighbourhood.add_neighbour(neighbour)?;
+    }
+
+    hillshade_seamless(&neighbourhood, hillshader, parameters, bitmap);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dem_tile_source::InMemoryTileSource;
+    use crate::hillshading::hillshader::FnHillshader;
+    use crate::hillshading::igor_hillshading_opt1;
+
+    fn opt1() -> FnHillshader {
+        FnHillshader::new("igor_opt1", igor_hillshading_opt1::hillshade)
+    }
+
+    /// Creates a tile with the heights calculated from the global (i.e.
+    /// across tiles) coordinates in degrees.
+    fn hills_tile(lon: i16, lat: i16, size: usize) -> DemTile {
+        let intervals = (size - 1) as f32;
+        let heights: Vec<i16> = (0..size * size)
+            .map(|index| {
+                let x = lon as f32 + (index % size) as f32 / intervals;
+                let y = lat as f32 + 1. - (index / size) as f32 / intervals;
+                (1000. + 500. * (x * 11.).sin() * (y * 13.).cos()) as i16
+            })
+            .collect();
+        DemTile::from_heights(lon, lat, size, &heights)
+    }
+
+    #[test]
+    fn heights_beyond_the_edges() {
+        let center =
+            DemTile::from_heights(7, 46, 3, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let east =
+            DemTile::from_heights(8, 46, 3, &[3, 20, 30, 6, 50, 60, 9, 80, 90]);
+        let mut neighbourhood = DemTileNeighbourhood::new(&center);
+        neighbourhood.add_neighbour(&east).unwrap();
+
+        assert_eq!(neighbourhood.height_at(1, 1), 5);
+        // the east neighbour's first column is shared with the center tile
+        assert_eq!(neighbourhood.height_at(3, 0), 20);
+        assert_eq!(neighbourhood.height_at(3, 2), 80);
+        // the missing neighbours' heights are extrapolated
+        assert_eq!(neighbourhood.height_at(-1, 1), 3);
+        assert_eq!(neighbourhood.height_at(1, -1), -1);
+        assert_eq!(neighbourhood.height_at(3, 3), 110);
+
+        let padded_tile = neighbourhood.padded_tile();
+        assert_eq!(padded_tile.size, 5);
+        assert_eq!(padded_tile.height_at(2, 2), 5);
+        assert_eq!(padded_tile.height_at(4, 1), 20);
+    }
+
+    #[test]
+    fn neighbours_must_match_the_center_tile() {
+        let center = DemTile::from_heights(179, 46, 2, &[1, 2, 3, 4]);
+        let distant: Vec<DemTile> = [(179, 46), (177, 46), (179, 48)]
+            .iter()
+            .map(|&(lon, lat)| DemTile::from_heights(lon, lat, 2, &[0; 4]))
+            .collect();
+        let mut neighbourhood = DemTileNeighbourhood::new(&center);
+
+        // the neighbours wrap around the antimeridian
+        let east = DemTile::from_heights(-180, 46, 2, &[2, 5, 4, 6]);
+        neighbourhood.add_neighbour(&east).unwrap();
+        assert_eq!(neighbourhood.height_at(2, 0), 5);
+
+        let smaller = DemTile::from_heights(178, 46, 1, &[1]);
+        assert!(matches!(
+            neighbourhood.add_neighbour(&smaller),
+            Err(SionError::TileSizeMismatch { .. })
+        ));
+
+        for tile in &distant {
+            match neighbourhood.add_neighbour(tile) {
+                Err(error @ SionError::TileNotAdjacent { .. }) => {
+                    assert_eq!(
+                        error.to_string(),
+                        format!(
+                            "The tile at {}, {} is not a neighbour of the \
+                            tile at 179, 46",
+                            tile.lon, tile.lat
+                        )
+                    )
+                }
+                _ => panic!("Expected a TileNotAdjacent error"),
+            }
+        }
+    }
+
+    #[test]
+    fn single_cell_tiles_are_padded_with_their_heights() {
+        let center = DemTile::from_heights(7, 46, 1, &[100]);
+        let east = DemTile::from_heights(8, 46, 1, &[200]);
+
+        let mut neighbourhood = DemTileNeighbourhood::new(&center);
+        assert_eq!(neighbourhood.padded_tile().heights(), [100; 9]);
+
+        neighbourhood.add_neighbour(&east).unwrap();
+        assert_eq!(neighbourhood.height_at(1, 0), 200);
+        assert_eq!(neighbourhood.height_at(1, 1), 200);
+        assert_eq!(neighbourhood.height_at(-1, -1), 100);
+
+        let mut bitmap = Grayscale8Bitmap::new(1, 1);
+        hillshade_seamless(
+            &neighbourhood,
+            &opt1(),
+            &HillshadingParameters::default(),
+            &mut bitmap,
+        );
+    }
+
+    #[test]
+    fn neighbours_without_shared_edges() {
+        let grid = |lon| GridGeometry::Geographic {
+            north_lat: 47. - 0.5 / 3.,
+            west_lon: lon + 0.5 / 3.,
+            cell_size: 1. / 3.,
+        };
+        let center =
+            DemTile::from_heights(7, 46, 3, &[1, 2, 3, 4, 5, 6, 7, 8, 9])
+                .with_grid_geometry(grid(7.));
+        let east = DemTile::from_heights(
+            8,
+            46,
+            3,
+            &[10, 20, 30, 40, 50, 60, 70, 80, 90],
+        )
+        .with_grid_geometry(grid(8.));
+        let mut neighbourhood = DemTileNeighbourhood::new(&center);
+        neighbourhood.add_neighbour(&east).unwrap();
+
+        // the east neighbour's first column is just beyond the center tile
+        assert_eq!(neighbourhood.height_at(3, 0), 10);
+        assert_eq!(neighbourhood.height_at(3, 2), 70);
+        assert_eq!(neighbourhood.height_at(2, 1), 6);
+    }
+
+    #[test]
+    fn adjacent_tiles_join_without_seams() {
+        let mut tiles = DemTileSet::new();
+        for lon in 6..=9 {
+            for lat in 45..=47 {
+                tiles.insert(hills_tile(lon, lat, 31)).unwrap();
+            }
+        }
+
+        let shade = |lon, lat| {
+            let neighbourhood = DemTileNeighbourhood::from_tile_set(
+                &tiles,
+                &DemTileId::new(lon, lat),
+            )
+            .unwrap();
+            let mut bitmap = Grayscale8Bitmap::new(31, 31);
+            hillshade_seamless(
+                &neighbourhood,
+                &opt1(),
+                &HillshadingParameters::default(),
+                &mut bitmap,
+            );
+            bitmap
+        };
+
+        let west = shade(7, 46);
+        let east = shade(8, 46);
+        let north = shade(7, 47);
+
+        for i in 0..31 {
+            assert_eq!(west.get_pixel(30, i), east.get_pixel(0, i));
+            assert_eq!(north.get_pixel(i, 30), west.get_pixel(i, 0));
+        }
+
+        // the seamless hillshade matches the plain one inside the tile
+        let mut plain = Grayscale8Bitmap::new(31, 31);
+        igor_hillshading_opt1::hillshade(
+            tiles.tile(&DemTileId::new(7, 46)).unwrap(),
+            &HillshadingParameters::default(),
+            &mut plain,
+        );
+        for y in 1..30 {
+            for x in 1..30 {
+                assert_eq!(west.get_pixel(x, y), plain.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn hillshade_tile_from_source() {
+        let mut source = InMemoryTileSource::new();
+        let mut tiles = DemTileSet::new();
+        for (lon, lat) in [(7, 46), (8, 46), (7, 45), (6, 47)] {
+            source.insert(hills_tile(lon, lat, 21)).unwrap();
+            tiles.insert(hills_tile(lon, lat, 21)).unwrap();
+        }
+
+        let mut from_source = Grayscale8Bitmap::new(21, 21);
+        hillshade_from_source(
+            &source,
+            &DemTileId::new(7, 46),
+            &opt1(),
+            &HillshadingParameters::default(),
+            &mut from_source,
+        )
+        .unwrap();
+
+        let mut from_set = Grayscale8Bitmap::new(21, 21);
+        hillshade_seamless(
+            &DemTileNeighbourhood::from_tile_set(
+                &tiles,
+                &DemTileId::new(7, 46),
+            )
+            .unwrap(),
+            &opt1(),
+            &HillshadingParameters::default(),
+            &mut from_set,
+        );
+        assert_eq!(from_source.data(), from_set.data());
+
+        assert!(matches!(
+            hillshade_from_source(
+                &source,
+                &DemTileId::new(9, 46),
+                &opt1(),
+                &HillshadingParameters::default(),
+                &mut from_source,
+            ),
+            Err(SionError::TileNotFound(_))
+        ));
+    }
+}
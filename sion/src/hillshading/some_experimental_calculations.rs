@@ -129,8 +129,8 @@ pub fn hillshading_1(sun_azimuth: i16, slope: i16, aspect: i16) -> i16 {
     let aspect_diff = diff_between_angles_deg(aspect, sun_azimuth);
     let aspect_light_intensity = (180. - aspect_diff as f32) / 180.;
     let light_intensity = slope_light_intensity * aspect_light_intensity;
-    let color = (255. * light_intensity) as i16;
-    color
+
+    (255. * light_intensity) as i16
 }
 
 #[allow(dead_code)]
@@ -140,8 +140,8 @@ pub fn hillshading_2(sun_azimuth: i16, slope: i16, aspect: i16) -> i16 {
     let aspect_light_intensity = (180 - aspect_diff as i32) * 255 / 180;
     let light_intensity =
         (slope_light_intensity * aspect_light_intensity) / 255;
-    let color = light_intensity as i16;
-    color
+
+    light_intensity as i16
 }
 
 #[allow(dead_code)]
@@ -150,8 +150,8 @@ pub fn hillshading_3(sun_azimuth: i16, slope: i16, aspect: i16) -> i16 {
     let aspect_diff = diff_between_angles_deg(aspect, sun_azimuth);
     let aspect_light_intensity = ((180 - aspect_diff as i32) << 8) / 180;
     let light_intensity = (slope_light_intensity * aspect_light_intensity) >> 8;
-    let color = light_intensity as i16;
-    color
+
+    light_intensity as i16
 }
 
 #[cfg(test)]
@@ -277,6 +277,7 @@ mod tests {
         );
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn assert_calculations(
         elevations: &Matrix3x3,
         d: i16,
@@ -287,7 +288,7 @@ mod tests {
         expected_aspect: f32,
         expected_hillshading_color: i16,
     ) {
-        let (p, q) = calculate_pq_1(d, &elevations);
+        let (p, q) = calculate_pq_1(d, elevations);
 
         assert_eq!(p, expected_p);
         assert_eq!(q, expected_q);
@@ -297,7 +298,7 @@ mod tests {
         let aspect = calculate_aspect_1(p, q);
         assert_eq_approx(aspect, expected_aspect, 0.001);
 
-        let (p_prime, q_prime) = calculate_pq_2(&elevations);
+        let (p_prime, q_prime) = calculate_pq_2(elevations);
         assert_eq!(
             calculate_slope_2(d, p_prime, q_prime),
             expected_slope,
@@ -325,6 +326,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::excessive_precision)]
     fn test_icebreaker() {
         fn latitude_to_world_cell_y_and_fraction(
             lat: f32,
@@ -339,7 +341,7 @@ mod tests {
             let mut cell_fraction =
                 cell_with_fraction - (cell_with_fraction as i16) as f32;
             if local_cell_y < 0 {
-                cell_fraction = 1. + cell_fraction;
+                cell_fraction += 1.;
             }
             (world_cell_y, cell_fraction)
         }
@@ -350,12 +352,12 @@ mod tests {
         ) -> (i16, i16) {
             let mut lat = world_cell_y / (tile_size as i32);
             if world_cell_y < 0 {
-                lat = lat - 1;
+                lat -= 1;
             }
 
             let mut modulo = world_cell_y % (tile_size as i32);
             if modulo < 0 {
-                modulo = (tile_size as i32) + modulo;
+                modulo += tile_size as i32;
             }
 
             let local_cell_y = tile_size - 1 - (modulo as i16);
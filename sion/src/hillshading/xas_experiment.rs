@@ -23,14 +23,14 @@ impl XasTile {
         let byte_offset = ((y as usize) * (self.size) + (x as usize)) << 1;
         let aspect_int = aspect.round() as u16;
         let slope_int = slope.round() as u16;
-        let encoded_value = slope_int << 7 | aspect_int;
+        let encoded_value = (slope_int << 7) | aspect_int;
         self.data[byte_offset] = (encoded_value >> 8) as u8;
         self.data[byte_offset + 1] = (encoded_value & 0xff) as u8;
     }
 
     pub fn get_aspect_and_slope(&self, x: u16, y: u16) -> (f32, f32) {
         let byte_offset = ((y as usize) * (self.size) + (x as usize)) << 1;
-        let encoded_value = (self.data[byte_offset] as u16) << 8
+        let encoded_value = ((self.data[byte_offset] as u16) << 8)
             | (self.data[byte_offset + 1] as u16);
         let aspect = (encoded_value & 0x7f) as f32;
         let slope = (encoded_value >> 7) as f32;
@@ -41,17 +41,16 @@ impl XasTile {
 #[cfg(test)]
 mod tests {
     use crate::consts::EARTH_CIRCUMFERENCE_METERS;
-    use crate::dem_tile::DemTile;
     use crate::hillshading::igor_hillshading_orig::{
         calculate_pq, calculate_slope_and_aspect,
     };
     use crate::hillshading::xas_experiment::XasTile;
-    use crate::testing::assert_eq_approx;
+    use crate::testing::{assert_eq_approx, SyntheticTerrain};
     use crate::trig::deg_to_rad;
 
     #[test]
     fn xas_experiment() {
-        let dem = DemTile::from_hgt_file("tests/data/N46E006.hgt");
+        let dem = SyntheticTerrain::mountains(6, 46, 1201).build();
 
         let mut xas_tile = XasTile::new(dem.size);
 
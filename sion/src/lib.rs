@@ -1,16 +1,30 @@
 #![deny(warnings)]
 
+pub mod ascii_grid;
 pub mod consts;
 pub mod dem_tile;
+pub mod dem_tile_cache;
+pub mod dem_tile_id;
+pub mod dem_tile_source;
+pub mod elevation;
+pub mod elevation_profile;
 pub mod errors;
 pub mod geo;
+pub mod geotiff;
 pub mod grayscale8_bitmap;
+pub mod grid_spacing;
+pub mod heightmap_png;
+pub mod hgt_zip;
 pub mod hillshading;
 pub mod maxx_sim;
 pub mod mono_bitmap;
 pub mod proj;
 pub mod raster16;
+pub mod resampling;
 pub mod slopes;
+pub mod terrain_rgb;
 pub mod testing;
 pub mod trig;
+pub mod void_filling;
 pub mod water_bodies;
+pub mod web_tile_id;
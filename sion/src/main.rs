@@ -1,8 +1,14 @@
 #![deny(warnings)]
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use rayon::ThreadPoolBuilder;
+use sion::dem_tile::DemTile;
+use sion::dem_tile_id::DemTileId;
+use sion::grayscale8_bitmap::Grayscale8Bitmap;
+use sion::hillshading::hillshader::HillshaderRegistry;
+use sion::hillshading::lookup_tables::LookupTables;
+use sion::hillshading::parameters::HillshadingParameters;
 use sion::water_bodies::command::generate_water_bodies_tile;
-use sion::water_bodies::dem_tile_id::DemTileId;
 
 #[derive(Parser)]
 #[command(name = "water-bodies")]
@@ -13,7 +19,50 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    GenerateTile { tile_id: DemTileId },
+    GenerateTile {
+        tile_id: DemTileId,
+    },
+    /// Hillshades an HGT or XTH file into a PNG file.
+    Hillshade {
+        dem_file: String,
+        png_file: String,
+        /// The hillshading algorithm (see `list-hillshaders`).
+        #[arg(long, default_value = "igor_opt1")]
+        algorithm: String,
+        #[arg(long, default_value_t = 315.)]
+        sun_azimuth: f32,
+        #[arg(long, default_value_t = 45.)]
+        sun_altitude: f32,
+        #[arg(long, default_value_t = 1.)]
+        intensity: f32,
+        #[arg(long, default_value_t = 1.)]
+        z_factor: f32,
+        /// The length of a degree in the heights' units (meters).
+        #[arg(long, default_value_t = 111120.)]
+        scale: f32,
+        /// The number of threads shading the DEM (0 for one per CPU core).
+        #[arg(long, default_value_t = 0)]
+        threads: usize,
+    },
+    /// Lists the available hillshading algorithms.
+    ListHillshaders,
+    /// Generates the slope and aspect lookup tables as source code.
+    LookupTables {
+        output_file: String,
+        /// The number of steps each table is divided into.
+        #[arg(long, default_value_t = 256)]
+        resolution: usize,
+        #[arg(long, value_enum, default_value_t = LookupTablesFormat::C)]
+        format: LookupTablesFormat,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LookupTablesFormat {
+    /// A C/C++ header.
+    C,
+    /// Rust `const` arrays.
+    Rust,
 }
 
 fn main() {
@@ -28,7 +77,97 @@ fn main() {
                 }
             }
         }
+        Commands::Hillshade {
+            dem_file,
+            png_file,
+            algorithm,
+            sun_azimuth,
+            sun_altitude,
+            intensity,
+            z_factor,
+            scale,
+            threads,
+        } => {
+            let parameters = HillshadingParameters {
+                sun_azimuth: *sun_azimuth,
+                sun_altitude: *sun_altitude,
+                intensity: *intensity,
+                z_factor: *z_factor,
+                scale: *scale,
+                ..HillshadingParameters::default()
+            };
+            match hillshade(
+                dem_file,
+                png_file,
+                algorithm,
+                &parameters,
+                *threads,
+            ) {
+                Ok(_) => println!("Hillshade written to {}.", png_file),
+                Err(e) => eprintln!("Error hillshading: {}", e),
+            }
+        }
+        Commands::ListHillshaders => {
+            for name in HillshaderRegistry::new().names() {
+                println!("{}", name);
+            }
+        }
+        Commands::LookupTables {
+            output_file,
+            resolution,
+            format,
+        } => {
+            if *resolution == 0 {
+                eprintln!("Error generating lookup tables: zero resolution");
+                return;
+            }
+
+            let tables = LookupTables::new(*resolution);
+            let result = match format {
+                LookupTablesFormat::C => tables.write_c_header(output_file),
+                LookupTablesFormat::Rust => tables.write_rust(output_file),
+            };
+            match result {
+                Ok(_) => println!("Lookup tables written to {}.", output_file),
+                Err(e) => eprintln!("Error generating lookup tables: {}", e),
+            }
+        }
+    }
+}
+
+fn hillshade(
+    dem_file: &str,
+    png_file: &str,
+    algorithm: &str,
+    parameters: &HillshadingParameters,
+    threads: usize,
+) -> Result<(), String> {
+    let registry = HillshaderRegistry::new();
+    let hillshader = registry.get(algorithm).ok_or_else(|| {
+        format!(
+            "Unknown hillshading algorithm '{}' (available: {})",
+            algorithm,
+            registry.names().join(", ")
+        )
+    })?;
+
+    let dem = if dem_file.to_lowercase().ends_with(".xth") {
+        DemTile::from_xth_file(dem_file)
+    } else {
+        DemTile::from_hgt_file(dem_file)
     }
+    .map_err(|e| e.to_string())?;
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut bitmap = Grayscale8Bitmap::new(dem.size as u16, dem.size as u16);
+    bitmap.data_mut().fill(255);
+    hillshader.hillshade_parallel(&dem, parameters, &mut bitmap, &pool);
+
+    bitmap.write_to_png(png_file).map_err(|e| e.to_string())
 }
 
 // todo: profile the code (maybe using http://www.codersnotes.com/sleepy/
@@ -51,7 +51,6 @@ mod tests {
     use super::*;
     use crate::maxx_sim::types::GlobalCell;
 
-
     #[test]
     fn test_cell_keys_1() {
         let cell_key = CellKey::from_cell_coords(
@@ -90,4 +89,4 @@ mod tests {
         assert_eq!(tile_lon_cell.value, -1419);
         assert_eq!(tile_lat_cell.value, -180);
     }
-}
\ No newline at end of file
+}
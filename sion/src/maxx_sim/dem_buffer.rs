@@ -410,40 +410,38 @@ impl DemBuffer {
                             block_move.block_height,
                         );
                     }
-                } else {
-                    if block_move.dest_y0 == 0 {
-                        // v
-                        self.update_buffer_area(
-                            0,
-                            0,
-                            block_move.dest_x0,
-                            block_move.block_height,
-                        );
+                } else if block_move.dest_y0 == 0 {
+                    // v
+                    self.update_buffer_area(
+                        0,
+                        0,
+                        block_move.dest_x0,
+                        block_move.block_height,
+                    );
 
-                        // h
-                        self.update_buffer_area(
-                            0,
-                            block_move.block_height,
-                            self.buffer_width,
-                            self.buffer_height - block_move.block_height,
-                        );
-                    } else {
-                        // h
-                        self.update_buffer_area(
-                            0,
-                            0,
-                            self.buffer_width,
-                            block_move.dest_y0,
-                        );
+                    // h
+                    self.update_buffer_area(
+                        0,
+                        block_move.block_height,
+                        self.buffer_width,
+                        self.buffer_height - block_move.block_height,
+                    );
+                } else {
+                    // h
+                    self.update_buffer_area(
+                        0,
+                        0,
+                        self.buffer_width,
+                        block_move.dest_y0,
+                    );
 
-                        // v
-                        self.update_buffer_area(
-                            0,
-                            block_move.dest_y0,
-                            block_move.dest_x0,
-                            block_move.block_height,
-                        );
-                    }
+                    // v
+                    self.update_buffer_area(
+                        0,
+                        block_move.dest_y0,
+                        block_move.dest_x0,
+                        block_move.block_height,
+                    );
                 }
             }
             None => {
@@ -598,18 +596,16 @@ impl DemBuffer {
 
                 if buffer_x == self.buffer_width / 2
                     && buffer_y == self.buffer_height / 2
+                    && (dem_lon_global_cell != self.center_global_cell_lon
+                        || dem_lat_global_cell != self.center_global_cell_lat)
                 {
-                    if dem_lon_global_cell != self.center_global_cell_lon
-                        || dem_lat_global_cell != self.center_global_cell_lat
-                    {
-                        panic!(
-                            "Bug: Center cell ({}, {}) does not match loaded DEM cell ({}, {})",
-                            self.center_global_cell_lon.value,
-                            self.center_global_cell_lat.value,
-                            dem_lon_global_cell.value,
-                            dem_lat_global_cell.value,
-                        );
-                    }
+                    panic!(
+                        "Bug: Center cell ({}, {}) does not match loaded DEM cell ({}, {})",
+                        self.center_global_cell_lon.value,
+                        self.center_global_cell_lat.value,
+                        dem_lon_global_cell.value,
+                        dem_lat_global_cell.value,
+                    );
                 }
 
                 let cell_key = CellKey::from_cell_coords(
@@ -3,7 +3,9 @@ use std::f32::consts::PI;
 use std::fmt;
 use std::ops::{Add, Sub, SubAssign};
 
+#[allow(dead_code)]
 const EARTH_RADIUS_METERS: f32 = 6378137.0;
+#[allow(dead_code)]
 const EARTH_CIRCUMFERENCE_METERS: f32 = 2.0 * PI * EARTH_RADIUS_METERS;
 
 pub const GRID_UNITS_PER_DEM_CELL_BITS: i32 = 8;
@@ -300,6 +302,7 @@ impl PartialEq for TileKey {
     }
 }
 
+#[allow(dead_code)]
 pub fn calculate_pixel_size_in_grid_units(
     latitude_rad: f32,
     zoom_meters_per_pixel: f32,
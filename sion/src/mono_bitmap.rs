@@ -94,8 +94,8 @@ mod tests {
     #[test]
     fn pixels_are_off_by_default() {
         let bitmap = MonoBitmap::new(10, 15);
-        assert_eq!(bitmap.get_pixel(0, 0), false);
-        assert_eq!(bitmap.get_pixel(4, 5), false);
+        assert!(!bitmap.get_pixel(0, 0));
+        assert!(!bitmap.get_pixel(4, 5));
     }
 
     /// Pixels can be set/reset and then retrieved.
@@ -103,9 +103,9 @@ mod tests {
     fn set_and_get_pixel() {
         let mut bitmap = MonoBitmap::new(10, 15);
         bitmap.set_pixel(3, 4, true);
-        assert_eq!(bitmap.get_pixel(3, 4), true);
+        assert!(bitmap.get_pixel(3, 4));
         bitmap.set_pixel(3, 4, false);
-        assert_eq!(bitmap.get_pixel(3, 4), false);
+        assert!(!bitmap.get_pixel(3, 4));
     }
 
     /// The bitmap can be written to a PNG file.
@@ -1,7 +1,9 @@
 use crate::consts::{DPI, EARTH_RADIUS_METERS, INCHES_PER_METER};
 use std::f32::consts::FRAC_PI_4;
 
+#[allow(clippy::excessive_precision)]
 pub const MIN_LAT: f32 = -1.48442222974533;
+#[allow(clippy::excessive_precision)]
 pub const MAX_LAT: f32 = 1.48442222974533;
 
 pub fn proj_scale_factor(map_scale: f32) -> f32 {
@@ -14,9 +16,8 @@ pub fn web_mercator_proj(
     lat: f32,
     proj_scale_factor: f32,
 ) -> (f32, f32) {
-    if lat < MIN_LAT || lat > MAX_LAT {
+    if !(MIN_LAT..=MAX_LAT).contains(&lat) {
         panic!("Latitude out of bounds: {}", lat);
-    } else {
     }
 
     let x = lon * proj_scale_factor;
@@ -31,6 +32,7 @@ mod tests {
     use crate::trig::deg_to_rad;
 
     #[test]
+    #[allow(clippy::excessive_precision)]
     fn mercator_projection() {
         let scale_factor = super::proj_scale_factor(1.);
         let (x, y) = super::web_mercator_proj(0., 0., scale_factor);
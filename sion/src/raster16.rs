@@ -1,4 +1,5 @@
-use image::{GrayImage, Luma};
+use crate::heightmap_png::Gray16Image;
+use image::Luma;
 
 /// Represents a 16-bit raster.
 #[derive(Debug)]
@@ -38,7 +39,7 @@ impl Raster16 {
         self.data[index] = value;
     }
 
-    /// Writes the 16-bit raster to a PNG file.
+    /// Writes the raster to a 16-bit grayscale PNG file.
     ///
     /// # Arguments
     ///
@@ -47,14 +48,39 @@ impl Raster16 {
         &self,
         file_path: &str,
     ) -> Result<(), image::ImageError> {
-        let mut img = GrayImage::new(self.width.into(), self.height.into());
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let pixel_value = self.get_pixel(x, y);
-                img.put_pixel(x.into(), y.into(), Luma([pixel_value as u8]));
-            }
+        self.to_image().save(file_path)
+    }
+
+    /// Reads the raster from a PNG file. 8-bit images are widened to 16 bits
+    /// (multiplying their values by 257).
+    pub fn from_png(file_path: &str) -> Result<Raster16, image::ImageError> {
+        Ok(Raster16::from_image(&image::open(file_path)?.into_luma16()))
+    }
+
+    /// Converts the raster into a 16-bit grayscale image.
+    pub fn to_image(&self) -> Gray16Image {
+        Gray16Image::from_fn(self.width.into(), self.height.into(), |x, y| {
+            Luma([self.get_pixel(x as u16, y as u16)])
+        })
+    }
+
+    /// Creates a raster from a 16-bit grayscale image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is wider or higher than `u16::MAX` pixels.
+    pub fn from_image(image: &Gray16Image) -> Raster16 {
+        let (Ok(width), Ok(height)) =
+            (u16::try_from(image.width()), u16::try_from(image.height()))
+        else {
+            panic!("The image is too large for a 16-bit raster");
+        };
+
+        Raster16 {
+            width,
+            height,
+            data: image.as_raw().clone().into_boxed_slice(),
         }
-        img.save(file_path)
     }
 }
 
@@ -112,4 +138,22 @@ mod tests {
             .write_to_png("target/debug/test-raster16.png")
             .unwrap();
     }
+
+    /// The PNG file keeps the full 16-bit values.
+    #[test]
+    fn write_and_read_png_without_losing_precision() {
+        let mut bitmap = Raster16::new(3, 2);
+        bitmap.set_pixel(0, 0, 256);
+        bitmap.set_pixel(2, 0, u16::MAX);
+        bitmap.set_pixel(1, 1, 1000);
+        bitmap
+            .write_to_png("target/debug/test-raster16-precision.png")
+            .unwrap();
+
+        let read_bitmap =
+            Raster16::from_png("target/debug/test-raster16-precision.png")
+                .unwrap();
+        assert_eq!((read_bitmap.width, read_bitmap.height), (3, 2));
+        assert_eq!(read_bitmap.data, bitmap.data);
+    }
 }
@@ -0,0 +1,324 @@
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+
+/// The method used to calculate the resampled tile's heights.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResamplingMethod {
+    /// Takes the height of the nearest source cell.
+    Nearest,
+    /// Interpolates linearly between the 2x2 surrounding source cells.
+    Bilinear,
+    /// Catmull-Rom interpolation of the 4x4 surrounding source cells.
+    Bicubic,
+    /// Averages the source cells covered by the resampled cell, weighted by
+    /// the covered area. Best suited for downsampling.
+    AreaAverage,
+}
+
+impl DemTile {
+    /// Resamples the tile to a new size (e.g. from 3601 to 1800 cells).
+    ///
+    /// The resampled tile's edges are calculated just from the source tile's
+    /// edges, so the neighbouring resampled tiles still line up (for the
+    /// tiles following the HGT convention of sharing the edge rows and
+    /// columns, the resampled ones share them too).
+    ///
+    /// A resampled cell is a void if any of the source cells it is
+    /// calculated from is a void, except for the area average which only
+    /// averages the valid cells.
+    pub fn resample(
+        &self,
+        new_size: usize,
+        method: ResamplingMethod,
+    ) -> DemTile {
+        if new_size < 2 {
+            panic!("The resampled tile must have at least 2 cells per side");
+        }
+
+        let weights: Vec<Vec<(usize, f32)>> = (0..new_size)
+            .map(|index| axis_weights(index, self.size, new_size, method))
+            .collect();
+
+        let mut heights = Vec::with_capacity(new_size * new_size);
+        for y_weights in &weights {
+            for x_weights in &weights {
+                heights.push(self.weighted_height(
+                    y_weights,
+                    x_weights,
+                    method == ResamplingMethod::AreaAverage,
+                ));
+            }
+        }
+
+        let resampled =
+            DemTile::from_heights(self.lon, self.lat, new_size, &heights);
+        match self.grid_geometry() {
+            Some(grid) => resampled
+                .with_grid_geometry(grid.resampled(self.size, new_size)),
+            None => resampled,
+        }
+    }
+
+    fn weighted_height(
+        &self,
+        y_weights: &[(usize, f32)],
+        x_weights: &[(usize, f32)],
+        skip_voids: bool,
+    ) -> i16 {
+        let mut weighted_sum = 0.;
+        let mut weights_sum = 0.;
+
+        for &(y, y_weight) in y_weights {
+            for &(x, x_weight) in x_weights {
+                let height = self.height_at_index(y * self.size + x);
+                if height == NO_DATA_HEIGHT {
+                    if skip_voids {
+                        continue;
+                    }
+                    return NO_DATA_HEIGHT;
+                }
+
+                weighted_sum += y_weight * x_weight * height as f32;
+                weights_sum += y_weight * x_weight;
+            }
+        }
+
+        if weights_sum == 0. {
+            return NO_DATA_HEIGHT;
+        }
+
+        // the bicubic interpolation can overshoot the source heights
+        let min_height = (NO_DATA_HEIGHT + 1) as f32;
+        (weighted_sum / weights_sum)
+            .round()
+            .clamp(min_height, i16::MAX as f32) as i16
+    }
+}
+
+/// Calculates the source cells (and their weights) along one axis that the
+/// resampled cell at `index` is calculated from. Only the cells with a
+/// non-zero weight are listed, so the resampled edges depend just on the
+/// source edges.
+fn axis_weights(
+    index: usize,
+    source_size: usize,
+    new_size: usize,
+    method: ResamplingMethod,
+) -> Vec<(usize, f32)> {
+    let last = source_size - 1;
+    // the source cells per resampled cell
+    let scale = last as f32 / (new_size - 1) as f32;
+
+    if index == 0 {
+        return vec![(0, 1.)];
+    }
+    if index == new_size - 1 {
+        return vec![(last, 1.)];
+    }
+
+    let position = index as f32 * scale;
+    let base = position.floor();
+    let fraction = position - base;
+    let base = base as usize;
+
+    let weights = match method {
+        ResamplingMethod::Nearest => {
+            vec![((position.round() as usize).min(last), 1.)]
+        }
+        ResamplingMethod::Bilinear => {
+            vec![(base, 1. - fraction), ((base + 1).min(last), fraction)]
+        }
+        ResamplingMethod::Bicubic => {
+            // the cells beyond the tile's edge are extrapolated linearly
+            // (h[-1] = 2 h[0] - h[1]), so planes stay planes at the edges
+            let mut weights = [0.; 4];
+            let first = base as isize - 1;
+            for (i, weight) in catmull_rom_weights(fraction).iter().enumerate()
+            {
+                let cell = first + i as isize;
+                if cell < 0 {
+                    weights[1] += 2. * weight;
+                    weights[2] -= weight;
+                } else if cell > last as isize {
+                    weights[i - 1] += 2. * weight;
+                    weights[i - 2] -= weight;
+                } else {
+                    weights[i] += weight;
+                }
+            }
+            weights
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| {
+                    (0..=last as isize).contains(&(first + i as isize))
+                })
+                .map(|(i, &weight)| ((first + i as isize) as usize, weight))
+                .collect()
+        }
+        ResamplingMethod::AreaAverage => {
+            // the resampled cell's footprint in source cells, each source
+            // cell covering the [j - 0.5, j + 0.5] interval
+            let half_width = scale.max(1.) / 2.;
+            let start = (position - half_width).max(-0.5);
+            let end = (position + half_width).min(last as f32 + 0.5);
+
+            let first = (start + 0.5).floor().max(0.) as usize;
+            let last_covered = ((end + 0.5).ceil() as usize).min(source_size);
+            (first..last_covered)
+                .map(|j| {
+                    let cell_start = j as f32 - 0.5;
+                    let overlap =
+                        end.min(cell_start + 1.) - start.max(cell_start);
+                    (j, overlap.max(0.))
+                })
+                .collect()
+        }
+    };
+
+    weights
+        .into_iter()
+        .filter(|&(_, weight)| weight != 0.)
+        .collect()
+}
+
+/// The Catmull-Rom spline weights of the 4 cells around the position
+/// (at offsets -1, 0, 1 and 2 from the base cell).
+pub(crate) fn catmull_rom_weights(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        (-t3 + 2. * t2 - t) / 2.,
+        (3. * t3 - 5. * t2 + 2.) / 2.,
+        (-3. * t3 + 4. * t2 + t) / 2.,
+        (t3 - t2) / 2.,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid_spacing::GridGeometry;
+    use rstest::rstest;
+
+    /// Creates a tile with the heights calculated from the global (i.e.
+    /// across tiles) cell coordinates.
+    fn tile_from_fn(
+        lon: i16,
+        size: usize,
+        height_fn: impl Fn(f32, f32) -> f32,
+    ) -> DemTile {
+        let intervals = (size - 1) as f32;
+        let heights: Vec<i16> = (0..size * size)
+            .map(|index| {
+                let x = lon as f32 + (index % size) as f32 / intervals;
+                let y = (index / size) as f32 / intervals;
+                height_fn(x, y).round() as i16
+            })
+            .collect();
+        DemTile::from_heights(lon, 46, size, &heights)
+    }
+
+    fn plane(x: f32, y: f32) -> f32 {
+        1000. + 800. * x - 400. * y
+    }
+
+    fn hills(x: f32, y: f32) -> f32 {
+        1000. + 300. * (x * 9.).sin() * (y * 7.).cos()
+    }
+
+    #[rstest]
+    #[case(ResamplingMethod::Nearest)]
+    #[case(ResamplingMethod::Bilinear)]
+    #[case(ResamplingMethod::Bicubic)]
+    #[case(ResamplingMethod::AreaAverage)]
+    fn resampling_to_the_same_size_keeps_the_heights(
+        #[case] method: ResamplingMethod,
+    ) {
+        let tile = tile_from_fn(7, 13, hills);
+        let resampled = tile.resample(13, method);
+
+        assert_eq!((resampled.lon, resampled.lat), (7, 46));
+        assert_eq!(resampled.heights(), tile.heights());
+    }
+
+    #[rstest]
+    #[case(ResamplingMethod::Nearest, 33, 17, 0)]
+    #[case(ResamplingMethod::Bilinear, 33, 7, 1)]
+    #[case(ResamplingMethod::Bicubic, 33, 7, 1)]
+    #[case(ResamplingMethod::AreaAverage, 33, 17, 1)]
+    #[case(ResamplingMethod::Bilinear, 9, 25, 1)]
+    #[case(ResamplingMethod::Bicubic, 9, 25, 1)]
+    fn resampled_plane_stays_a_plane(
+        #[case] method: ResamplingMethod,
+        #[case] size: usize,
+        #[case] new_size: usize,
+        #[case] tolerance: i16,
+    ) {
+        let resampled = tile_from_fn(0, size, plane).resample(new_size, method);
+        let expected = tile_from_fn(0, new_size, plane);
+
+        assert_eq!(resampled.size, new_size);
+        for (height, expected) in
+            resampled.heights().into_iter().zip(expected.heights())
+        {
+            assert!(
+                (height - expected).abs() <= tolerance,
+                "{} instead of {}",
+                height,
+                expected
+            );
+        }
+    }
+
+    #[rstest]
+    #[case(ResamplingMethod::Nearest)]
+    #[case(ResamplingMethod::Bilinear)]
+    #[case(ResamplingMethod::Bicubic)]
+    #[case(ResamplingMethod::AreaAverage)]
+    fn neighbouring_resampled_tiles_line_up(#[case] method: ResamplingMethod) {
+        let west = tile_from_fn(7, 61, hills).resample(18, method);
+        let east = tile_from_fn(8, 61, hills).resample(18, method);
+
+        for y in 0..18 {
+            assert_eq!(west.height_at(17, y), east.height_at(0, y));
+        }
+    }
+
+    #[test]
+    fn voids_are_propagated_or_skipped() {
+        let mut heights = vec![100; 25];
+        heights[6] = NO_DATA_HEIGHT;
+        let tile = DemTile::from_heights(7, 46, 5, &heights);
+
+        let bilinear = tile.resample(4, ResamplingMethod::Bilinear);
+        assert_eq!(bilinear.height_at(1, 1), NO_DATA_HEIGHT);
+        assert_eq!(bilinear.height_at(2, 2), 100);
+
+        let area_average = tile.resample(4, ResamplingMethod::AreaAverage);
+        assert_eq!(area_average.void_count(), 0);
+        assert_eq!(area_average.height_at(1, 1), 100);
+    }
+
+    #[test]
+    fn resampled_edge_cells_stay_in_place() {
+        let grid = GridGeometry::Geographic {
+            north_lat: 47. - 0.5 / 4.,
+            west_lon: 7. + 0.5 / 4.,
+            cell_size: 1. / 4.,
+        };
+        let tile = DemTile::from_heights(7, 46, 4, &[100; 16])
+            .with_grid_geometry(grid);
+
+        let resampled = tile.resample(7, ResamplingMethod::Bilinear);
+        let resampled_grid = GridGeometry::of_tile(&resampled);
+        assert_eq!(resampled_grid.row_lat(0), grid.row_lat(0));
+        assert_eq!(resampled_grid.row_lat(6), grid.row_lat(3));
+        assert_eq!(
+            GridGeometry::of_tile(&tile.resample(2, ResamplingMethod::Nearest)),
+            GridGeometry::Geographic {
+                north_lat: grid.row_lat(0).unwrap(),
+                west_lon: 7. + 0.5 / 4.,
+                cell_size: 0.75
+            }
+        );
+    }
+}
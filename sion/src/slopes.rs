@@ -1,6 +1,6 @@
 use crate::geo::geodetic_distance_approximate;
 
-pub fn grid_size(coords: &Vec<(f32, f32)>) -> (f32, f32) {
+pub fn grid_size(coords: &[(f32, f32)]) -> (f32, f32) {
     let (lon1, lat1) = coords[0];
     let (lon2, lat2) = coords[1];
     let (lon3, lat3) = coords[3];
@@ -11,10 +11,7 @@ pub fn grid_size(coords: &Vec<(f32, f32)>) -> (f32, f32) {
     (width, height)
 }
 
-pub fn calculate_pq(
-    coords: &Vec<(f32, f32)>,
-    heights: &Vec<f32>,
-) -> (f32, f32) {
+pub fn calculate_pq(coords: &[(f32, f32)], heights: &[f32]) -> (f32, f32) {
     let (grid_width, grid_height) = grid_size(coords);
 
     let p = ((heights[8] + 2.0 * heights[5] + heights[2])
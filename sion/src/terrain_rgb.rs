@@ -0,0 +1,580 @@
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+use crate::dem_tile_id::DemTileId;
+use crate::elevation::{DemTileSet, InterpolationMethod};
+use crate::errors::SionError;
+use crate::grid_spacing::GridGeometry;
+use crate::raster16::Raster16;
+use crate::web_tile_id::WebTileId;
+use image::{ImageError, Rgba, RgbaImage};
+use std::io;
+
+/// The encodings of heights into the RGB channels used by the web map
+/// terrain tiles. The voids are encoded as fully transparent pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrainEncoding {
+    /// Mapbox Terrain-RGB: `height = -10000 + (R * 65536 + G * 256 + B) *
+    /// 0.1`.
+    TerrainRgb,
+    /// Mapzen/AWS Terrarium: `height = R * 256 + G + B / 256 - 32768`.
+    Terrarium,
+}
+
+impl TerrainEncoding {
+    /// The offset (in meters) and the 24-bit value's units per meter.
+    fn offset_and_scale(&self) -> (f32, f32) {
+        match self {
+            TerrainEncoding::TerrainRgb => (10000., 10.),
+            TerrainEncoding::Terrarium => (32768., 256.),
+        }
+    }
+
+    /// Encodes the height (in meters) into the RGB channels, clamping it to
+    /// the encoding's range.
+    pub fn encode(&self, height: f32) -> [u8; 3] {
+        let (offset, scale) = self.offset_and_scale();
+        let value = ((height + offset) * scale)
+            .round()
+            .clamp(0., 0xFF_FFFF as f32) as u32;
+        [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+    }
+
+    /// Decodes the height (in meters) from the RGB channels.
+    pub fn decode(&self, rgb: [u8; 3]) -> f32 {
+        let (offset, scale) = self.offset_and_scale();
+        let value =
+            ((rgb[0] as u32) << 16) | ((rgb[1] as u32) << 8) | rgb[2] as u32;
+        value as f32 / scale - offset
+    }
+
+    /// Encodes the heights (listed row by row, NaN for the voids) into an
+    /// image.
+    pub fn encode_heights(
+        &self,
+        width: u32,
+        height: u32,
+        heights: &[f32],
+    ) -> RgbaImage {
+        if heights.len() != width as usize * height as usize {
+            panic!("The number of heights does not match the image size");
+        }
+
+        RgbaImage::from_fn(width, height, |x, y| {
+            let height = heights[(y * width + x) as usize];
+            if height.is_nan() {
+                Rgba([0, 0, 0, 0])
+            } else {
+                let [r, g, b] = self.encode(height);
+                Rgba([r, g, b, 255])
+            }
+        })
+    }
+
+    /// Decodes the image's heights row by row (NaN for the transparent
+    /// pixels).
+    pub fn decode_heights(&self, image: &RgbaImage) -> Vec<f32> {
+        image
+            .pixels()
+            .map(|&Rgba([r, g, b, alpha])| {
+                if alpha == 0 {
+                    f32::NAN
+                } else {
+                    self.decode([r, g, b])
+                }
+            })
+            .collect()
+    }
+}
+
+/// Renders the terrain image of the web map tile (`tile_size` pixels per
+/// side), with the heights (in meters) at the pixels' centers provided by
+/// `elevation_at` (`None` for the transparent pixels).
+pub fn web_terrain_image(
+    tile_id: &WebTileId,
+    tile_size: u32,
+    encoding: TerrainEncoding,
+    elevation_at: impl Fn(f64, f64) -> Option<f32>,
+) -> RgbaImage {
+    let heights: Vec<f32> = (0..tile_size * tile_size)
+        .map(|index| {
+            let (lon, lat) = tile_id.pixel_center(
+                tile_size,
+                index % tile_size,
+                index / tile_size,
+            );
+            elevation_at(lon, lat).unwrap_or(f32::NAN)
+        })
+        .collect();
+
+    encoding.encode_heights(tile_size, tile_size, &heights)
+}
+
+/// Saves the terrain image as a PNG file.
+fn save_terrain_png(image: &RgbaImage, file: &str) -> Result<(), SionError> {
+    image
+        .save(file)
+        .map_err(|error| SionError::CannotWriteFile {
+            file: file.to_string(),
+            source: match error {
+                ImageError::IoError(error) => error,
+                error => io::Error::new(io::ErrorKind::Other, error),
+            },
+        })
+}
+
+impl DemTile {
+    /// Encodes the tile's heights into a terrain image, pixel for pixel (see
+    /// `to_web_terrain_image` for rendering a web map tile).
+    pub fn to_terrain_image(&self, encoding: TerrainEncoding) -> RgbaImage {
+        let heights: Vec<f32> = self
+            .heights()
+            .into_iter()
+            .map(|height| {
+                if height == NO_DATA_HEIGHT {
+                    f32::NAN
+                } else {
+                    height as f32
+                }
+            })
+            .collect();
+
+        encoding.encode_heights(self.size as u32, self.size as u32, &heights)
+    }
+
+    /// Writes the tile as a terrain PNG file, pixel for pixel.
+    pub fn write_terrain_png(
+        &self,
+        file: &str,
+        encoding: TerrainEncoding,
+    ) -> Result<(), SionError> {
+        save_terrain_png(&self.to_terrain_image(encoding), file)
+    }
+
+    /// Renders the terrain image of the web map tile (`tile_size` pixels per
+    /// side, e.g. 256 or 512 for MapLibre), resampling the tile's heights
+    /// with the interpolation `method` (see `elevation_at`). The pixels
+    /// outside of the tile (or at its voids) are transparent.
+    pub fn to_web_terrain_image(
+        &self,
+        tile_id: &WebTileId,
+        tile_size: u32,
+        encoding: TerrainEncoding,
+        method: InterpolationMethod,
+    ) -> RgbaImage {
+        web_terrain_image(tile_id, tile_size, encoding, |lon, lat| {
+            self.elevation_at(lon, lat, method)
+        })
+    }
+
+    /// Writes the web map tile as a terrain PNG file, see
+    /// `to_web_terrain_image`.
+    pub fn write_web_terrain_png(
+        &self,
+        file: &str,
+        tile_id: &WebTileId,
+        tile_size: u32,
+        encoding: TerrainEncoding,
+        method: InterpolationMethod,
+    ) -> Result<(), SionError> {
+        let image =
+            self.to_web_terrain_image(tile_id, tile_size, encoding, method);
+        save_terrain_png(&image, file)
+    }
+
+    /// Creates a tile from a (square) terrain image of the web map tile,
+    /// e.g. a downloaded one. The heights are rounded to whole meters. The
+    /// tile's cells get the Web Mercator `GridGeometry` of the map tile's
+    /// pixels, its coordinates are those of the whole degrees containing
+    /// the map tile's south-west corner. Since many map tiles share these
+    /// coordinates, the tile is rejected by the degree-keyed tile stores
+    /// (`InMemoryTileSource`, `DemTileSet`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is not square.
+    pub fn from_terrain_image(
+        image: &RgbaImage,
+        encoding: TerrainEncoding,
+        tile_id: &WebTileId,
+    ) -> DemTile {
+        if image.width() != image.height() {
+            panic!("The terrain image is not square");
+        }
+
+        let min_height = (NO_DATA_HEIGHT + 1) as f32;
+        let heights: Vec<i16> = encoding
+            .decode_heights(image)
+            .into_iter()
+            .map(|height| {
+                if height.is_nan() {
+                    NO_DATA_HEIGHT
+                } else {
+                    height.round().clamp(min_height, i16::MAX as f32) as i16
+                }
+            })
+            .collect();
+
+        let size = image.width() as usize;
+        let bounding_box = tile_id.bounding_box();
+        let corner =
+            DemTileId::containing(bounding_box.min_lon, bounding_box.min_lat)
+                .unwrap();
+        let grid = GridGeometry::WebMercator {
+            north_row: (tile_id.y as usize * size) as f64 + 0.5,
+            west_column: (tile_id.x as usize * size) as f64 + 0.5,
+            world_size: (tile_id.tiles_per_side() as usize * size) as f64,
+        };
+
+        DemTile::from_heights(corner.lon, corner.lat, size, &heights)
+            .with_grid_geometry(grid)
+    }
+
+    /// Loads a tile from a (square) terrain PNG file of the web map tile,
+    /// see `from_terrain_image`.
+    pub fn from_terrain_png(
+        file: &str,
+        encoding: TerrainEncoding,
+        tile_id: &WebTileId,
+    ) -> Result<DemTile, SionError> {
+        let image = image::open(file)
+            .map_err(|error| match error {
+                ImageError::IoError(error) => SionError::CannotOpenFile {
+                    file: file.to_string(),
+                    source: error,
+                },
+                error => SionError::InvalidImage {
+                    file: file.to_string(),
+                    reason: error.to_string(),
+                },
+            })?
+            .into_rgba8();
+
+        if image.width() != image.height() {
+            return Err(SionError::InvalidImage {
+                file: file.to_string(),
+                reason: format!(
+                    "the image is not square ({}x{})",
+                    image.width(),
+                    image.height()
+                ),
+            });
+        }
+
+        Ok(DemTile::from_terrain_image(&image, encoding, tile_id))
+    }
+}
+
+impl DemTileSet {
+    /// Renders the terrain image of the web map tile from the set's tiles,
+    /// e.g. for the map tiles spanning several degrees. See
+    /// `DemTile::to_web_terrain_image`.
+    pub fn to_web_terrain_image(
+        &self,
+        tile_id: &WebTileId,
+        tile_size: u32,
+        encoding: TerrainEncoding,
+        method: InterpolationMethod,
+    ) -> RgbaImage {
+        web_terrain_image(tile_id, tile_size, encoding, |lon, lat| {
+            self.elevation_at(lon, lat, method)
+        })
+    }
+}
+
+impl Raster16 {
+    /// Encodes the raster's values (heights in meters) into a terrain
+    /// image.
+    pub fn to_terrain_image(&self, encoding: TerrainEncoding) -> RgbaImage {
+        let heights: Vec<f32> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.get_pixel(x, y) as f32)
+            .collect();
+
+        encoding.encode_heights(self.width as u32, self.height as u32, &heights)
+    }
+
+    /// Creates a raster from a terrain image, with the heights rounded to
+    /// whole meters and clamped to the raster's values (the transparent
+    /// pixels become 0).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is wider or higher than `u16::MAX` pixels.
+    pub fn from_terrain_image(
+        image: &RgbaImage,
+        encoding: TerrainEncoding,
+    ) -> Raster16 {
+        let (Ok(width), Ok(height)) =
+            (u16::try_from(image.width()), u16::try_from(image.height()))
+        else {
+            panic!("The image is too large for a 16-bit raster");
+        };
+
+        let mut raster = Raster16::new(width, height);
+        for (index, height) in
+            encoding.decode_heights(image).into_iter().enumerate()
+        {
+            let value = if height.is_nan() {
+                0
+            } else {
+                height.round().clamp(0., u16::MAX as f32) as u16
+            };
+            raster.set_pixel(
+                (index % width as usize) as u16,
+                (index / width as usize) as u16,
+                value,
+            );
+        }
+        raster
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dem_tile_source::InMemoryTileSource;
+    use crate::elevation::DemTileSet;
+    use crate::grayscale8_bitmap::Grayscale8Bitmap;
+    use crate::hillshading::lambertian_hillshading;
+    use crate::hillshading::parameters::HillshadingParameters;
+    use crate::testing::assert_eq_approx;
+    use rstest::rstest;
+    use std::fs;
+
+    #[rstest]
+    #[case(TerrainEncoding::TerrainRgb, [1, 134, 160], 0.)]
+    #[case(TerrainEncoding::TerrainRgb, [1, 153, 113], 481.7)]
+    #[case(TerrainEncoding::TerrainRgb, [0, 0, 0], -10000.)]
+    #[case(TerrainEncoding::Terrarium, [128, 0, 0], 0.)]
+    #[case(TerrainEncoding::Terrarium, [129, 225, 179], 481.69922)]
+    #[case(TerrainEncoding::Terrarium, [127, 255, 128], -0.5)]
+    fn encode_and_decode_heights(
+        #[case] encoding: TerrainEncoding,
+        #[case] rgb: [u8; 3],
+        #[case] height: f32,
+    ) {
+        assert_eq_approx(encoding.decode(rgb), height, 0.001);
+        assert_eq!(encoding.encode(height), rgb);
+    }
+
+    #[rstest]
+    #[case(TerrainEncoding::TerrainRgb, -10000)]
+    #[case(TerrainEncoding::Terrarium, NO_DATA_HEIGHT + 1)]
+    fn tiles_survive_png_round_trip(
+        #[case] encoding: TerrainEncoding,
+        #[case] lowest_height: i16,
+    ) {
+        let heights = [
+            lowest_height,
+            -1,
+            0,
+            1,
+            4808,
+            NO_DATA_HEIGHT,
+            8848,
+            i16::MAX,
+            7,
+        ];
+        let tile = DemTile::from_heights(-123, 37, 3, &heights);
+
+        let dir = "target/debug/terrain_rgb_tests";
+        fs::create_dir_all(dir).unwrap();
+        let file = format!("{}/{:?}.png", dir, encoding);
+        tile.write_terrain_png(&file, encoding).unwrap();
+
+        // the zoom level 1 tile south-west of the map's center
+        let web_tile = WebTileId::new(1, 0, 1);
+        let read_tile =
+            DemTile::from_terrain_png(&file, encoding, &web_tile).unwrap();
+        assert_eq!((read_tile.lon, read_tile.lat), (-180, -86));
+        assert_eq!(
+            read_tile.grid_geometry(),
+            Some(GridGeometry::WebMercator {
+                north_row: 3.5,
+                west_column: 0.5,
+                world_size: 6.
+            })
+        );
+        assert_eq!(read_tile.heights(), heights);
+    }
+
+    #[test]
+    fn non_square_images_are_rejected() {
+        let dir = "target/debug/terrain_rgb_tests";
+        fs::create_dir_all(dir).unwrap();
+        let file = format!("{}/non_square.png", dir);
+        TerrainEncoding::Terrarium
+            .encode_heights(2, 1, &[1., 2.])
+            .save(&file)
+            .unwrap();
+
+        assert!(matches!(
+            DemTile::from_terrain_png(
+                &file,
+                TerrainEncoding::Terrarium,
+                &WebTileId::new(0, 0, 0)
+            ),
+            Err(SionError::InvalidImage { .. })
+        ));
+        assert!(matches!(
+            DemTile::from_terrain_png(
+                "target/debug/missing.png",
+                TerrainEncoding::Terrarium,
+                &WebTileId::new(0, 0, 0)
+            ),
+            Err(SionError::CannotOpenFile { .. })
+        ));
+    }
+
+    /// The web map tile's pixels shrink with their latitude, a plane on
+    /// them gets the same shade as anywhere else.
+    #[test]
+    fn decoded_web_tiles_are_shaded_by_their_slopes() {
+        // the zoom level 12 tile just south-west of 10°E, 60°N
+        let web_tile = WebTileId::new(12, 2161, 1189);
+        let size = 64;
+        let grid = GridGeometry::WebMercator {
+            north_row: (1189 * size) as f64 + 0.5,
+            west_column: (2161 * size) as f64 + 0.5,
+            world_size: (4096 * size) as f64,
+        };
+
+        // a 30° slope facing the west
+        let parameters = HillshadingParameters::default();
+        let equator_pixel = 360. * parameters.scale / (4096 * size) as f32;
+        let heights: Vec<f32> = (0..size * size)
+            .map(|index| {
+                let (x, y) = (index % size, index / size);
+                let pixel = equator_pixel
+                    * grid.row_lat(y).unwrap().to_radians().cos() as f32;
+                100. + x as f32 * pixel * 30f32.to_radians().tan()
+            })
+            .collect();
+        let image = TerrainEncoding::TerrainRgb.encode_heights(
+            size as u32,
+            size as u32,
+            &heights,
+        );
+
+        let dem = DemTile::from_terrain_image(
+            &image,
+            TerrainEncoding::TerrainRgb,
+            &web_tile,
+        );
+        assert_eq!((dem.lon, dem.lat), (9, 59));
+        assert_eq!(dem.grid_geometry(), Some(grid));
+
+        let mut bitmap = Grayscale8Bitmap::new(size as u16, size as u16);
+        lambertian_hillshading::hillshade(&dem, &parameters, &mut bitmap);
+
+        // 1 + 254 * (sin 45° cos 30° + cos 45° sin 30° cos 45°)
+        let expected = 220;
+        for (x, y) in [(1, 1), (32, 32), (62, 62)] {
+            let shade = bitmap.get_pixel(x, y);
+            assert!(shade.abs_diff(expected) <= 1, "{} at {}, {}", shade, x, y);
+        }
+    }
+
+    #[test]
+    fn web_tiles_are_not_served_by_their_degrees() {
+        // two of the zoom level 10 tiles in the 9°E, 59°N degree
+        let image = TerrainEncoding::Terrarium.encode_heights(2, 2, &[1.; 4]);
+        let tile = |x| {
+            DemTile::from_terrain_image(
+                &image,
+                TerrainEncoding::Terrarium,
+                &WebTileId::new(10, x, 297),
+            )
+        };
+        assert_eq!(tile(538).tile_id(), tile(539).tile_id());
+
+        assert!(matches!(
+            InMemoryTileSource::new().insert(tile(538)),
+            Err(SionError::UnsupportedTileGrid {
+                lon: 9,
+                lat: 59,
+                ..
+            })
+        ));
+        assert!(matches!(
+            DemTileSet::new().insert(tile(539)),
+            Err(SionError::UnsupportedTileGrid {
+                lon: 9,
+                lat: 59,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn dem_tiles_are_rendered_as_web_map_tiles() {
+        // a plane rising by 1 m per 0.01° to the east and the north
+        let size = 101;
+        let heights: Vec<i16> = (0..size * size)
+            .map(|index| (index % size + (size - 1 - index / size)) as i16)
+            .collect();
+        let dem = DemTile::from_heights(9, 59, size, &heights);
+
+        // the zoom level 10 tile reaching north of the DEM tile
+        let web_tile = WebTileId::new(10, 538, 297);
+        let bilinear = InterpolationMethod::Bilinear;
+        let image = dem.to_web_terrain_image(
+            &web_tile,
+            16,
+            TerrainEncoding::Terrarium,
+            bilinear,
+        );
+        assert_eq!(image.dimensions(), (16, 16));
+
+        let plane = |lon: f64, lat: f64| {
+            ((lon - 9.) * 100. + (lat - 59.) * 100.) as f32
+        };
+        let (lon, lat) = web_tile.pixel_center(16, 5, 12);
+        let Rgba([r, g, b, alpha]) = *image.get_pixel(5, 12);
+        assert_eq!(alpha, 255);
+        assert_eq_approx(
+            TerrainEncoding::Terrarium.decode([r, g, b]),
+            plane(lon, lat),
+            0.01,
+        );
+        // north of 60°N
+        assert_eq!(image.get_pixel(5, 0)[3], 0);
+
+        // the decoded web map tile has the same elevations
+        let decoded = DemTile::from_terrain_image(
+            &image,
+            TerrainEncoding::Terrarium,
+            &web_tile,
+        );
+        let elevation = decoded.elevation_at(lon, lat, bilinear).unwrap();
+        assert_eq_approx(elevation, plane(lon, lat).round(), 0.001);
+
+        // the same tile rendered from a tile set
+        let mut tiles = DemTileSet::new();
+        tiles.insert(dem).unwrap();
+        let from_set = tiles.to_web_terrain_image(
+            &web_tile,
+            16,
+            TerrainEncoding::Terrarium,
+            bilinear,
+        );
+        assert_eq!(from_set.get_pixel(5, 12), image.get_pixel(5, 12));
+    }
+
+    #[test]
+    fn raster_survives_terrain_encoding() {
+        let mut raster = Raster16::new(3, 2);
+        raster.set_pixel(0, 0, 4807);
+        raster.set_pixel(2, 0, 1);
+        raster.set_pixel(1, 1, 8848);
+
+        let image = raster.to_terrain_image(TerrainEncoding::TerrainRgb);
+        let decoded =
+            Raster16::from_terrain_image(&image, TerrainEncoding::TerrainRgb);
+
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        assert_eq!(decoded.get_pixel(0, 0), 4807);
+        assert_eq!(decoded.get_pixel(2, 0), 1);
+        assert_eq!(decoded.get_pixel(1, 1), 8848);
+        assert_eq!(decoded.get_pixel(0, 1), 0);
+    }
+}
@@ -1,3 +1,10 @@
+use crate::consts::EARTH_CIRCUMFERENCE_METERS;
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+use crate::dem_tile_id::DemTileId;
+use crate::errors::SionError;
+use crate::grid_spacing::{GridGeometry, GridSpacing};
+use std::path::Path;
+
 pub fn assert_eq_approx<T>(a: T, b: T, tolerance: T)
 where
     T: PartialOrd + std::ops::Sub<Output = T> + Copy + std::fmt::Debug + Abs,
@@ -63,3 +70,377 @@ impl Abs for i64 {
         }
     }
 }
+
+/// An analytic surface, added to the synthetic terrain's heights. The cell
+/// coordinates are fractional (x to the east, y to the south, starting at
+/// the tile's north-west corner), the heights and distances in meters.
+#[derive(Clone, Copy, Debug)]
+pub enum Surface {
+    /// A plane through the tile's center at `height`, sloping down by
+    /// `slope` degrees towards the `aspect` (in degrees clockwise from the
+    /// north). The east-west distances shrink with each row's latitude,
+    /// like the hillshaders' grid spacing (see `GridSpacing`).
+    Plane {
+        height: f32,
+        slope: f32,
+        aspect: f32,
+    },
+    /// A cone with its peak `height` above the `center` cell, reaching zero
+    /// at `radius` cells from it.
+    Cone {
+        center: (f32, f32),
+        radius: f32,
+        height: f32,
+    },
+    /// A Gaussian hill with its peak `height` above the `center` cell and
+    /// the standard deviation of `sigma` cells.
+    GaussianHill {
+        center: (f32, f32),
+        sigma: f32,
+        height: f32,
+    },
+    /// Seeded value noise with `octaves` layers, each one with half of the
+    /// previous one's wavelength and amplitude. The heights range from
+    /// `-amplitude` to `amplitude` (roughly, the octaves add up).
+    FractalNoise {
+        seed: u64,
+        amplitude: f32,
+        wavelength: f32,
+        octaves: u32,
+    },
+}
+
+impl Surface {
+    fn height_at(
+        &self,
+        x: f32,
+        y: f32,
+        spacing: (f32, f32),
+        size: usize,
+    ) -> f32 {
+        match *self {
+            Surface::Plane {
+                height,
+                slope,
+                aspect,
+            } => {
+                let center = (size - 1) as f32 / 2.;
+                let east = (x - center) * spacing.0;
+                let north = (center - y) * spacing.1;
+                let aspect = aspect.to_radians();
+                height
+                    - slope.to_radians().tan()
+                        * (east * aspect.sin() + north * aspect.cos())
+            }
+            Surface::Cone {
+                center,
+                radius,
+                height,
+            } => {
+                let distance = (x - center.0).hypot(y - center.1);
+                height * (1. - distance / radius).max(0.)
+            }
+            Surface::GaussianHill {
+                center,
+                sigma,
+                height,
+            } => {
+                let distance_squared =
+                    (x - center.0).powi(2) + (y - center.1).powi(2);
+                height * (-distance_squared / (2. * sigma * sigma)).exp()
+            }
+            Surface::FractalNoise {
+                seed,
+                amplitude,
+                wavelength,
+                octaves,
+            } => (0..octaves)
+                .map(|octave| {
+                    let scale = 2f32.powi(octave as i32);
+                    let seed = seed.wrapping_add(octave as u64);
+                    value_noise(
+                        seed,
+                        x * scale / wavelength,
+                        y * scale / wavelength,
+                    ) * amplitude
+                        / scale
+                })
+                .sum(),
+        }
+    }
+}
+
+/// Smoothly interpolated random values (from -1 to 1) at the integer
+/// coordinates.
+fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: f32| t * t * (3. - 2. * t);
+    let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+
+    let lattice = |dx: i64, dy: i64| {
+        let (ix, iy) = (x0 as i64 + dx, y0 as i64 + dy);
+        let hash = split_mix64(
+            seed ^ (ix as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+                ^ (iy as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F),
+        );
+        (hash >> 40) as f32 / (1u64 << 23) as f32 - 1.
+    };
+
+    let top = lattice(0, 0) + (lattice(1, 0) - lattice(0, 0)) * tx;
+    let bottom = lattice(0, 1) + (lattice(1, 1) - lattice(0, 1)) * tx;
+    top + (bottom - top) * ty
+}
+
+/// The SplitMix64 mixing function, a small deterministic hash.
+fn split_mix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Builds DEM tiles from the sum of analytic surfaces, so the tests and the
+/// benchmarks do not depend on the real DEM files and their expected values
+/// can be calculated.
+///
+/// ```
+/// use sion::testing::{Surface, SyntheticTerrain};
+///
+/// let tile = SyntheticTerrain::new(6, 46, 101)
+///     .with(Surface::GaussianHill {
+///         center: (50., 50.),
+///         sigma: 10.,
+///         height: 1000.,
+///     })
+///     .build();
+/// assert_eq!(tile.height_at(50, 50), 1000);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SyntheticTerrain {
+    lon: i16,
+    lat: i16,
+    size: usize,
+    surfaces: Vec<Surface>,
+    voids: Vec<(usize, usize)>,
+}
+
+impl SyntheticTerrain {
+    /// Creates a flat terrain at sea level for the tile at the given south-
+    /// west corner coordinates.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is less than 2.
+    pub fn new(lon: i16, lat: i16, size: usize) -> SyntheticTerrain {
+        if size < 2 {
+            panic!("The tile size must be at least 2");
+        }
+
+        SyntheticTerrain {
+            lon,
+            lat,
+            size,
+            surfaces: Vec::new(),
+            voids: Vec::new(),
+        }
+    }
+
+    /// Creates a mountainous terrain standing in for a real alpine tile
+    /// (like N46E006) in the whole-tile tests and the benchmarks: a massif
+    /// in the tile's middle, rugged with fractal noise.
+    pub fn mountains(lon: i16, lat: i16, size: usize) -> SyntheticTerrain {
+        let middle = (size - 1) as f32 / 2.;
+        SyntheticTerrain::new(lon, lat, size)
+            .with(Surface::GaussianHill {
+                center: (middle, middle),
+                sigma: size as f32 / 4.,
+                height: 2500.,
+            })
+            .with(Surface::FractalNoise {
+                seed: 46,
+                amplitude: 600.,
+                wavelength: size as f32 / 8.,
+                octaves: 6,
+            })
+    }
+
+    /// Adds the surface to the terrain.
+    pub fn with(mut self, surface: Surface) -> SyntheticTerrain {
+        self.surfaces.push(surface);
+        self
+    }
+
+    /// Marks the cell as a void.
+    pub fn with_void(mut self, x: usize, y: usize) -> SyntheticTerrain {
+        self.voids.push((x, y));
+        self
+    }
+
+    /// The grid spacing (in meters) of the tile's rows, as the hillshaders
+    /// calculate it for a regular tile.
+    pub fn grid_spacing(&self) -> GridSpacing {
+        let geometry = GridGeometry::regular(self.lon, self.lat, self.size);
+        GridSpacing::new(
+            &geometry,
+            self.size,
+            EARTH_CIRCUMFERENCE_METERS / 360.,
+        )
+    }
+
+    /// Calculates the (unrounded) height at the cell.
+    pub fn height_at(&self, x: usize, y: usize) -> f32 {
+        self.height_with_spacing(x, y, &self.grid_spacing())
+    }
+
+    fn height_with_spacing(
+        &self,
+        x: usize,
+        y: usize,
+        spacing: &GridSpacing,
+    ) -> f32 {
+        let row_spacing = (spacing.east_west(y), spacing.north_south(y));
+        self.surfaces
+            .iter()
+            .map(|surface| {
+                surface.height_at(x as f32, y as f32, row_spacing, self.size)
+            })
+            .sum()
+    }
+
+    /// Builds the tile, rounding the heights to whole meters (and clamping
+    /// them to the valid heights).
+    pub fn build(&self) -> DemTile {
+        let min_height = (NO_DATA_HEIGHT + 1) as f32;
+        let spacing = self.grid_spacing();
+        let mut heights: Vec<i16> = (0..self.size * self.size)
+            .map(|index| {
+                let (x, y) = (index % self.size, index / self.size);
+                self.height_with_spacing(x, y, &spacing)
+                    .round()
+                    .clamp(min_height, i16::MAX as f32) as i16
+            })
+            .collect();
+
+        for &(x, y) in &self.voids {
+            heights[y * self.size + x] = NO_DATA_HEIGHT;
+        }
+
+        DemTile::from_heights(self.lon, self.lat, self.size, &heights)
+    }
+
+    /// Builds the tile and writes it into the directory as an HGT file named
+    /// after the tile (e.g. `N46E006.hgt`), returning the file's path.
+    pub fn write_hgt(&self, directory: &str) -> Result<String, SionError> {
+        let file = self.file_path(directory, "hgt");
+        self.build().write_hgt(&file)?;
+        Ok(file)
+    }
+
+    /// Builds the tile and writes it into the directory as an XTH file named
+    /// after the tile (e.g. `N46E006.xth`), returning the file's path.
+    pub fn write_xth(&self, directory: &str) -> Result<String, SionError> {
+        let file = self.file_path(directory, "xth");
+        self.build().write_xth(&file)?;
+        Ok(file)
+    }
+
+    fn file_path(&self, directory: &str, extension: &str) -> String {
+        let tile_id = DemTileId::new(self.lon, self.lat);
+        Path::new(directory)
+            .join(format!("{}.{}", tile_id, extension))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn plane_has_the_given_slope_and_aspect() {
+        // sloping down towards the east
+        let terrain = SyntheticTerrain::new(6, 46, 101).with(Surface::Plane {
+            height: 1000.,
+            slope: 30.,
+            aspect: 90.,
+        });
+        let east_spacing = terrain.grid_spacing().east_west(50);
+
+        assert_eq_approx(terrain.height_at(50, 50), 1000., 0.001);
+        assert_eq_approx(terrain.height_at(50, 0), 1000., 0.01);
+        let drop = terrain.height_at(50, 50) - terrain.height_at(51, 50);
+        assert_eq_approx(drop / east_spacing, 30f32.to_radians().tan(), 1e-4);
+    }
+
+    #[test]
+    fn cones_and_hills_peak_at_their_centers() {
+        let tile = SyntheticTerrain::new(0, 0, 11)
+            .with(Surface::Cone {
+                center: (2., 2.),
+                radius: 2.,
+                height: 100.,
+            })
+            .with(Surface::GaussianHill {
+                center: (8., 8.),
+                sigma: 1.,
+                height: 50.,
+            })
+            .with_void(10, 0)
+            .build();
+
+        assert_eq!(tile.height_at(2, 2), 100);
+        assert_eq!(tile.height_at(3, 2), 50);
+        assert_eq!(tile.height_at(4, 2), 0);
+        assert_eq!(tile.height_at(8, 8), 50);
+        // exp(-1/2)
+        assert_eq!(tile.height_at(8, 7), 30);
+        assert_eq!(tile.height_at(10, 0), NO_DATA_HEIGHT);
+    }
+
+    #[test]
+    fn noise_is_deterministic_for_the_seed() {
+        let noise = |seed| {
+            SyntheticTerrain::new(0, 0, 33)
+                .with(Surface::FractalNoise {
+                    seed,
+                    amplitude: 200.,
+                    wavelength: 16.,
+                    octaves: 4,
+                })
+                .build()
+                .heights()
+        };
+
+        assert_eq!(noise(7), noise(7));
+        assert_ne!(noise(7), noise(8));
+        assert!(noise(7).iter().all(|height| height.abs() <= 400));
+        assert!(noise(7).iter().any(|&height| height != 0));
+    }
+
+    #[test]
+    fn terrain_is_written_as_hgt_and_xth_files() {
+        let dir = "target/debug/testing_tests";
+        fs::create_dir_all(dir).unwrap();
+        let terrain = SyntheticTerrain::new(-7, -5, 5).with(Surface::Cone {
+            center: (2., 2.),
+            radius: 3.,
+            height: 300.,
+        });
+
+        let hgt_file = terrain.write_hgt(dir).unwrap();
+        assert!(hgt_file.ends_with("S05W007.hgt"));
+        let xth_file = terrain.write_xth(dir).unwrap();
+
+        let expected = terrain.build().heights();
+        assert_eq!(
+            DemTile::from_hgt_file(&hgt_file).unwrap().heights(),
+            expected
+        );
+        assert_eq!(
+            DemTile::from_xth_file(&xth_file).unwrap().heights(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use crate::dem_tile::{DemTile, NO_DATA_HEIGHT};
+
+/// The interpolation used to fill the DEM voids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoidFillMethod {
+    /// Inverse-distance weighting (power 2) of the heights on the void's
+    /// edge. Cheap, but produces slightly "tented" surfaces in big voids.
+    InverseDistance,
+    /// Solves the Laplace equation over the void with the void's edge as the
+    /// boundary, producing the smoothest possible surface.
+    Laplacian,
+}
+
+/// The Laplacian relaxation stops once no cell changes by more than this
+/// (in meters)...
+const LAPLACIAN_TOLERANCE: f32 = 0.01;
+/// ...or after this many iterations.
+const LAPLACIAN_MAX_ITERATIONS: usize = 10_000;
+
+/// A connected (4-neighbourhood) group of void cells together with the valid
+/// cells touching it.
+struct Void {
+    cells: Vec<usize>,
+    edge: Vec<usize>,
+}
+
+impl DemTile {
+    /// Returns the number of void (`NO_DATA_HEIGHT`) cells in the tile.
+    pub fn void_count(&self) -> usize {
+        (0..self.size * self.size)
+            .filter(|&index| self.height_at_index(index) == NO_DATA_HEIGHT)
+            .count()
+    }
+
+    /// Fills the tile's voids by interpolating the heights from the voids'
+    /// edges and returns the number of filled cells. Voids without any valid
+    /// cell around them (i.e. a tile without any data) are left as they are.
+    ///
+    /// A memory-mapped tile is moved into memory when it has voids to fill.
+    pub fn fill_voids(&mut self, method: VoidFillMethod) -> usize {
+        if self.void_count() == 0 {
+            return 0;
+        }
+
+        let heights = self.heights();
+        let mut values: Vec<f32> = heights.iter().map(|&h| h as f32).collect();
+        let mut filled_count = 0;
+
+        for void in find_voids(&heights, self.size) {
+            if void.edge.is_empty() {
+                continue;
+            }
+
+            match method {
+                VoidFillMethod::InverseDistance => {
+                    fill_inverse_distance(&mut values, self.size, &void)
+                }
+                VoidFillMethod::Laplacian => {
+                    fill_laplacian(&mut values, self.size, &void)
+                }
+            }
+            filled_count += void.cells.len();
+        }
+
+        if filled_count > 0 {
+            // the unfilled voids are still at NO_DATA_HEIGHT
+            let heights: Vec<i16> =
+                values.iter().map(|value| value.round() as i16).collect();
+            self.set_heights(&heights);
+        }
+        filled_count
+    }
+}
+
+fn find_voids(heights: &[i16], size: usize) -> Vec<Void> {
+    let mut visited = vec![false; heights.len()];
+    // the void each valid cell was last added to the edge of
+    let mut edge_of = vec![usize::MAX; heights.len()];
+    let mut voids = Vec::new();
+
+    for start in 0..heights.len() {
+        if visited[start] || heights[start] != NO_DATA_HEIGHT {
+            continue;
+        }
+
+        let mut void = Void {
+            cells: Vec::new(),
+            edge: Vec::new(),
+        };
+        let mut stack = vec![start];
+        visited[start] = true;
+
+        while let Some(index) = stack.pop() {
+            void.cells.push(index);
+
+            for neighbour in neighbours(index, size) {
+                if visited[neighbour] {
+                    continue;
+                }
+
+                if heights[neighbour] == NO_DATA_HEIGHT {
+                    visited[neighbour] = true;
+                    stack.push(neighbour);
+                } else if edge_of[neighbour] != voids.len() {
+                    edge_of[neighbour] = voids.len();
+                    void.edge.push(neighbour);
+                }
+            }
+        }
+
+        voids.push(void);
+    }
+
+    voids
+}
+
+/// Returns the indices of the (up to 4) direct neighbours of the cell.
+fn neighbours(index: usize, size: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (index % size, index / size);
+
+    [
+        (x > 0).then(|| index - 1),
+        (x + 1 < size).then(|| index + 1),
+        (y > 0).then(|| index - size),
+        (y + 1 < size).then(|| index + size),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+fn fill_inverse_distance(values: &mut [f32], size: usize, void: &Void) {
+    for &index in &void.cells {
+        let (x, y) = ((index % size) as f32, (index / size) as f32);
+
+        let mut weighted_sum = 0.;
+        let mut weights_sum = 0.;
+        for &edge_index in &void.edge {
+            let dx = (edge_index % size) as f32 - x;
+            let dy = (edge_index / size) as f32 - y;
+            let weight = 1. / (dx * dx + dy * dy);
+
+            weighted_sum += weight * values[edge_index];
+            weights_sum += weight;
+        }
+
+        values[index] = weighted_sum / weights_sum;
+    }
+}
+
+/// Relaxes the void's cells (Gauss-Seidel) until each of them is the average
+/// of its neighbours. The edge cells keep their heights, the tile border is
+/// treated as a zero-gradient boundary.
+fn fill_laplacian(values: &mut [f32], size: usize, void: &Void) {
+    let edge_mean = void.edge.iter().map(|&i| values[i]).sum::<f32>()
+        / void.edge.len() as f32;
+    for &index in &void.cells {
+        values[index] = edge_mean;
+    }
+
+    for _ in 0..LAPLACIAN_MAX_ITERATIONS {
+        let mut max_change: f32 = 0.;
+
+        for &index in &void.cells {
+            let (sum, count) = neighbours(index, size)
+                .fold((0., 0.), |(sum, count), neighbour| {
+                    (sum + values[neighbour], count + 1.)
+                });
+            let value = sum / count;
+
+            max_change = max_change.max((value - values[index]).abs());
+            values[index] = value;
+        }
+
+        if max_change < LAPLACIAN_TOLERANCE {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    /// A 9x9 tile with a tilted plane (height = 100 + 10x + 20y) and a 3x3
+    /// void in the middle plus a single void cell on the tile's border (which
+    /// can only be approximated, as the plane is not extrapolated).
+    fn plane_with_voids() -> DemTile {
+        let size = 9;
+        let heights: Vec<i16> = (0..size * size)
+            .map(|index| {
+                let (x, y) = (index % size, index / size);
+                if (3..6).contains(&x) && (3..6).contains(&y)
+                    || (x, y) == (0, 7)
+                {
+                    NO_DATA_HEIGHT
+                } else {
+                    (100 + 10 * x + 20 * y) as i16
+                }
+            })
+            .collect();
+
+        DemTile::from_heights(7, 46, size, &heights)
+    }
+
+    #[rstest]
+    #[case(VoidFillMethod::InverseDistance, 5)]
+    #[case(VoidFillMethod::Laplacian, 3)]
+    fn voids_on_a_plane_are_filled(
+        #[case] method: VoidFillMethod,
+        #[case] tolerance: i16,
+    ) {
+        let mut tile = plane_with_voids();
+        assert_eq!(tile.void_count(), 10);
+
+        assert_eq!(tile.fill_voids(method), 10);
+        assert_eq!(tile.void_count(), 0);
+
+        for y in 0..9u16 {
+            for x in 0..9u16 {
+                let expected = 100 + 10 * x as i16 + 20 * y as i16;
+                let difference = (tile.height_at(x, y) - expected).abs();
+                assert!(
+                    difference <= tolerance,
+                    "({}, {}): {} instead of {}",
+                    x,
+                    y,
+                    tile.height_at(x, y),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tile_without_voids_is_left_untouched() {
+        let mut tile = DemTile::from_heights(7, 46, 2, &[1, 2, 3, 4]);
+        assert_eq!(tile.fill_voids(VoidFillMethod::Laplacian), 0);
+        assert_eq!(tile.heights(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn tile_without_any_data_cannot_be_filled() {
+        let mut tile = DemTile::from_heights(7, 46, 2, &[NO_DATA_HEIGHT; 4]);
+        assert_eq!(tile.fill_voids(VoidFillMethod::InverseDistance), 0);
+        assert_eq!(tile.void_count(), 4);
+    }
+}
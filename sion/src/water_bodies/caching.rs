@@ -30,7 +30,7 @@ pub fn ensure_file_in_cache(
             }
         }
 
-        let mut file = match File::create(&cached_file_name) {
+        let mut file = match File::create(cached_file_name) {
             Ok(file) => file,
             Err(e) => {
                 return Err(format!(
@@ -1,10 +1,9 @@
-use crate::water_bodies::dem_tile_id::DemTileId;
+use crate::dem_tile_id::DemTileId;
 
-// todo 4: start working on generate_water_bodies_tile implementation 
+// todo 4: start working on generate_water_bodies_tile implementation
 pub fn generate_water_bodies_tile(tile_id: &DemTileId) -> Result<(), String> {
     println!("Generating water bodies tile for tile ID: {}", tile_id);
     Err("Not implemented".to_string())
 }
 
-
-// todo 2: start preparing tests for generate_water_bodies_tile 
\ No newline at end of file
+// todo 2: start preparing tests for generate_water_bodies_tile
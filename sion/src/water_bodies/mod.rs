@@ -2,6 +2,6 @@
 
 pub mod caching;
 pub mod command;
-pub mod dem_tile_id;
+#[allow(clippy::module_inception)]
 pub mod water_bodies;
 pub mod worldcover;
@@ -1,5 +1,5 @@
+use crate::dem_tile_id::DemTileId;
 use crate::raster16::Raster16;
-use crate::water_bodies::dem_tile_id::DemTileId;
 use crate::water_bodies::worldcover::WORLD_COVER_TILES_IN_BATCH;
 use rayon::prelude::*;
 use std::collections::VecDeque;
@@ -29,7 +29,7 @@ pub struct WaterBodiesProcessingTile {
 impl WaterBodiesProcessingTile {
     pub fn new(tile_id: &DemTileId, tile_size: u16) -> Self {
         WaterBodiesProcessingTile {
-            tile_id: tile_id.clone(),
+            tile_id: *tile_id,
             tile_size,
             cells: vec![
                 0;
@@ -154,7 +154,7 @@ pub fn generate_water_bodies_processing_tiles_from_worldcover_ones(
             );
             let downsampled_tile =
                 WaterBodiesProcessingTile::downsample_from_worldcover_tile(
-                    &tile_id, &tile,
+                    &tile_id, tile,
                 );
 
             let tile_file_name = processing_dir
@@ -320,7 +320,7 @@ pub fn color_water_bodies(
 
 #[cfg(test)]
 pub mod tests {
-    use crate::water_bodies::dem_tile_id::DemTileId;
+    use crate::dem_tile_id::DemTileId;
     use crate::water_bodies::water_bodies::{Rect, WaterBodiesProcessingTile};
 
     #[derive(Debug)]
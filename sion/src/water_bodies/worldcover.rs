@@ -1,6 +1,6 @@
+use crate::dem_tile_id::DemTileId;
 use crate::raster16::Raster16;
 use crate::water_bodies::caching::ensure_file_in_cache;
-use crate::water_bodies::dem_tile_id::DemTileId;
 use crate::water_bodies::water_bodies::WaterBodyValue;
 use flate2::read::ZlibDecoder;
 use serde_json::{Map, Value};
@@ -55,7 +55,7 @@ pub fn ensure_world_cover_tile(
         .join(WORLD_COVER_CACHE_DIR)
         .join(format!("{}.tif", tile_id));
     ensure_file_in_cache(
-        &world_cover_tile_download_url(&tile_id),
+        &world_cover_tile_download_url(tile_id),
         &cached_file_name,
     )
 }
@@ -133,7 +133,7 @@ fn decompress_tile_data(
 pub fn read_world_cover_tiff_file(
     world_cover_tiff_file_name: &Path,
 ) -> Result<Vec<Raster16>, String> {
-    let file = match File::open(&world_cover_tiff_file_name) {
+    let file = match File::open(world_cover_tiff_file_name) {
         Ok(file) => file,
         Err(e) => return Err(format!("Failed to open TIFF file: {}", e)),
     };
@@ -158,8 +158,7 @@ pub fn read_world_cover_tiff_file(
             WORLD_COVER_BITMAP_SIZE,
             image_width,
             image_height
-        )
-        .into());
+        ));
     }
 
     match decoder.get_tag_u32(Tag::PlanarConfiguration) {
@@ -168,8 +167,7 @@ pub fn read_world_cover_tiff_file(
                 return Err(format!(
                     "Expected CONTIG/Chunky planar configuration, but got {:?}",
                     v
-                ))
-                .into();
+                ));
             }
         }
         Err(e) => {
@@ -206,7 +204,7 @@ pub fn read_world_cover_tiff_file(
     let tiles_per_row = (image_width + tile_width - 1) / tile_width;
     let tiles_per_column = (image_height + tile_height - 1) / tile_height;
 
-    let file_for_image_data = match File::open(&world_cover_tiff_file_name) {
+    let file_for_image_data = match File::open(world_cover_tiff_file_name) {
         Ok(file) => file,
         Err(e) => return Err(format!("Failed to open TIFF file: {}", e)),
     };
@@ -0,0 +1,130 @@
+use crate::dem_tile_id::BoundingBox;
+use crate::errors::SionError;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// The ID of a Web Mercator (XYZ) map tile, like the downloaded
+/// Terrain-RGB and Terrarium tiles: the zoom level and the tile's column and
+/// row, counted from the map's north-west corner.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WebTileId {
+    pub zoom: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl WebTileId {
+    /// The deepest supported zoom level.
+    pub const MAX_ZOOM: u8 = 30;
+
+    /// Creates the ID of the tile.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tile is not on the map, see `try_new`.
+    pub fn new(zoom: u8, x: u32, y: u32) -> Self {
+        WebTileId::try_new(zoom, x, y)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Creates the ID of the tile, or returns
+    /// `SionError::InvalidWebTileId` if the zoom level is above `MAX_ZOOM`
+    /// or the column or row is not below `2^zoom`.
+    pub fn try_new(zoom: u8, x: u32, y: u32) -> Result<Self, SionError> {
+        if zoom <= WebTileId::MAX_ZOOM && x < 1 << zoom && y < 1 << zoom {
+            Ok(WebTileId { zoom, x, y })
+        } else {
+            Err(SionError::InvalidWebTileId { zoom, x, y })
+        }
+    }
+
+    /// The number of tiles in each row (and column) at the tile's zoom
+    /// level.
+    pub fn tiles_per_side(&self) -> u32 {
+        1 << self.zoom
+    }
+
+    /// The tile's extent in degrees.
+    pub fn bounding_box(&self) -> BoundingBox {
+        let tiles = self.tiles_per_side() as f64;
+        let lon = |x: u32| x as f64 / tiles * 360. - 180.;
+        let lat = |y: u32| {
+            (PI * (1. - 2. * y as f64 / tiles))
+                .sinh()
+                .atan()
+                .to_degrees()
+        };
+
+        BoundingBox {
+            min_lon: lon(self.x),
+            min_lat: lat(self.y + 1),
+            max_lon: lon(self.x + 1),
+            max_lat: lat(self.y),
+        }
+    }
+
+    /// The coordinates (longitude and latitude, in degrees) of the pixel's
+    /// center, with the tile rendered at `tile_size` pixels per side.
+    pub fn pixel_center(&self, tile_size: u32, x: u32, y: u32) -> (f64, f64) {
+        let world_size = self.tiles_per_side() as f64 * tile_size as f64;
+        let world_x = (self.x as f64 * tile_size as f64) + x as f64 + 0.5;
+        let world_y = (self.y as f64 * tile_size as f64) + y as f64 + 0.5;
+
+        let lon = world_x / world_size * 360. - 180.;
+        let lat = (PI * (1. - 2. * world_y / world_size))
+            .sinh()
+            .atan()
+            .to_degrees();
+        (lon, lat)
+    }
+}
+
+impl fmt::Display for WebTileId {
+    /// Formats the ID as `zoom/x/y`, the way the tile URLs do.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.zoom, self.x, self.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_eq_approx;
+
+    #[test]
+    fn tiles_cover_the_mercator_map() {
+        let world = WebTileId::new(0, 0, 0).bounding_box();
+        assert_eq!((world.min_lon, world.max_lon), (-180., 180.));
+        assert_eq_approx(world.max_lat, 85.0511287798, 1e-9);
+        assert_eq_approx(world.min_lat, -85.0511287798, 1e-9);
+
+        let north_east = WebTileId::new(1, 1, 0);
+        assert_eq!(north_east.to_string(), "1/1/0");
+        let bounding_box = north_east.bounding_box();
+        assert_eq!((bounding_box.min_lon, bounding_box.max_lon), (0., 180.));
+        assert_eq_approx(bounding_box.min_lat, 0., 1e-12);
+
+        // the pixels' centers of the zoom level 1 tile rendered at 2x2
+        let (lon, lat) = north_east.pixel_center(2, 1, 1);
+        assert_eq!(lon, 135.);
+        assert_eq_approx(lat, (PI / 4.).sinh().atan().to_degrees(), 1e-12);
+    }
+
+    #[test]
+    fn tiles_off_the_map_are_rejected() {
+        assert!(WebTileId::try_new(2, 3, 3).is_ok());
+        for (zoom, x, y) in [(2, 4, 0), (2, 0, 4), (31, 0, 0)] {
+            match WebTileId::try_new(zoom, x, y) {
+                Err(error @ SionError::InvalidWebTileId { .. }) => assert_eq!(
+                    error.to_string(),
+                    format!(
+                        "Invalid web tile: {}/{}/{} (expected the zoom in \
+                        0..=30 and the column and row below 2^zoom)",
+                        zoom, x, y
+                    )
+                ),
+                _ => panic!("Expected an InvalidWebTileId error"),
+            }
+        }
+    }
+}